@@ -0,0 +1,128 @@
+use arena::{AnalysisLine, Engine, Game, TimeControl, polyglot_key};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use queenfish::board::Board;
+use queenfish::board::bishop_magic::init_bishop_magics;
+use queenfish::board::rook_magic::init_rook_magics;
+use std::collections::HashSet;
+use std::hint::black_box;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+const PLIES: [usize; 3] = [50, 100, 200];
+
+// A legal line of `plies` moves that never repeats a position and never ends the game, so a game
+// replaying it runs until `with_max_plies` stops it.
+fn long_line(plies: usize) -> Vec<String> {
+    let mut board = Board::new();
+    let mut seen = HashSet::from([polyglot_key(&board)]);
+    let mut line = Vec::new();
+    while line.len() < plies {
+        let moves = board.generate_moves();
+        let next = (0..moves.len())
+            .map(|index| moves[(index + line.len() * 7) % moves.len()])
+            .find(|&mv| {
+                let mut after = board.clone();
+                after.make_move(mv);
+                !seen.contains(&polyglot_key(&after)) && !after.generate_moves().is_empty()
+            })
+            .expect("ran out of fresh positions");
+        board.make_move(next);
+        seen.insert(polyglot_key(&board));
+        line.push(next.to_uci());
+    }
+    line
+}
+
+// Serves an engine over local TCP that answers every `go` at once with the next move of `line`,
+// so a game's time is the arena's own overhead plus the loopback round trips.
+fn instant_engine(line: Arc<Vec<String>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let line = line.clone();
+            thread::spawn(move || {
+                stream.set_nodelay(true).ok();
+                let mut writer = stream.try_clone().unwrap();
+                let mut ply = 0;
+                for command in BufReader::new(stream).lines().map_while(Result::ok) {
+                    let reply = match command.split_whitespace().next() {
+                        Some("uci") => "id name Instant\nuciok\n".to_string(),
+                        Some("isready") => "readyok\n".to_string(),
+                        Some("position") => {
+                            ply = command
+                                .split_whitespace()
+                                .skip_while(|&token| token != "moves")
+                                .count()
+                                .saturating_sub(1);
+                            continue;
+                        }
+                        Some("go") => format!(
+                            "info depth 1 score cp 0 nodes 1 time 0 pv {0}\nbestmove {0}\n",
+                            line[ply]
+                        ),
+                        Some("quit") => break,
+                        _ => continue,
+                    };
+                    if writer.write_all(reply.as_bytes()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    address
+}
+
+fn game_overhead(c: &mut Criterion) {
+    init_bishop_magics();
+    init_rook_magics();
+    let line = Arc::new(long_line(PLIES[PLIES.len() - 1]));
+    let white = Engine::remote(&instant_engine(line.clone()), "White");
+    let black = Engine::remote(&instant_engine(line), "Black");
+    let play = |plies: usize| {
+        let result = Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(1000))
+            .with_max_plies(Some(plies))
+            .play();
+        assert_eq!(result.moves_list().len(), plies);
+    };
+
+    // per-ply cost should stay flat as games get longer; growth means quadratic work somewhere
+    for plies in PLIES {
+        let start = Instant::now();
+        play(plies);
+        println!(
+            "{}-ply game: {:?} per ply",
+            plies,
+            start.elapsed() / plies as u32
+        );
+    }
+
+    let mut group = c.benchmark_group("game overhead");
+    group.sample_size(20);
+    for plies in PLIES {
+        group.throughput(Throughput::Elements(plies as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(plies), &plies, |b, &plies| {
+            b.iter(|| play(plies))
+        });
+    }
+    group.finish();
+
+    c.bench_function("parse bestmove", |b| {
+        b.iter(|| AnalysisLine::new(black_box("bestmove e2e4 ponder e7e5".to_string())))
+    });
+    c.bench_function("parse info", |b| {
+        b.iter(|| {
+            AnalysisLine::new(black_box(
+                "info depth 24 seldepth 33 multipv 1 score cp 31 nodes 4218843 nps 1405000 hashfull 412 tbhits 0 time 3003 pv e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6"
+                    .to_string(),
+            ))
+        })
+    });
+}
+
+criterion_group!(benches, game_overhead);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+use arena::MoveCache;
+use criterion::{Criterion, criterion_group, criterion_main};
+use queenfish::board::Board;
+use queenfish::board::bishop_magic::init_bishop_magics;
+use queenfish::board::rook_magic::init_rook_magics;
+use std::hint::black_box;
+
+// What the GUI does when every square is clicked once in the same position: each click needs
+// the legal moves.
+fn clicks_uncached(board: &Board) -> usize {
+    (0..64)
+        .map(|square| {
+            board
+                .generate_moves()
+                .iter()
+                .filter(|mv| mv.from() == square)
+                .count()
+        })
+        .sum()
+}
+
+fn clicks_cached(board: &Board, move_cache: &mut MoveCache) -> usize {
+    (0..64)
+        .map(|square| {
+            move_cache
+                .moves(board)
+                .iter()
+                .filter(|mv| mv.from() == square)
+                .count()
+        })
+        .sum()
+}
+
+fn move_generation(c: &mut Criterion) {
+    init_bishop_magics();
+    init_rook_magics();
+    let board = Board::new();
+
+    let mut move_cache = MoveCache::default();
+    clicks_cached(&board, &mut move_cache);
+    println!(
+        "move generator calls for 64 clicks: {} uncached, {} cached",
+        64,
+        move_cache.generated()
+    );
+
+    c.bench_function("64 clicks, uncached", |b| {
+        b.iter(|| clicks_uncached(black_box(&board)))
+    });
+    c.bench_function("64 clicks, cached", |b| {
+        b.iter(|| {
+            let mut move_cache = MoveCache::default();
+            clicks_cached(black_box(&board), &mut move_cache)
+        })
+    });
+}
+
+criterion_group!(benches, move_generation);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+use arena::PositionCommand;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts allocations, so the saving shows up as a number and not only as time.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const PLIES: usize = 300;
+
+// How `Game` built the command before: the whole move list joined again every ply.
+fn game_rejoined(moves: &[String]) -> usize {
+    (0..=moves.len())
+        .map(|ply| {
+            let moves = moves[..ply].iter().map(String::as_str).collect::<Vec<_>>();
+            let position = "position startpos".to_string();
+            let command = if moves.is_empty() {
+                format!("{}\n", position)
+            } else {
+                format!("{} moves {}\n", position, moves.join(" "))
+            };
+            command.len()
+        })
+        .sum()
+}
+
+fn game_cached(moves: &[String]) -> usize {
+    let mut position = PositionCommand::new(None);
+    let mut total = position.command(None).len();
+    for mv in moves {
+        position.push(mv);
+        total += position.command(None).len();
+    }
+    total
+}
+
+fn allocations(f: impl FnOnce() -> usize) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn position_commands(c: &mut Criterion) {
+    // the moves only need to look like uci, nothing replays them
+    let moves = (0..PLIES)
+        .map(|ply| ["g1f3", "g8f6", "f3g1", "f6g8"][ply % 4].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(game_rejoined(&moves), game_cached(&moves));
+    println!(
+        "allocations for a {}-ply game: {} rejoined, {} cached",
+        PLIES,
+        allocations(|| game_rejoined(&moves)),
+        allocations(|| game_cached(&moves))
+    );
+
+    c.bench_function("300 plies, rejoined", |b| {
+        b.iter(|| game_rejoined(black_box(&moves)))
+    });
+    c.bench_function("300 plies, cached", |b| {
+        b.iter(|| game_cached(black_box(&moves)))
+    });
+}
+
+criterion_group!(benches, position_commands);
+criterion_main!(benches);
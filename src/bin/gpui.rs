@@ -3,7 +3,7 @@ use arena::gui::input::{
     Backspace, Copy, Cut, Delete, End, Home, InputController, InputField, Left, Paste, Right,
     SelectAll, SelectLeft, SelectRight, ShowCharacterPalette,
 };
-use arena::{AnalysisLine, Engine, gui};
+use arena::{AnalysisLine, Engine, MoveCache, gui};
 use gpui::{
     App, Application, AsyncApp, Bounds, Context, Corner, ElementId, Focusable,
     KeyBinding, MouseButton, SharedString,  TitlebarOptions, Window,
@@ -17,6 +17,7 @@ use rfd::FileDialog;
 use std::{collections::HashSet};
 use arena::gui::fen_window::FenWindow;
 use arena::gui::state::SharedState;
+use arena::gui::theme::PieceTheme;
 use arena::gui::components::{board_square, logo_button, menu_button, seperator};
 use arena::gui::state::EnginesServices;
 use arena::gui::engine_options::EngineOptionsWindow;
@@ -24,6 +25,7 @@ use arena::gui::engine_options::EngineOptionsWindow;
 
 struct Board {
     board: QueenFishBoard,
+    legal_moves: MoveCache,
     focus_handle: gpui::FocusHandle,
     available_moves: Vec<(usize, usize)>,
     is_analyzing: bool,
@@ -51,7 +53,7 @@ impl Board {
             }
         }
 
-        let moves = self.board.generate_moves();
+        let moves = self.legal_moves.moves(&self.board).to_vec();
         let available_squares = self
             .available_moves
             .iter()
@@ -102,6 +104,7 @@ impl Board {
 
         let element = Board {
             board,
+            legal_moves: MoveCache::default(),
             focus_handle,
             available_moves: Vec::new(),
             // analysis: Vec::new(),
@@ -120,6 +123,7 @@ impl Board {
 
     pub fn reset_board(&mut self) {
         self.board = QueenFishBoard::new();
+        self.legal_moves.invalidate();
         self.available_moves = Vec::new();
         self.current_move_index = 0;
         self.make_move_history = Vec::new();
@@ -129,6 +133,7 @@ impl Board {
 
     pub fn load_from_fen(&mut self, fen: String) {
         self.board.load_from_fen(fen.as_str());
+        self.legal_moves.invalidate();
     } //
 
     pub fn play_move(&mut self, mv: String) {
@@ -140,6 +145,7 @@ impl Board {
         self.is_analyzing = false;
         let mv = Move::from_uci(mv.as_str(), &(self.board));
         let unmakemove = self.board.make_move(mv);
+        self.legal_moves.invalidate();
         self.make_move_history.push(mv);
         self.unmake_move_history.push(unmakemove);
         self.current_move_index += 1;
@@ -152,6 +158,7 @@ impl Board {
         self.is_analyzing = false;
         let mv = self.make_move_history[self.current_move_index];
         self.board.make_move(mv);
+        self.legal_moves.invalidate();
         self.current_move_index += 1;
     } //
 
@@ -163,6 +170,7 @@ impl Board {
         self.is_analyzing = false;
         let unmake = self.unmake_move_history[current_move_index];
         self.board.unmake_move(unmake);
+        self.legal_moves.invalidate();
         self.current_move_index -= 1;
     } //
 
@@ -234,7 +242,7 @@ impl Render for Board {
                             )
                             // .when(!self.is_analyzing, |this| this.hidden())
                             .children(engine.analysis.iter().rev().map(|x| match x {
-                                AnalysisLine::Move(m) => {
+                                AnalysisLine::Move(m, _) => {
                                     return div()
                                         .flex()
                                         .flex_row()
@@ -243,6 +251,15 @@ impl Render for Board {
                                         .child(format!("Best Move: {}", m))
                                         .text_color(gpui::white());
                                 }
+                                AnalysisLine::Message(message) => {
+                                    return div()
+                                        .flex()
+                                        .flex_row()
+                                        .gap_2()
+                                        .items_center()
+                                        .child(message.clone())
+                                        .text_color(rgb(gui::colors::MUTED));
+                                }
                                 AnalysisLine::Depth {
                                     depth,
                                     score,
@@ -250,6 +267,7 @@ impl Render for Board {
                                     nodes,
                                     selective_depth,
                                     time,
+                                    ..
                                 } => {
                                     let score_text: Option<String> = match score {
                                         Some(Score::Cp(cp)) => Some(format!("{} cp", cp)),
@@ -326,6 +344,7 @@ impl Render for Board {
             queenfish::board::Turn::BLACK => self.board.black_king_sq(),
         };
 
+        let piece_theme = cx.global::<SharedState>().piece_theme.clone();
         let mut squares = (0..64)
             .collect::<Vec<_>>()
             .chunks(8)
@@ -333,7 +352,7 @@ impl Render for Board {
             .flatten()
             .copied()
             .map(|i| {
-                let mut element = board_square(i, self.selected_square, self.board.piece_at[i], self.is_board_flipped, is_king_in_check, current_turn_king_sq, &self.available_moves, winning_tag_index, losing_tag_index, draw_tag_index);
+                let mut element = board_square(i, self.selected_square, self.board.piece_at[i], &piece_theme, self.is_board_flipped, is_king_in_check, current_turn_king_sq, &self.available_moves, winning_tag_index, losing_tag_index, draw_tag_index);
 
                 element = element.on_mouse_down(
                     gpui::MouseButton::Left,
@@ -416,6 +435,20 @@ impl Render for Board {
                             .detach();
                         })),
                     )
+                    .child(menu_button("Piece Theme").on_any_mouse_down(cx.listener(
+                        |_, _, _, cx| {
+                            let task = cx.spawn(async move |_, cx: &mut AsyncApp| {
+                                if let Some(dir) = FileDialog::new().pick_folder() {
+                                    let _ = cx.update(move |cx| {
+                                        cx.global_mut::<SharedState>().piece_theme =
+                                            PieceTheme::new(dir);
+                                        cx.refresh_windows();
+                                    });
+                                }
+                            });
+                            task.detach();
+                        },
+                    )))
                     .child(
                         menu_button("Engines")
                             .on_mouse_down(
@@ -499,9 +532,10 @@ impl Render for Board {
                                                                             .pick_file();
                                                                         if let Some(file_path) = file_path {
                                                                             let _ = cx.update(move |cx| {
+                                                                                // named by its own `id name`
                                                                                 let new_engine = Engine::new(
                                                                                     file_path.to_str().unwrap(),
-                                                                                    file_path.file_name().unwrap().to_str().unwrap(),
+                                                                                    "",
                                                                                 );
                                                                                 cx.global_mut::<SharedState>().engines.engines.push(new_engine);
                                                                             });
@@ -0,0 +1,134 @@
+use crate::engine::Score;
+use crate::game::GameResult;
+use crate::san::moves_to_san;
+use queenfish::board::Board;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const HEADER: &str = "game,ply,side,uci,san,time_ms,score,depth,nodes";
+
+/// Writes one CSV row per ply of each game, for spreadsheets and plotting tools: game index,
+/// ply, side, the move in uci and SAN, the search time in ms, the score the mover reported (cp,
+/// or `#n` for mate, from its own side), the depth and the nodes. Columns that don't apply, as
+/// for opening and book moves, are left empty.
+///
+/// Rows are written and flushed a game at a time, so a file can be followed while a tournament
+/// runs.
+pub struct CsvExporter<W: Write> {
+    writer: W,
+}
+
+impl CsvExporter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        CsvExporter::new(BufWriter::new(File::create(path)?))
+    } //
+}
+
+impl<W: Write> CsvExporter<W> {
+    /// Starts the output with the header row.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(CsvExporter { writer })
+    } //
+
+    pub fn write_game(&mut self, game_index: usize, game: &GameResult) -> io::Result<()> {
+        let mut board = Board::new();
+        if let Some(fen) = game.start_fen() {
+            board.load_from_fen(fen);
+        }
+        // the side to move at the start, so a game from a black-to-move FEN begins with black
+        let black_first = game
+            .start_fen()
+            .and_then(|fen| fen.split_whitespace().nth(1))
+            == Some("b");
+        let sans = moves_to_san(&board, game.moves_list());
+        for (ply, (uci, san)) in game.moves_list().iter().zip(&sans).enumerate() {
+            let side = if (ply % 2 == 1) != black_first {
+                "black"
+            } else {
+                "white"
+            };
+            let score = match game.scores().get(ply).copied().flatten() {
+                Some(Score::Cp(cp)) => cp.to_string(),
+                Some(Score::Mate(moves)) => format!("#{}", moves),
+                None => String::new(),
+            };
+            let stats = game.move_stats().get(ply).copied().flatten();
+            let field = |value: Option<String>| value.unwrap_or_default();
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{}",
+                game_index,
+                ply + 1,
+                side,
+                uci,
+                san,
+                field(stats.map(|stats| stats.time.as_millis().to_string())),
+                score,
+                field(
+                    stats
+                        .and_then(|stats| stats.depth)
+                        .map(|depth| depth.to_string())
+                ),
+                field(
+                    stats
+                        .and_then(|stats| stats.nodes)
+                        .map(|nodes| nodes.to_string())
+                ),
+            )?;
+        }
+        self.writer.flush()
+    } //
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    } //
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::game::{Game, TimeControl};
+    use crate::mock_engine::MockEngine;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    #[test]
+    fn every_ply_becomes_a_row() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let engine = |name: &str, mv: &str| {
+            MockEngine::new(name)
+                .with_moves(&[mv])
+                .with_go(r#"echo "info depth 9 score cp 25 nodes 1200 pv $1"; echo "bestmove $1"; shift"#)
+                .build()
+        };
+        let result = Game::new(
+            engine("White", "g2g4"),
+            engine("Black", "d8h4"),
+            TimeControl::TimePerMove(10),
+        )
+        .with_opening_moves(&["f2f3".to_string(), "e7e5".to_string()])
+        .play();
+
+        let mut exporter = CsvExporter::new(Vec::new()).unwrap();
+        exporter.write_game(3, &result).unwrap();
+        let csv = String::from_utf8(exporter.into_inner()).unwrap();
+        let rows = csv.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0], HEADER);
+        assert_eq!(rows[1], "3,1,white,f2f3,f3,,,,");
+        assert_eq!(rows[2], "3,2,black,e7e5,e5,,,,");
+        for (row, prefix, suffix) in [
+            (rows[3], "3,3,white,g2g4,g4,", ",25,9,1200"),
+            (rows[4], "3,4,black,d8h4,Qh4#,", ",25,9,1200"),
+        ] {
+            assert!(row.starts_with(prefix) && row.ends_with(suffix), "{}", row);
+            let time_ms = &row[prefix.len()..row.len() - suffix.len()];
+            assert!(time_ms.parse::<u64>().is_ok(), "{}", row);
+        }
+    }
+}
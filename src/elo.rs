@@ -0,0 +1,149 @@
+// Logistic Elo model: a player rated `d` points above the opponent is expected to score
+// 1 / (1 + 10^(-d/400)).
+
+// slope of the expected score per Elo point, divided by p(1 - p)
+const LOGISTIC_SCALE: f64 = std::f64::consts::LN_10 / 400.0;
+
+/// Expected score, between 0 and 1, against an opponent `elo_diff` points weaker.
+pub fn expected_score(elo_diff: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo_diff / 400.0))
+} //
+
+/// Elo difference that makes `score` (strictly between 0 and 1) the expected score.
+pub fn elo_diff(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+} //
+
+/// Maximum likelihood rating of a player from `(opponent rating, score)` games, with the 95%
+/// margin of that estimate. A perfect or zero score has no finite estimate, so the rating is
+/// kept within 800 points of the opponents. None without games.
+pub fn estimate_rating(games: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let lowest = games.iter().map(|(rating, _)| *rating).reduce(f64::min)?;
+    let highest = games.iter().map(|(rating, _)| *rating).reduce(f64::max)?;
+    let surplus = |rating: f64| {
+        games
+            .iter()
+            .map(|(opponent, score)| score - expected_score(rating - opponent))
+            .sum::<f64>()
+    };
+
+    // the surplus only falls as the rating rises, so bisect for its zero
+    let (mut low, mut high) = (lowest - 800.0, highest + 800.0);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if surplus(mid) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let rating = (low + high) / 2.0;
+
+    let information = games
+        .iter()
+        .map(|(opponent, _)| {
+            let p = expected_score(rating - opponent);
+            p * (1.0 - p) * LOGISTIC_SCALE * LOGISTIC_SCALE
+        })
+        .sum::<f64>();
+    Some((rating, 1.96 / information.sqrt()))
+} //
+
+/// Elo difference implied by a match's wins, draws and losses, with the 95% margin worked out
+/// from the per-game variance of the score. A perfect or zero score has no finite estimate, so
+/// it gives an infinite difference and margin, as does a bound of the interval reaching 0% or
+/// 100% for the margin. Without games the difference is 0, with an infinite margin.
+pub fn match_elo(wins: u64, draws: u64, losses: u64) -> (f64, f64) {
+    let games = (wins + draws + losses) as f64;
+    if games == 0.0 {
+        return (0.0, f64::INFINITY);
+    }
+    let score = (wins as f64 + draws as f64 / 2.0) / games;
+    let variance = (wins as f64 * (1.0 - score).powi(2)
+        + draws as f64 * (0.5 - score).powi(2)
+        + losses as f64 * score.powi(2))
+        / games;
+    let deviation = 1.96 * (variance / games).sqrt();
+    // elo_diff is infinite at 0 and 1 rather than dividing by zero
+    let elo = |score: f64| match score {
+        score if score <= 0.0 => f64::NEG_INFINITY,
+        score if score >= 1.0 => f64::INFINITY,
+        score => elo_diff(score),
+    };
+    let margin = if score == 0.0 || score == 1.0 {
+        f64::INFINITY
+    } else {
+        (elo(score + deviation) - elo(score - deviation)) / 2.0
+    };
+    (elo(score), margin)
+} //
+
+/// Probability that the player with `wins` against `losses` is the stronger one; draws say
+/// nothing either way. 0.5 without decisive games.
+pub fn likelihood_of_superiority(wins: u64, losses: u64) -> f64 {
+    if wins + losses == 0 {
+        return 0.5;
+    }
+    let z = (wins as f64 - losses as f64) / (2.0 * (wins + losses) as f64).sqrt();
+    0.5 * (1.0 + erf(z))
+} //
+
+// Abramowitz and Stegun 7.1.26, accurate to 1.5e-7.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expected_score_and_elo_diff_invert_each_other() {
+        assert_eq!(expected_score(0.0), 0.5);
+        assert!((expected_score(400.0) - 10.0 / 11.0).abs() < 1e-12);
+        assert!((elo_diff(0.75) - 190.85).abs() < 0.01);
+        assert!((elo_diff(expected_score(-123.0)) + 123.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rating_is_estimated_from_results() {
+        assert_eq!(estimate_rating(&[]), None);
+
+        let (rating, _) = estimate_rating(&[(1500.0, 1.0), (1500.0, 0.0)]).unwrap();
+        assert!((rating - 1500.0).abs() < 1e-6);
+
+        // 3 out of 4 against 1500 is a 75% score
+        let games = [(1500.0, 1.0), (1500.0, 1.0), (1500.0, 0.5), (1500.0, 0.5)];
+        let (rating, margin) = estimate_rating(&games).unwrap();
+        assert!((rating - 1690.85).abs() < 0.01);
+
+        // more of the same games, a narrower margin
+        let (_, narrower) = estimate_rating(&games.repeat(4)).unwrap();
+        assert!((narrower - margin / 2.0).abs() < 1e-6);
+
+        let (rating, _) = estimate_rating(&[(2000.0, 1.0)]).unwrap();
+        assert!((rating - 2800.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn match_results_give_elo_and_los() {
+        // 60 wins, 20 draws, 20 losses: a 70% score
+        let (elo, margin) = match_elo(60, 20, 20);
+        assert!((elo - elo_diff(0.7)).abs() < 1e-9);
+        assert!((margin - 66.01).abs() < 0.01);
+        assert!((likelihood_of_superiority(60, 20) - 0.999_996_1).abs() < 1e-6);
+
+        assert_eq!(match_elo(0, 0, 0), (0.0, f64::INFINITY));
+        assert_eq!(match_elo(10, 0, 0), (f64::INFINITY, f64::INFINITY));
+        assert_eq!(match_elo(0, 0, 3).0, f64::NEG_INFINITY);
+        let (elo, margin) = match_elo(0, 8, 0);
+        assert_eq!((elo, margin), (0.0, 0.0));
+        assert_eq!(likelihood_of_superiority(0, 0), 0.5);
+        assert!((likelihood_of_superiority(5, 5) - 0.5).abs() < 1e-6);
+    }
+}
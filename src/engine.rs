@@ -1,35 +1,130 @@
-use std::io::{BufRead, BufReader, Write};
-use std::path::Path;
+use crate::error::EngineError;
+use crate::fen::validate_fen;
+use crate::game::{DEFAULT_SEARCH_TIMEOUT, TimeControl, wait_for_bestmove};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
+const DEFAULT_SPAWN_RETRIES: u32 = 3;
+const DEFAULT_SPAWN_BACKOFF: Duration = Duration::from_millis(50);
+const DEFAULT_MAX_ANALYSIS_LINES: usize = 256;
+const DEFAULT_MAX_PV_LENGTH: usize = 32;
+// How long an engine assumed to speak UCI may stay quiet before the handshake is given up on.
+const ASSUMED_UCI_GRACE: Duration = Duration::from_millis(500);
+// Engine processes and connections that may be live at once, see `set_max_processes`.
+const DEFAULT_MAX_PROCESSES: usize = 256;
+// Processes a position in `Engine::analyze_batch` may take down before it is given up on.
+const BATCH_ATTEMPTS: u32 = 2;
+// Commands remembered per process for spotting an engine that echoes them back.
+const RECENT_COMMANDS: usize = 8;
+// How long `EngineHandle::sync` waits for `readyok`; allocating a large hash or loading a network
+// can take a while.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+// How long a process told to `quit` when its handle goes away has to exit before it is killed.
+const QUIT_GRACE: Duration = Duration::from_millis(100);
+// Stderr lines kept per process; an engine dying usually explains itself in the last few.
+const STDERR_LINES: usize = 100;
+// How long a closed process's stderr is given to be read to the end.
+const STDERR_GRACE: Duration = Duration::from_millis(50);
+
+// Keywords that can follow a `pv` on an info line and end it.
+const INFO_KEYWORDS: [&str; 14] = [
+    "depth",
+    "seldepth",
+    "time",
+    "nodes",
+    "multipv",
+    "score",
+    "currmove",
+    "currmovenumber",
+    "hashfull",
+    "nps",
+    "tbhits",
+    "cpuload",
+    "refutation",
+    "currline",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Score {
     Cp(i32),
     Mate(i32),
 }
 
+/// What an engine made of a single position, see `Engine::analyze`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisResult {
+    pub best_move: String,
+    pub ponder: Option<String>,
+    pub score: Option<Score>, // from the side to move, as UCI reports it
+    pub depth: Option<u32>,
+    pub pv: Vec<String>, // of the last info line that had one
+}
+
+impl AnalysisResult {
+    /// Whether the engine settled on a move other than the one its last pv began with. Usually
+    /// harmless (a search stopped mid-iteration), but it can point at a bug in the engine.
+    pub fn best_move_left_pv(&self) -> bool {
+        self.pv
+            .first()
+            .is_some_and(|pv_move| *pv_move != self.best_move)
+    } //
+}
+
 #[derive(Debug)]
 pub enum AnalysisLine {
-    Move(String),
+    Move(String, Option<String>), // best move, ponder move
+    Message(String),
     Depth {
         depth: Option<String>,
         selective_depth: Option<String>,
         score: Option<Score>,
         best_move: Option<String>,
         nodes: Option<String>,
+        nps: Option<String>,
         time: Option<String>,
+        pv: Vec<String>,
     },
 }
 impl AnalysisLine {
-    fn new(line: String) -> Option<AnalysisLine> {
+    pub fn new(line: String) -> Option<AnalysisLine> {
         let line = line.trim().replace("\n", "");
-        let args = line.split_whitespace().collect::<Vec<_>>();
+        let mut args = line.split_whitespace().collect::<Vec<_>>();
         if line.starts_with("bestmove") {
-            return Some(AnalysisLine::Move(args[1].to_string()));
+            let ponder = args
+                .iter()
+                .position(|str| str == &"ponder")
+                .and_then(|ponder_index| args.get(ponder_index + 1))
+                .map(|ponder| ponder.to_string());
+            // some engines send a bare `bestmove` when they have nothing to play; read it as the
+            // null move
+            let best_move = args
+                .get(1)
+                .filter(|&&token| token != "ponder")
+                .unwrap_or(&"0000");
+            return Some(AnalysisLine::Move(best_move.to_string(), ponder));
         } else if line.starts_with("info") {
+            // `string` swallows the rest of the line, so nothing after it is data
+            if let Some(string_index) = args.iter().position(|str| str == &"string") {
+                if string_index == 1 {
+                    return Some(AnalysisLine::Message(args[2..].join(" ")));
+                }
+                args.truncate(string_index);
+            }
+
             let mut depth = None;
             let mut nodes = None;
             let mut best_move = None;
@@ -62,11 +157,17 @@ impl AnalysisLine {
                     nodes = Some(nodes_str.to_string());
                 }
             }
+            let mut pv = Vec::new();
             let best_move_index = args.iter().position(|str| str == &"pv");
             if let Some(best_move_index) = best_move_index {
                 if let Some(best_move_str) = args.get(best_move_index + 1) {
                     best_move = Some(best_move_str.to_string());
                 }
+                pv = args[best_move_index + 1..]
+                    .iter()
+                    .take_while(|token| !INFO_KEYWORDS.contains(token))
+                    .map(|token| token.to_string())
+                    .collect();
             }
             let time_index = args.iter().position(|str| str == &"time");
             if let Some(time_index) = time_index {
@@ -74,21 +175,49 @@ impl AnalysisLine {
                     time = Some(time_str.to_string());
                 }
             }
+            let value_of = |keyword: &str| {
+                let index = args.iter().position(|str| *str == keyword)?;
+                args.get(index + 1).map(|value| value.to_string())
+            };
+            let selective_depth = value_of("seldepth");
+            let nps = value_of("nps");
 
             return Some(AnalysisLine::Depth {
                 depth: depth,
-                selective_depth: None,
+                selective_depth,
                 score,
                 best_move: best_move,
                 nodes,
+                nps,
                 time,
+                pv,
             });
         }
         None
     }
 }
 
-#[derive(Clone)]
+/// `mv` as UCI spells it: in lower case, so the `e7e8Q` of a non-conforming engine reads as
+/// `e7e8q`. None unless it is two squares, optionally followed by a promotion to a queen, rook,
+/// bishop or knight.
+pub fn canonical_uci(mv: &str) -> Option<String> {
+    let mv = mv.to_ascii_lowercase();
+    let is_square = |square: &[u8]| matches!(square, [b'a'..=b'h', b'1'..=b'8']);
+    let bytes = mv.as_bytes();
+    let is_move = match bytes.len() {
+        4 => is_square(&bytes[..2]) && is_square(&bytes[2..]),
+        5 => {
+            is_square(&bytes[..2])
+                && is_square(&bytes[2..4])
+                && matches!(bytes[4], b'q' | b'r' | b'b' | b'n')
+        }
+        _ => false,
+    };
+    is_move.then_some(mv)
+} //
+
+/// An option an engine advertises in its reply to `uci`, with its default value.
+#[derive(Debug, Clone, PartialEq)]
 pub enum EngineOption {
     CHECK {
         name: String,
@@ -100,177 +229,674 @@ pub enum EngineOption {
         min: Option<i32>,
         max: Option<i32>,
     },
+    COMBO {
+        name: String,
+        value: String,
+        choices: Vec<String>, // the `var`s, in the engine's order
+    },
+    STRING {
+        name: String,
+        value: String, // empty for a `<empty>` default
+    },
+    BUTTON {
+        name: String,
+    },
 } //
 
+impl EngineOption {
+    pub fn name(&self) -> &str {
+        match self {
+            EngineOption::CHECK { name, .. }
+            | EngineOption::SPIN { name, .. }
+            | EngineOption::COMBO { name, .. }
+            | EngineOption::STRING { name, .. }
+            | EngineOption::BUTTON { name } => name,
+        }
+    } //
+
+    /// Reads an `option name ... type ...` line. None for other lines, unknown types, and check,
+    /// spin or combo options without a usable default.
+    pub fn parse(line: &str) -> Option<Self> {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        if args.first() != Some(&"option") {
+            return None;
+        }
+        // names may contain spaces and run up to `type`
+        let name_index = args.iter().position(|w| w == &"name")?;
+        let type_index = args.iter().position(|w| w == &"type")?;
+        let name = args.get(name_index + 1..type_index)?.join(" ");
+        let option_type = *args.get(type_index + 1)?;
+        let fields = &args[type_index + 2..];
+        // the words after the field name at `index`, up to the next field; string values and
+        // combo choices may contain spaces too
+        let value_after = |index: usize| {
+            let end = fields[index + 1..]
+                .iter()
+                .position(|w| matches!(*w, "default" | "min" | "max" | "var"))
+                .map_or(fields.len(), |end| index + 1 + end);
+            let value = fields[index + 1..end].join(" ");
+            if value == "<empty>" {
+                String::new()
+            } else {
+                value
+            }
+        };
+        let field = |field: &str| fields.iter().position(|w| *w == field).map(value_after);
+
+        match option_type {
+            "check" => Some(EngineOption::CHECK {
+                name,
+                value: field("default")?.parse().ok()?,
+            }),
+            "spin" => Some(EngineOption::SPIN {
+                name,
+                value: field("default")?.parse().ok()?,
+                min: field("min").and_then(|min| min.parse().ok()),
+                max: field("max").and_then(|max| max.parse().ok()),
+            }),
+            "combo" => Some(EngineOption::COMBO {
+                name,
+                value: field("default")?,
+                choices: (0..fields.len())
+                    .filter(|&index| fields[index] == "var")
+                    .map(value_after)
+                    .collect(),
+            }),
+            "string" => Some(EngineOption::STRING {
+                name,
+                value: field("default").unwrap_or_default(),
+            }),
+            "button" => Some(EngineOption::BUTTON { name }),
+            _ => None,
+        }
+    } //
+}
+
+/// Where an engine runs: a binary started here and spoken to over its stdin/stdout, or a UCI
+/// server reached over TCP. A remote server must start a fresh engine for every connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineLocation {
+    Local(PathBuf),
+    Remote(String), // host:port
+}
+
 pub struct Engine {
-    pub path: String,
+    pub path: String, // the binary, or host:port for remote engines
+    pub location: EngineLocation,
     pub name: String,
+    pub reported_name: Option<String>, // from the engine's `id name`
+    pub author: Option<String>,        // from the engine's `id author`
+    pub banner: Vec<String>, // anything else printed during the handshake, e.g. a startup banner
     pub engine_options: Vec<EngineOption>,
     pub engine_handle: Option<EngineHandle>,
     pub analysis: Vec<AnalysisLine>,
     pub is_show: bool,
+    pub working_dir: Option<PathBuf>, // engines resolve relative files (nets, books) against this
+    pub debug: bool,                  // send `debug on` to processes spawned for games
+    pub configured_options: Vec<(String, String)>, // (name, value) pairs requested by the user
+    pub spawn_retries: u32,           // extra attempts when starting a process fails
+    pub spawn_backoff: Duration,      // delay before the first retry, doubled after each
+    pub idle_timeout: Option<Duration>, // silence during a search before the engine is pinged
+    pub max_analysis_lines: usize,    // lines kept in `analysis`, the oldest dropped first
+    pub max_pv_length: usize,         // moves kept of each line's pv
+    pub assume_uci: bool,             // don't wait for `uciok`; see `try_new_assuming_uci`
 } //
 
 impl Clone for Engine {
     fn clone(&self) -> Self {
         Engine {
             path: self.path.clone(),
+            location: self.location.clone(),
             name: self.name.clone(),
+            reported_name: self.reported_name.clone(),
+            author: self.author.clone(),
+            banner: self.banner.clone(),
             engine_options: self.engine_options.clone(),
             engine_handle: None,
             analysis: Vec::new(),
             is_show: true,
+            working_dir: self.working_dir.clone(),
+            debug: self.debug,
+            configured_options: self.configured_options.clone(),
+            spawn_retries: self.spawn_retries,
+            spawn_backoff: self.spawn_backoff,
+            idle_timeout: self.idle_timeout,
+            max_analysis_lines: self.max_analysis_lines,
+            max_pv_length: self.max_pv_length,
+            assume_uci: self.assume_uci,
         }
     }
 }
 
 impl Engine {
+    /// An empty `name` is replaced by the engine's own `id name`, or the file name if it has none.
     pub fn new(path: &str, name: &str) -> Self {
+        Engine::try_new(path, name).unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    /// Starting the engine for its handshake is retried as `with_spawn_retry` describes, 3 times
+    /// from 50ms; `try_new_with_spawn_retry` sets another policy from the start.
+    pub fn try_new(path: &str, name: &str) -> Result<Self, EngineError> {
+        Engine::try_new_with_spawn_retry(path, name, DEFAULT_SPAWN_RETRIES, DEFAULT_SPAWN_BACKOFF)
+    } //
+
+    /// Like `try_new`, with the retry policy of `with_spawn_retry` applied to the handshake too.
+    pub fn try_new_with_spawn_retry(
+        path: &str,
+        name: &str,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Self, EngineError> {
+        Engine::try_local(path, name, false, (retries, backoff))
+    } //
+
+    /// For engines that play UCI but never send `uciok`. The handshake is skipped and the engine
+    /// is given a short grace to list its options, so it goes by `name` or its file name. Every
+    /// other engine should use `try_new`, which fails on a missing `uciok` rather than guessing.
+    pub fn try_new_assuming_uci(path: &str, name: &str) -> Result<Self, EngineError> {
+        Engine::try_local(
+            path,
+            name,
+            true,
+            (DEFAULT_SPAWN_RETRIES, DEFAULT_SPAWN_BACKOFF),
+        )
+    } //
+
+    // `spawn_retry` is the retries and the first backoff, see `with_spawn_retry`.
+    fn try_local(
+        path: &str,
+        name: &str,
+        assume_uci: bool,
+        spawn_retry: (u32, Duration),
+    ) -> Result<Self, EngineError> {
         let path = Path::new(path);
 
-        if !path.exists() {
-            panic!("Engine path does not exist");
-        } else if !path.is_file() {
-            panic!("Engine path is not a file");
+        if !path.is_file() {
+            return Err(EngineError::NotFound(path.display().to_string()));
         }
-        if let Some(extension) = path.extension() {
-            if extension != "exe" && extension != "" {
-                panic!("Engine file is not an executable");
-            }
+        match path.extension() {
+            None => {}
+            Some(extension) if extension == "exe" || extension.is_empty() => {}
+            _ => return Err(EngineError::NotExecutable(path.display().to_string())),
+        }
+        let path =
+            std::path::absolute(path).map_err(|error| EngineError::NotFound(error.to_string()))?;
+        let working_dir = path.parent().map(Path::to_path_buf);
+        Engine::connect(
+            EngineLocation::Local(path),
+            working_dir,
+            name,
+            assume_uci,
+            spawn_retry,
+        )
+    } //
+
+    /// An engine served over TCP at `address` (`host:port`). An empty `name` is replaced by the
+    /// engine's own `id name`, or the address if it has none.
+    pub fn remote(address: &str, name: &str) -> Self {
+        Engine::try_remote(address, name).unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    pub fn try_remote(address: &str, name: &str) -> Result<Self, EngineError> {
+        Engine::connect(
+            EngineLocation::Remote(address.to_string()),
+            None,
+            name,
+            false,
+            (DEFAULT_SPAWN_RETRIES, DEFAULT_SPAWN_BACKOFF),
+        )
+    } //
+
+    // Runs the UCI handshake once to learn the engine's id, then probes its options. An engine
+    // assumed to speak UCI has no handshake to run, so it keeps no id.
+    fn connect(
+        location: EngineLocation,
+        working_dir: Option<PathBuf>,
+        name: &str,
+        assume_uci: bool,
+        (spawn_retries, spawn_backoff): (u32, Duration),
+    ) -> Result<Self, EngineError> {
+        let id = if assume_uci {
+            EngineId::default()
         } else {
-            panic!("Engine file has no extension");
-        }
-
-        let mut engine_process = Command::new(path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("Failed to start engine process");
-
-        let mut stdin = engine_process
-            .stdin
-            .take()
-            .expect("Failed to take engine stdin");
-        let mut stdout = BufReader::new(
-            engine_process
-                .stdout
-                .take()
-                .expect("Failed to take engine stdout"),
-        );
+            let _slot = PROCESS_SLOTS.acquire();
+            let (mut transport, mut writer, mut reader) = Transport::open(
+                &location,
+                working_dir.as_deref(),
+                name,
+                spawn_retries,
+                spawn_backoff,
+            )?;
 
-        stdin
-            .write_all("uci\n".as_bytes())
-            .expect("Failed to write 'uci' to engine stdin");
+            let handshake = uci_handshake(&mut writer, &mut reader);
+            let _ = writer.write_all("quit\n".as_bytes());
+            drop(writer);
+            transport.wait();
+            handshake?
+        };
 
-        let is_uci_ok;
-        loop {
-            let mut line = String::new();
-            let _ = stdout.read_line(&mut line);
-            if line.starts_with("uciok") {
-                is_uci_ok = true;
-                break;
-            }
-        }
-        if !is_uci_ok {
-            panic!("Engine is not UCI compatible");
+        let path = match &location {
+            EngineLocation::Local(path) => path.to_str().unwrap().to_string(),
+            EngineLocation::Remote(address) => address.clone(),
+        };
+        // without a name from the user, go by what the engine calls itself
+        let name = match (name.trim(), &id.name) {
+            ("", Some(reported_name)) => reported_name.clone(),
+            ("", None) => match &location {
+                EngineLocation::Local(path) => path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                EngineLocation::Remote(address) => address.clone(),
+            },
+            (name, _) => name.to_string(),
+        };
+        if assume_uci {
+            log::warn!("Assuming {} speaks UCI: uciok will not be waited for", name);
         }
 
-        stdin
-            .write_all("quit\n".as_bytes())
-            .expect("Error stopping connection");
-
         let mut engine = Engine {
-            path: path.to_str().unwrap().to_string(),
-            name: name.to_string(),
+            path,
+            location,
+            name,
+            reported_name: id.name,
+            author: id.author,
+            banner: id.banner,
             engine_options: Vec::new(),
             engine_handle: None,
             analysis: Vec::new(),
             is_show: true,
+            working_dir,
+            debug: false,
+            configured_options: Vec::new(),
+            spawn_retries,
+            spawn_backoff,
+            idle_timeout: None,
+            max_analysis_lines: DEFAULT_MAX_ANALYSIS_LINES,
+            max_pv_length: DEFAULT_MAX_PV_LENGTH,
+            assume_uci,
         };
         engine.engine_options = engine.detect_engine_options();
 
-        engine
+        Ok(engine)
+    } //
+
+    /// Directory the engine is started in. Defaults to the directory containing the binary.
+    pub fn with_working_dir(mut self, working_dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    } //
+
+    /// Have the engine send its extra `info string` diagnostics during games. Off by default, as
+    /// some engines get verbose and slow with it.
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    } //
+
+    /// How often starting a process is retried, and the delay before the first retry. The delay
+    /// doubles after every attempt.
+    pub fn with_spawn_retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.spawn_retries = retries;
+        self.spawn_backoff = backoff;
+        self
+    } //
+
+    /// Watch for hangs during searches: after `idle_timeout` without output the engine is sent
+    /// `isready`, and if that goes unanswered too it is treated as crashed. Off by default.
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    } //
+
+    /// Bounds `analysis`: at most `max_lines` lines are kept, and each pv is cut to
+    /// `max_pv_length` moves, so a long infinite search can't grow it without end.
+    pub fn with_analysis_limits(mut self, max_lines: usize, max_pv_length: usize) -> Self {
+        self.max_analysis_lines = max_lines;
+        self.max_pv_length = max_pv_length;
+        self
+    } //
+
+    /// Sets option `name` to `value` with `setoption` in every process started for a game or an
+    /// analysis, after the handshake; see `send_options`. `check_configured_options` (or
+    /// `Tournament::validate`) checks the values beforehand.
+    pub fn with_option(mut self, name: &str, value: &str) -> Self {
+        self.configured_options
+            .push((name.to_string(), value.to_string()));
+        self
+    } //
+
+    /// The options the engine advertised in its reply to `uci` when it was created, with their
+    /// defaults, for deciding what to pass to `with_option`.
+    pub fn options(&self) -> &[EngineOption] {
+        &self.engine_options
+    } //
+
+    /// `Threads` the engine will search with: the configured value if any, otherwise the
+    /// advertised default, otherwise 1.
+    pub fn threads(&self) -> u32 {
+        let configured = self
+            .configured_options
+            .iter()
+            .rev()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Threads"))
+            .and_then(|(_, value)| value.parse::<u32>().ok());
+        let default = self.engine_options.iter().find_map(|option| match option {
+            EngineOption::SPIN { name, value, .. } if name.eq_ignore_ascii_case("Threads") => {
+                u32::try_from(*value).ok()
+            }
+            _ => None,
+        });
+        configured.or(default).unwrap_or(1)
+    } //
+
+    /// Checks `configured_options` against the options the engine advertises: the name must
+    /// exist (case-insensitively, as in UCI) and the value must fit its type and range: one of a
+    /// combo's choices, and empty for a button. Values are trimmed first, as they are when sent.
+    pub fn check_configured_options(&self) -> Vec<EngineError> {
+        let mut errors = Vec::new();
+        for (name, configured) in &self.configured_options {
+            let value = configured.trim();
+            let is_valid = match self.find_option(name) {
+                None => {
+                    errors.push(EngineError::UnknownOption {
+                        engine: self.name.clone(),
+                        name: name.clone(),
+                    });
+                    continue;
+                }
+                Some(EngineOption::CHECK { .. }) => value == "true" || value == "false",
+                Some(EngineOption::SPIN { min, max, .. }) => {
+                    value.parse::<i32>().is_ok_and(|value| {
+                        min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+                    })
+                }
+                Some(EngineOption::COMBO { choices, .. }) => choices
+                    .iter()
+                    .any(|choice| choice.eq_ignore_ascii_case(value)),
+                Some(EngineOption::STRING { .. }) => true,
+                Some(EngineOption::BUTTON { .. }) => value.is_empty(),
+            };
+            if !is_valid {
+                errors.push(EngineError::InvalidOptionValue {
+                    engine: self.name.clone(),
+                    name: name.clone(),
+                    value: configured.clone(),
+                });
+            }
+        }
+        errors
+    } //
+
+    /// Weakens the engine to play at about `elo` through the standard `UCI_LimitStrength` and
+    /// `UCI_Elo` options, e.g. to build a ladder of handicapped versions of one engine.
+    pub fn limit_strength(self, elo: i32) -> Self {
+        self.try_limit_strength(elo)
+            .unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    /// Like `limit_strength`, but returns an error if the engine lacks either option or `elo`
+    /// is outside the range it advertises for `UCI_Elo`.
+    pub fn try_limit_strength(self, elo: i32) -> Result<Self, EngineError> {
+        let unknown = |name: &str| EngineError::UnknownOption {
+            engine: self.name.clone(),
+            name: name.to_string(),
+        };
+        if !matches!(
+            self.find_option("UCI_LimitStrength"),
+            Some(EngineOption::CHECK { .. })
+        ) {
+            return Err(unknown("UCI_LimitStrength"));
+        }
+        let Some(EngineOption::SPIN { min, max, .. }) = self.find_option("UCI_Elo") else {
+            return Err(unknown("UCI_Elo"));
+        };
+        if min.is_some_and(|min| elo < min) || max.is_some_and(|max| elo > max) {
+            return Err(EngineError::EloOutOfRange {
+                engine: self.name.clone(),
+                elo,
+                min: *min,
+                max: *max,
+            });
+        }
+        Ok(self
+            .with_option("UCI_LimitStrength", "true")
+            .with_option("UCI_Elo", &elo.to_string()))
+    } //
+
+    // The advertised option called `name`, compared case-insensitively as in UCI.
+    fn find_option(&self, name: &str) -> Option<&EngineOption> {
+        self.engine_options
+            .iter()
+            .find(|option| option.name().eq_ignore_ascii_case(name))
+    } //
+
+    /// Sends `configured_options` to a process that has finished its handshake. A value that
+    /// doesn't fit its advertised option (see `check_configured_options`) stops it: nothing is sent
+    /// and the first such error is returned. Options the engine doesn't advertise are sent anyway,
+    /// since some engines take more than they list, and one that doesn't ignores them.
+    pub fn send_options(&self, process: &EngineHandle) -> Result<(), EngineError> {
+        if let Some(error) = self
+            .check_configured_options()
+            .into_iter()
+            .find(|error| matches!(error, EngineError::InvalidOptionValue { .. }))
+        {
+            return Err(error);
+        }
+        for (name, value) in &self.configured_options {
+            // a button is pressed, it takes no value
+            if let Some(EngineOption::BUTTON { .. }) = self.find_option(name) {
+                process.send_command(&format!("setoption name {}\n", name.trim()));
+            } else {
+                process.set_option(name, value);
+            }
+        }
+        Ok(())
     } //
 
     pub fn spawn_handle(&mut self) {
+        self.engine_handle = Some(self.spawn_process());
+    } //
+
+    pub fn spawn_process(&self) -> EngineHandle {
+        self.spawn_tagged_process(None, None)
+    } //
+
+    /// Spawns a process identified by `tag`. With a `log_dir`, everything sent to and received
+    /// from the engine is written to `<log_dir>/<tag>.log`, one file per process.
+    pub fn spawn_tagged_process(
+        &self,
+        tag: Option<ProcessTag>,
+        log_dir: Option<&Path>,
+    ) -> EngineHandle {
+        self.try_spawn_tagged_process(tag, log_dir)
+            .unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    /// Like `spawn_tagged_process`, but returns `EngineError::SpawnFailed` once every retry has
+    /// failed, or `EngineError::LogFailed` if the transcript can't be created under `log_dir`.
+    /// Remote engines get a new connection instead of a process.
+    pub fn try_spawn_tagged_process(
+        &self,
+        tag: Option<ProcessTag>,
+        log_dir: Option<&Path>,
+    ) -> Result<EngineHandle, EngineError> {
+        let transcript = match tag.as_ref().zip(log_dir) {
+            Some((tag, log_dir)) => {
+                let log_failed = |error: std::io::Error| EngineError::LogFailed(error.to_string());
+                fs::create_dir_all(log_dir).map_err(log_failed)?;
+                let file =
+                    File::create(log_dir.join(format!("{}.log", tag))).map_err(log_failed)?;
+                Some(Arc::new(Mutex::new(file)))
+            }
+            None => None,
+        };
         let (cmd_tx, cmd_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
         let (evt_tx, evt_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
 
-        let mut child_process = Command::new(&self.path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("Failed to start engine process");
-        let mut stdin = child_process
-            .stdin
-            .take()
-            .expect("Failed to take engine stdin");
-        let stdout = BufReader::new(
-            child_process
-                .stdout
-                .take()
-                .expect("Failed to take engine stdout"),
-        );
+        let slot = PROCESS_SLOTS.acquire();
+        let (transport, mut stdin, stdout) = Transport::open(
+            &self.location,
+            self.working_dir.as_deref(),
+            &self.name,
+            self.spawn_retries,
+            self.spawn_backoff,
+        )?;
 
         // stdin writer task
+        let writer_transcript = transcript.clone();
         thread::spawn(move || {
             while let Ok(cmd) = cmd_rx.recv() {
+                log_line(writer_transcript.as_ref(), '>', cmd.trim_end());
                 let _ = stdin.write_all(cmd.as_bytes());
                 let _ = stdin.flush();
             }
         });
 
         // stdout reader task
-        thread::spawn(move || {
-            let mut reader = stdout;
-            let mut line = String::new();
-
-            loop {
-                line.clear();
-                if reader
-                    .read_line(&mut line)
-                    .ok()
-                    .filter(|&n| n > 0)
-                    .is_none()
-                {
-                    break;
-                }
-                let _ = evt_tx.send(line.clone());
-            }
-        });
+        spawn_line_reader(stdout, evt_tx, transcript);
 
-        self.engine_handle = Some(EngineHandle {
-            process: child_process,
+        Ok(EngineHandle {
+            transport,
+            slot: Some(slot),
             tx: cmd_tx,
             rx: evt_rx,
+            tag,
+            idle_timeout: self.idle_timeout,
+            assume_uci: self.assume_uci,
+            on_line: RefCell::new(None),
+            sent: RefCell::new(VecDeque::with_capacity(RECENT_COMMANDS)),
+            ended: Cell::new(false),
+        })
+    } //
+
+    /// Searches `fen` once under `time_control` on a process of its own, started for the
+    /// purpose and quit afterwards. A search that outlasts a minute (or an infinite one) is
+    /// stopped and its best move so far taken.
+    pub fn analyze(&self, fen: &str, time_control: TimeControl) -> AnalysisResult {
+        self.try_analyze(fen, time_control)
+            .unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    pub fn try_analyze(
+        &self,
+        fen: &str,
+        time_control: TimeControl,
+    ) -> Result<AnalysisResult, EngineError> {
+        validate_fen(fen).map_err(EngineError::InvalidPosition)?;
+        let mut process = self.start_analysis()?;
+        let result = search_position(&process, fen, time_control);
+        process.send_command("quit\n");
+        process.disconnect();
+        result
+    } //
+
+    /// Analyzes each of `fens` like `analyze`, on a pool of up to `concurrency` processes that
+    /// take positions from a shared queue, and returns the results in the order of `fens`. A
+    /// process that crashes or hangs is replaced and its position queued again, so another
+    /// worker may pick it up; positions failing every attempt get their error instead.
+    pub fn analyze_batch(
+        &self,
+        fens: &[String],
+        time_control: TimeControl,
+        concurrency: usize,
+    ) -> Vec<Result<AnalysisResult, EngineError>> {
+        // (index into fens, attempts so far)
+        let queue = Mutex::new(
+            (0..fens.len())
+                .map(|index| (index, 0))
+                .collect::<VecDeque<_>>(),
+        );
+        let results = Mutex::new((0..fens.len()).map(|_| None).collect::<Vec<_>>());
+        thread::scope(|scope| {
+            for _ in 0..concurrency.clamp(1, fens.len().max(1)) {
+                let engine = self.clone();
+                let (queue, results) = (&queue, &results);
+                scope.spawn(move || {
+                    let mut process: Option<EngineHandle> = None;
+                    loop {
+                        let Some((index, attempts)) = queue.lock().unwrap().pop_front() else {
+                            break;
+                        };
+                        let fen = &fens[index];
+                        if let Err(error) = validate_fen(fen) {
+                            results.lock().unwrap()[index] =
+                                Some(Err(EngineError::InvalidPosition(error)));
+                            continue;
+                        }
+                        let started = match process.take() {
+                            Some(process) => Ok(process),
+                            None => engine.start_analysis(),
+                        };
+                        let result = started.and_then(|started| {
+                            let result = search_position(&started, fen, time_control);
+                            // a process that failed is dropped; the next position gets a new one
+                            if result.is_ok() {
+                                process = Some(started);
+                            }
+                            result
+                        });
+                        match result {
+                            Err(error) if attempts + 1 < BATCH_ATTEMPTS => {
+                                log::warn!("Retrying position {} after: {}", index, error);
+                                queue.lock().unwrap().push_back((index, attempts + 1));
+                            }
+                            result => results.lock().unwrap()[index] = Some(result),
+                        }
+                    }
+                    if let Some(process) = process {
+                        process.send_command("quit\n");
+                    }
+                });
+            }
         });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every position is analyzed or given up on"))
+            .collect()
+    } //
+
+    // A process of its own, past the handshake and ready to be given positions.
+    pub(crate) fn start_analysis(&self) -> Result<EngineHandle, EngineError> {
+        let process = self.try_spawn_tagged_process(None, None)?;
+        process.send_command("uci\n");
+        if !process.wait_for_uciok() {
+            return Err(EngineError::NotUci);
+        }
+        self.send_options(&process)?;
+        Ok(process)
     } //
 
     pub fn send_command(&mut self, command: &str) {
         if self.engine_handle.is_none() {
             self.spawn_handle();
         }
-        self.engine_handle
-            .as_ref()
-            .unwrap()
-            .tx
-            .send(command.to_string())
-            .ok();
+        self.engine_handle.as_ref().unwrap().send_command(command);
     } //
     pub fn read_line(&self) -> Option<String> {
         if let Some(engine) = self.engine_handle.as_ref() {
-            engine.rx.recv().ok()
+            engine.read_line()
         } else {
             None
         }
     } //
     pub fn try_read_line(&self) -> Option<String> {
         if let Some(engine) = self.engine_handle.as_ref() {
-            engine.rx.try_recv().ok()
+            engine.try_read_line()
         } else {
             None
         }
     } //
+    // A line of the reply to `uci`, which from an engine assumed to speak UCI may never end in
+    // `uciok`.
+    fn read_uci_line(&self) -> Option<String> {
+        match self.engine_handle.as_ref() {
+            Some(engine) => engine.read_uci_line(),
+            None => None,
+        }
+    } //
 
     pub fn detect_engine_options(&mut self) -> Vec<EngineOption> {
         if self.engine_handle.is_none() {
@@ -280,55 +906,10 @@ impl Engine {
         self.send_command("uci\n");
         let mut options = vec![];
         loop {
-            if let Some(str) = self.read_line() {
-                if str.starts_with("option") {
-                    let args = str.split_whitespace().collect::<Vec<_>>();
-                    let option_type;
-                    let value;
-                    let name;
-
-                    if let Some(name_index) = args.iter().position(|w| w == &"name") {
-                        name = args[name_index + 1].to_string();
-                    } else {
-                        continue;
-                    }
-                    if let Some(default_index) = args.iter().position(|w| w == &"default") {
-                        value = args[default_index + 1].to_string();
-                    } else {
-                        continue;
-                    }
-                    if let Some(option_type_index) = args.iter().position(|w| w == &"type") {
-                        option_type = args[option_type_index + 1].to_string();
-                    } else {
-                        continue;
-                    }
-
-                    match option_type.as_str() {
-                        "check" => {
-                            options.push(EngineOption::CHECK {
-                                name,
-                                value: value.parse::<bool>().unwrap(),
-                            });
-                        }
-                        "spin" => {
-                            let mut min = None;
-                            let mut max = None;
-                            if let Some(min_index) = args.iter().position(|w| w == &"min") {
-                                min = Some(args[min_index + 1].parse::<i32>().unwrap());
-                            }
-                            if let Some(max_index) = args.iter().position(|w| w == &"max") {
-                                max = Some(args[max_index + 1].parse::<i32>().unwrap());
-                            }
-                            options.push(EngineOption::SPIN {
-                                name,
-                                value: value.parse::<i32>().unwrap(),
-                                min,
-                                max,
-                            });
-                        }
-                        _ => {}
-                    }
-                } else if str.contains("uciok") {
+            if let Some(str) = self.read_uci_line() {
+                if str.starts_with("option name ") {
+                    options.extend(EngineOption::parse(&str));
+                } else if str.starts_with("uciok") {
                     break;
                 }
             } else {
@@ -340,104 +921,570 @@ impl Engine {
 
     pub fn disconnect(&mut self) {
         if let Some(handle) = self.engine_handle.as_mut() {
-            handle.process.kill().ok();
-            handle.process.wait().ok();
+            handle.close();
         }
     }
 
     pub fn poll_engine(&mut self) {
-        if let Some(handle) = self.engine_handle.as_mut() {
-            while let Some(line) = handle.try_read_line() {
-                if let Some(analysis) = AnalysisLine::new(line) {
-                    self.analysis.push(analysis);
-                }
-            }
+        while let Some(line) = self
+            .engine_handle
+            .as_ref()
+            .and_then(|handle| handle.try_read_line())
+        {
+            self.record_analysis(line);
         }
     }
+
+    fn record_analysis(&mut self, line: String) {
+        let Some(mut analysis) = AnalysisLine::new(line) else {
+            return;
+        };
+        if let AnalysisLine::Depth { pv, .. } = &mut analysis {
+            pv.truncate(self.max_pv_length);
+        }
+        self.analysis.push(analysis);
+        if self.analysis.len() > self.max_analysis_lines {
+            let excess = self.analysis.len() - self.max_analysis_lines;
+            self.analysis.drain(..excess);
+        }
+    } //
 }
 
+// Searches `fen`, which must be valid, on a process that has been through the handshake.
+fn search_position(
+    process: &EngineHandle,
+    fen: &str,
+    time_control: TimeControl,
+) -> Result<AnalysisResult, EngineError> {
+    process.send_command("ucinewgame\n");
+    process.send_command(&format!("position fen {}\n", fen));
+    let clocks = match time_control.clock() {
+        Some((base_ms, _)) => [base_ms as i64; 2],
+        None => [0; 2],
+    };
+    process.send_command(&format!("go {}\n", time_control.search_limits(clocks, 0)));
+    let reply =
+        wait_for_bestmove(process, Some(DEFAULT_SEARCH_TIMEOUT)).ok_or(EngineError::NoBestMove)?;
+    Ok(AnalysisResult {
+        best_move: reply.best_move,
+        ponder: reply.ponder,
+        score: reply.score,
+        depth: reply.depth,
+        pv: reply.pv,
+    })
+} //
+
 impl Drop for Engine {
     fn drop(&mut self) {
         self.disconnect();
     }
 }
 
+// What the engine says about itself in its `id` lines, and any free text around them.
+#[derive(Debug, Default, PartialEq)]
+struct EngineId {
+    name: Option<String>,
+    author: Option<String>,
+    banner: Vec<String>,
+}
+
+// Sends `uci` and reads up to `uciok`. Copy protection and registration checks may come first:
+// `copyprotection error` is fatal, a failed registration is postponed with `register later`.
+fn uci_handshake<W: Write, R: BufRead>(
+    stdin: &mut W,
+    stdout: &mut R,
+) -> Result<EngineId, EngineError> {
+    stdin
+        .write_all("uci\n".as_bytes())
+        .and_then(|_| stdin.flush())
+        .map_err(|_| EngineError::NotUci)?;
+
+    let mut id = EngineId::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stdout.read_line(&mut line).unwrap_or(0) == 0 {
+            return Err(EngineError::NotUci);
+        }
+        let line = line.trim();
+        if line.starts_with("uciok") {
+            return Ok(id);
+        } else if let Some(name) = line.strip_prefix("id name ") {
+            id.name = Some(name.trim().to_string());
+        } else if let Some(author) = line.strip_prefix("id author ") {
+            id.author = Some(author.trim().to_string());
+        } else if line == "copyprotection error" {
+            return Err(EngineError::CopyProtection);
+        } else if line == "registration error" {
+            let _ = stdin
+                .write_all("register later\n".as_bytes())
+                .and_then(|_| stdin.flush());
+        } else if !line.is_empty()
+            && line != "uci" // echoed back by a few engines
+            && !["copyprotection ", "registration ", "option ", "info "]
+                .iter()
+                .any(|reply| line.starts_with(reply))
+        {
+            // not UCI at all: many engines print a banner at startup, before reading `uci`
+            id.banner.push(line.to_string());
+        }
+        // `copyprotection checking`/`ok`, `registration checking`/`ok`, `option` and `info`
+        // lines are read past until `uciok`
+    }
+} //
+
+static PROCESS_SLOTS: ProcessSlots = ProcessSlots::new(DEFAULT_MAX_PROCESSES);
+
+/// Caps the engine processes (and remote connections) alive at once across the whole crate, so
+/// a constrained machine or container isn't run out of memory or pids. Starting one more waits
+/// until another ends. Defaults to 256.
+///
+/// Every running game holds two, an `analyze_batch` up to its `concurrency`, and an `Engine`
+/// keeps the process its options were probed with until it is dropped or disconnected. The limit
+/// has to leave room for all of those that run together, or they wait on each other for good;
+/// it is never set below 2, what a single game needs.
+pub fn set_max_processes(limit: usize) {
+    PROCESS_SLOTS.set_limit(limit.max(2));
+} //
+
+pub fn max_processes() -> usize {
+    PROCESS_SLOTS.state.lock().unwrap().1
+} //
+
+// A counting semaphore: (live, limit), and a wake-up for waiters whenever a slot frees.
+struct ProcessSlots {
+    state: Mutex<(usize, usize)>,
+    freed: Condvar,
+}
+
+impl ProcessSlots {
+    const fn new(limit: usize) -> Self {
+        ProcessSlots {
+            state: Mutex::new((0, limit)),
+            freed: Condvar::new(),
+        }
+    } //
+
+    fn acquire(&self) -> ProcessSlot<'_> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 >= state.1 {
+            log::debug!("All {} engine process slots are taken, waiting", state.1);
+        }
+        while state.0 >= state.1 {
+            state = self.freed.wait(state).unwrap();
+        }
+        state.0 += 1;
+        ProcessSlot(self)
+    } //
+
+    fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap().1 = limit;
+        self.freed.notify_all();
+    } //
+}
+
+// Held for as long as a process lives; dropping it lets the next one start.
+struct ProcessSlot<'a>(&'a ProcessSlots);
+
+impl Drop for ProcessSlot<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().0 -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+// How commands reach an engine and its output comes back: a child's pipes, or a socket.
+enum Transport {
+    Process(Child, StderrDrain),
+    Socket(TcpStream),
+}
+
+// Reads a child's stderr on a thread of its own, so a chatty engine can't fill the pipe and
+// block, keeping the last `STDERR_LINES` lines.
+struct StderrDrain {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl StderrDrain {
+    fn spawn<R: BufRead + Send + 'static>(reader: R, name: &str) -> Self {
+        let lines = Arc::new(Mutex::new(VecDeque::new()));
+        let kept = lines.clone();
+        let name = name.to_string();
+        let thread = thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                let line = line.trim_end_matches('\r').to_string();
+                log::debug!("{} stderr: {}", name, line);
+                let mut kept = kept.lock().unwrap();
+                if kept.len() == STDERR_LINES {
+                    kept.pop_front();
+                }
+                kept.push_back(line);
+            }
+        });
+        StderrDrain { lines, thread }
+    } //
+}
+
+// A transport, with the ends commands are written to and output is read from.
+type Opened = (Transport, Box<dyn Write + Send>, Box<dyn BufRead + Send>);
+
+impl Transport {
+    fn open(
+        location: &EngineLocation,
+        working_dir: Option<&Path>,
+        name: &str,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Opened, EngineError> {
+        match location {
+            EngineLocation::Local(path) => {
+                let mut command = Command::new(path);
+                if let Some(working_dir) = working_dir {
+                    command.current_dir(working_dir);
+                }
+                command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                let mut child = start_with_retry(name, retries, backoff, || command.spawn())?;
+                let stdin = child.stdin.take().expect("Failed to take engine stdin");
+                let stdout = child.stdout.take().expect("Failed to take engine stdout");
+                let stderr = child.stderr.take().expect("Failed to take engine stderr");
+                let stderr = StderrDrain::spawn(BufReader::new(stderr), name);
+                Ok((
+                    Transport::Process(child, stderr),
+                    Box::new(stdin),
+                    Box::new(BufReader::new(stdout)),
+                ))
+            }
+            EngineLocation::Remote(address) => {
+                let stream = start_with_retry(name, retries, backoff, || {
+                    TcpStream::connect(address.as_str())
+                })?;
+                // UCI is a ping-pong of short lines, which Nagle's algorithm would hold back until
+                // the other side's delayed ack, tens of ms per move
+                stream.set_nodelay(true).ok();
+                let clone = || {
+                    stream
+                        .try_clone()
+                        .map_err(|error| EngineError::SpawnFailed(error.to_string()))
+                };
+                let (writer, reader) = (clone()?, clone()?);
+                Ok((
+                    Transport::Socket(stream),
+                    Box::new(writer),
+                    Box::new(BufReader::new(reader)),
+                ))
+            }
+        }
+    } //
+
+    // Lets the engine finish on its own after `quit`.
+    fn wait(&mut self) {
+        match self {
+            Transport::Process(child, _) => {
+                child.wait().ok();
+            }
+            Transport::Socket(stream) => {
+                stream.shutdown(Shutdown::Write).ok();
+            }
+        }
+    } //
+
+    // Gives an engine that was sent `quit` up to `QUIT_GRACE` to exit, then kills it, so no
+    // process outlives its handle whatever state the game left it in. Its stderr is then read
+    // to the end, unless something it started still holds the pipe open.
+    fn close(&mut self) {
+        match self {
+            Transport::Process(child, stderr) => {
+                let deadline = Instant::now() + QUIT_GRACE;
+                while matches!(child.try_wait(), Ok(None)) && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                child.kill().ok();
+                child.wait().ok();
+                let deadline = Instant::now() + STDERR_GRACE;
+                while !stderr.thread.is_finished() && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(5));
+                }
+            }
+            Transport::Socket(stream) => {
+                stream.shutdown(Shutdown::Both).ok();
+            }
+        }
+    } //
+}
+
+// Starting a process or connecting can fail transiently (e.g. EAGAIN on a loaded machine, or a
+// server still coming up), so it is retried `retries` times, `backoff` apart and doubling. A
+// missing or forbidden binary won't come back and fails at once.
+fn start_with_retry<T>(
+    name: &str,
+    retries: u32,
+    backoff: Duration,
+    mut start: impl FnMut() -> io::Result<T>,
+) -> Result<T, EngineError> {
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        match start() {
+            Ok(started) => return Ok(started),
+            Err(error)
+                if attempt < retries
+                    && !matches!(
+                        error.kind(),
+                        ErrorKind::NotFound | ErrorKind::PermissionDenied
+                    ) =>
+            {
+                attempt += 1;
+                log::warn!(
+                    "Failed to start {} ({}), retry {}/{} in {:?}",
+                    name,
+                    error,
+                    attempt,
+                    retries,
+                    delay
+                );
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(error) => return Err(EngineError::SpawnFailed(error.to_string())),
+        }
+    }
+} //
+
+// One line of a process transcript: `>` for commands sent to the engine, `<` for its output.
+fn log_line(transcript: Option<&Arc<Mutex<File>>>, direction: char, line: &str) {
+    if let Some(mut file) = transcript.and_then(|transcript| transcript.lock().ok()) {
+        let _ = writeln!(file, "{} {}", direction, line);
+    }
+} //
+
+// Forwards every line from `reader` with its `\n` / `\r\n` ending stripped, so a trailing `\r`
+// from Windows engines never ends up glued to the last token.
+fn spawn_line_reader<R: BufRead + Send + 'static>(
+    mut reader: R,
+    tx: Sender<String>,
+    transcript: Option<Arc<Mutex<File>>>,
+) {
+    thread::spawn(move || {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if reader
+                .read_line(&mut line)
+                .ok()
+                .filter(|&n| n > 0)
+                .is_none()
+            {
+                break;
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            log_line(transcript.as_ref(), '<', line);
+            let _ = tx.send(line.to_string());
+        }
+    });
+} //
+
+/// Stable identity of an engine process in a game, e.g. `round-047-white-Stockfish`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessTag {
+    pub round: Option<i32>,
+    pub white: bool,
+    pub engine_name: String,
+}
+impl fmt::Display for ProcessTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(round) = self.round {
+            write!(f, "round-{:03}-", round)?;
+        }
+        let color = if self.white { "white" } else { "black" };
+        // the tag names log files, so keep it to characters every filesystem accepts
+        let engine_name = self
+            .engine_name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        write!(f, "{}-{}", color, engine_name)
+    }
+}
+
+/// Called with every line an engine sends. See `EngineHandle::on_line`.
+pub type LineCallback = Box<dyn FnMut(&str) + Send>;
+
 pub struct EngineHandle {
-    process: Child,
-    pub tx: Sender<String>,
-    pub rx: Receiver<String>,
+    transport: Transport,
+    slot: Option<ProcessSlot<'static>>, // given back once the transport is closed
+    tx: Sender<String>,
+    rx: Receiver<String>,
+    tag: Option<ProcessTag>,
+    idle_timeout: Option<Duration>,
+    assume_uci: bool,
+    on_line: RefCell<Option<LineCallback>>,
+    sent: RefCell<VecDeque<String>>, // the last RECENT_COMMANDS commands, to spot echoes
+    ended: Cell<bool>,               // the engine's output closed, every line read
 }
 impl Drop for EngineHandle {
     fn drop(&mut self) {
-        self.process.kill().ok();
-        self.process.wait().ok();
+        self.close();
     }
 }
 
 impl EngineHandle {
+    /// See `Engine::with_idle_timeout`.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    } //
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeout = idle_timeout;
+    } //
+    pub fn tag(&self) -> Option<&ProcessTag> {
+        self.tag.as_ref()
+    } //
     pub fn send_command(&self, command: &str) {
+        let mut sent = self.sent.borrow_mut();
+        if sent.len() == RECENT_COMMANDS {
+            sent.pop_front();
+        }
+        sent.push_back(command.trim().to_string());
         self.tx.send(command.to_string()).ok();
     } //
+    pub fn set_option(&self, name: &str, value: &str) {
+        self.send_command(&format!(
+            "setoption name {} value {}\n",
+            name.trim(),
+            value.trim()
+        ));
+    } //
+    /// Have `callback` see every line the engine sends, before the line is parsed, so output the
+    /// crate doesn't understand (engine-specific `info`, say) can be picked up. It runs on
+    /// whichever thread reads from the handle, during a game the game's own: the game waits for
+    /// it, and the engine's clock keeps running meanwhile. For the processes a game spawns, see
+    /// `Game::with_line_callback`.
+    pub fn on_line(&mut self, callback: LineCallback) {
+        *self.on_line.get_mut() = Some(callback);
+    } //
     pub fn read_line(&self) -> Option<String> {
-        self.rx.recv().ok()
+        loop {
+            let line = self.rx.recv().inspect_err(|_| self.ended.set(true));
+            let line = self.seen(line.ok())?;
+            if !self.is_echo(&line) {
+                return Some(line);
+            }
+        }
     } //
     pub fn try_read_line(&self) -> Option<String> {
-        self.rx.try_recv().ok()
+        loop {
+            let line = self.rx.try_recv().inspect_err(|error| {
+                self.ended
+                    .set(self.ended.get() || *error == TryRecvError::Disconnected)
+            });
+            let line = self.seen(line.ok())?;
+            if !self.is_echo(&line) {
+                return Some(line);
+            }
+        }
     }
+    /// Like `read_line`, but gives up after `timeout`, so a silent engine can't stall the
+    /// caller. Lines are read on a thread of each process's own and handed over a channel, which
+    /// is what makes the wait bounded; it costs that thread for every process. None on a timeout
+    /// and once the engine's output has ended, which `output_ended` tells apart.
+    pub fn read_line_timeout(&self, timeout: Duration) -> Option<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = self.rx.recv_timeout(timeout).inspect_err(|error| {
+                self.ended
+                    .set(self.ended.get() || *error == RecvTimeoutError::Disconnected)
+            });
+            let line = self.seen(line.ok())?;
+            if !self.is_echo(&line) {
+                return Some(line);
+            }
+        }
+    }
+    /// Whether every line the engine will ever send has been read: it exited, crashed or
+    /// dropped the connection. Only known once a read has come back empty.
+    pub fn output_ended(&self) -> bool {
+        self.ended.get()
+    } //
+    fn seen(&self, line: Option<String>) -> Option<String> {
+        if let (Some(line), Some(callback)) = (&line, self.on_line.borrow_mut().as_mut()) {
+            callback(line);
+        }
+        line
+    } //
+    // Some engines echo what they are sent. No engine output is ever the same as a command, so a
+    // line matching a recent one is an echo and is dropped before an echoed `position` or `go`
+    // can be mistaken for anything. The line callback still sees it.
+    fn is_echo(&self, line: &str) -> bool {
+        let line = line.trim();
+        let echo = !line.is_empty() && self.sent.borrow().iter().any(|command| command == line);
+        if echo {
+            log::debug!("Ignoring echoed command: {}", line);
+        }
+        echo
+    } //
+    pub fn wait_for(&self, token: &str) -> bool {
+        while let Some(line) = self.read_line() {
+            if line.starts_with(token) {
+                return true;
+            }
+        }
+        false
+    } //
+    /// Sends `isready` and waits for `readyok`, so an engine still setting itself up doesn't miss
+    /// what comes next. False if it doesn't answer within READY_TIMEOUT.
+    pub fn sync(&self) -> bool {
+        self.send_command("isready\n");
+        let deadline = Instant::now() + READY_TIMEOUT;
+        while let Some(line) =
+            self.read_line_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            if line.starts_with("readyok") {
+                return true;
+            }
+        }
+        false
+    } //
+    /// Waits out the reply to `uci`. For an engine assumed to speak UCI (see
+    /// `Engine::try_new_assuming_uci`) that is `uciok` or a short silence, whichever comes first.
+    pub fn wait_for_uciok(&self) -> bool {
+        if !self.assume_uci {
+            return self.wait_for("uciok");
+        }
+        while let Some(line) = self.read_uci_line() {
+            if line.starts_with("uciok") {
+                break;
+            }
+        }
+        true
+    } //
+    fn read_uci_line(&self) -> Option<String> {
+        if self.assume_uci {
+            self.read_line_timeout(ASSUMED_UCI_GRACE)
+        } else {
+            self.read_line()
+        }
+    } //
 
     pub fn detect_engine_options(&mut self) -> Vec<EngineOption> {
         self.send_command("uci\n");
         let mut options = vec![];
         loop {
-            if let Some(str) = self.read_line() {
-                if str.starts_with("option") {
-                    let args = str.split_whitespace().collect::<Vec<_>>();
-                    let option_type;
-                    let value;
-                    let name;
-
-                    if let Some(name_index) = args.iter().position(|w| w == &"name") {
-                        name = args[name_index + 1].to_string();
-                    } else {
-                        continue;
-                    }
-                    if let Some(default_index) = args.iter().position(|w| w == &"default") {
-                        value = args[default_index + 1].to_string();
-                    } else {
-                        continue;
-                    }
-                    if let Some(option_type_index) = args.iter().position(|w| w == &"type") {
-                        option_type = args[option_type_index + 1].to_string();
-                    } else {
-                        continue;
-                    }
-
-                    match option_type.as_str() {
-                        "check" => {
-                            options.push(EngineOption::CHECK {
-                                name,
-                                value: value.parse::<bool>().unwrap(),
-                            });
-                        }
-                        "spin" => {
-                            let mut min = None;
-                            let mut max = None;
-                            if let Some(min_index) = args.iter().position(|w| w == &"min") {
-                                min = Some(args[min_index + 1].parse::<i32>().unwrap());
-                            }
-                            if let Some(max_index) = args.iter().position(|w| w == &"max") {
-                                max = Some(args[max_index + 1].parse::<i32>().unwrap());
-                            }
-                            options.push(EngineOption::SPIN {
-                                name,
-                                value: value.parse::<i32>().unwrap(),
-                                min,
-                                max,
-                            });
-                        }
-                        _ => {}
-                    }
-                } else if str.contains("uciok") {
+            if let Some(str) = self.read_uci_line() {
+                if str.starts_with("option name ") {
+                    options.extend(EngineOption::parse(&str));
+                } else if str.starts_with("uciok") {
                     break;
                 }
             } else {
@@ -447,7 +1494,747 @@ impl EngineHandle {
         options
     } //
 
+    pub fn set_debug(&mut self, on: bool) {
+        if on {
+            self.send_command("debug on\n");
+        } else {
+            self.send_command("debug off\n");
+        }
+    } //
+
     pub fn disconnect(&mut self) {
         self.send_command("quit\n");
     } //
+
+    /// Quits the engine, killing it if it hasn't exited after a moment, and frees its process
+    /// slot. Dropping the handle does the same; closing it first lets `take_stderr` see
+    /// everything the engine wrote. Only the first call does anything, so a handle closed by
+    /// hand is not told to quit again when it is dropped.
+    pub fn close(&mut self) {
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        self.send_command("quit\n");
+        self.transport.close();
+        drop(slot);
+    } //
+
+    /// The lines the engine wrote to stderr since the last call, up to the last 100. Always
+    /// empty for remote engines.
+    pub fn take_stderr(&self) -> Vec<String> {
+        match &self.transport {
+            Transport::Process(_, stderr) => stderr.lines.lock().unwrap().drain(..).collect(),
+            Transport::Socket(_) => Vec::new(),
+        }
+    } //
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn info_string_is_a_message() {
+        let line = AnalysisLine::new(
+            "info string Found network file nn-1c0000000000.nnue depth 20\n".to_string(),
+        );
+        match line {
+            Some(AnalysisLine::Message(message)) => {
+                assert_eq!(message, "Found network file nn-1c0000000000.nnue depth 20")
+            }
+            other => panic!("expected a message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn crlf_is_stripped_from_lines() {
+        let (tx, rx) = mpsc::channel();
+        spawn_line_reader(
+            std::io::Cursor::new("bestmove e2e4\r\nreadyok\r\npartial"),
+            tx,
+            None,
+        );
+        let lines = rx.iter().collect::<Vec<_>>();
+        assert_eq!(lines, vec!["bestmove e2e4", "readyok", "partial"]);
+
+        match AnalysisLine::new(lines[0].clone()) {
+            Some(AnalysisLine::Move(best_move, None)) => assert_eq!(best_move, "e2e4"),
+            other => panic!("expected a bestmove, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn uci_moves_are_made_canonical() {
+        assert_eq!(canonical_uci("e7e8Q").as_deref(), Some("e7e8q"));
+        assert_eq!(canonical_uci("E2E4").as_deref(), Some("e2e4"));
+        assert_eq!(canonical_uci("a2a1n").as_deref(), Some("a2a1n"));
+        for invalid in [
+            "e7e8k", "e7e8p", "e7e8x", "e7e8qq", "e9e8", "i2i4", "0000", "",
+        ] {
+            assert_eq!(canonical_uci(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn every_search_field_of_an_info_line_is_read() {
+        let line = AnalysisLine::new(
+            "info depth 18 seldepth 27 score cp -35 nodes 9123 nps 15000 time 608 pv d7d5 c2c4\n"
+                .to_string(),
+        );
+        match line {
+            Some(AnalysisLine::Depth {
+                depth,
+                selective_depth,
+                score,
+                nodes,
+                nps,
+                time,
+                pv,
+                ..
+            }) => {
+                assert_eq!(depth.as_deref(), Some("18"));
+                assert_eq!(selective_depth.as_deref(), Some("27"));
+                assert!(matches!(score, Some(Score::Cp(-35))));
+                assert_eq!(nodes.as_deref(), Some("9123"));
+                assert_eq!(nps.as_deref(), Some("15000"));
+                assert_eq!(time.as_deref(), Some("608"));
+                assert_eq!(pv, ["d7d5", "c2c4"]);
+            }
+            other => panic!("expected a depth line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn info_string_after_data_is_ignored() {
+        let line = AnalysisLine::new("info depth 12 score cp 31 string mate 3 nodes 9".to_string());
+        match line {
+            Some(AnalysisLine::Depth {
+                depth,
+                score,
+                nodes,
+                ..
+            }) => {
+                assert_eq!(depth.as_deref(), Some("12"));
+                assert!(matches!(score, Some(Score::Cp(31))));
+                assert_eq!(nodes, None);
+            }
+            other => panic!("expected a depth line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handshake_waits_through_copy_protection() {
+        let mut stdin = Vec::new();
+        let mut stdout = std::io::Cursor::new(
+            "id name Shredder\ncopyprotection checking\ncopyprotection ok\nregistration checking\nregistration error\noption name Hash type spin default 16 min 1 max 1024\nuciok\n",
+        );
+        assert_eq!(
+            uci_handshake(&mut stdin, &mut stdout).map(|id| id.name),
+            Ok(Some("Shredder".to_string()))
+        );
+        assert_eq!(String::from_utf8(stdin).unwrap(), "uci\nregister later\n");
+    }
+
+    #[test]
+    fn process_slots_wait_for_a_free_one() {
+        let slots = ProcessSlots::new(1);
+        let first = slots.acquire();
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _second = slots.acquire();
+                tx.send(()).unwrap();
+            });
+            assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+            drop(first);
+            assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        });
+        assert_eq!(*slots.state.lock().unwrap(), (0, 1));
+
+        // raising the limit wakes waiters too
+        let _first = slots.acquire();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| drop(slots.acquire()));
+            thread::sleep(Duration::from_millis(50));
+            slots.set_limit(2);
+            waiter.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn handshake_reads_the_engine_id() {
+        let mut stdout = std::io::Cursor::new(
+            "id name Stockfish 16\nid author the Stockfish developers (see AUTHORS file)\nuciok\n",
+        );
+        assert_eq!(
+            uci_handshake(&mut Vec::new(), &mut stdout),
+            Ok(EngineId {
+                name: Some("Stockfish 16".to_string()),
+                author: Some("the Stockfish developers (see AUTHORS file)".to_string()),
+                banner: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn every_option_type_is_read() {
+        let cases = [
+            (
+                "option name Hash type spin default 16 min 1 max 1024",
+                Some(EngineOption::SPIN {
+                    name: "Hash".to_string(),
+                    value: 16,
+                    min: Some(1),
+                    max: Some(1024),
+                }),
+            ),
+            (
+                "option name UCI Chess960 type check default false",
+                Some(EngineOption::CHECK {
+                    name: "UCI Chess960".to_string(),
+                    value: false,
+                }),
+            ),
+            (
+                "option name Style type combo default Normal var Solid var Normal var Very Risky",
+                Some(EngineOption::COMBO {
+                    name: "Style".to_string(),
+                    value: "Normal".to_string(),
+                    choices: vec![
+                        "Solid".to_string(),
+                        "Normal".to_string(),
+                        "Very Risky".to_string(),
+                    ],
+                }),
+            ),
+            (
+                "option name SyzygyPath type string default <empty>",
+                Some(EngineOption::STRING {
+                    name: "SyzygyPath".to_string(),
+                    value: String::new(),
+                }),
+            ),
+            (
+                "option name EvalFile type string default nets/big net.nnue",
+                Some(EngineOption::STRING {
+                    name: "EvalFile".to_string(),
+                    value: "nets/big net.nnue".to_string(),
+                }),
+            ),
+            (
+                "option name Clear Hash type button",
+                Some(EngineOption::BUTTON {
+                    name: "Clear Hash".to_string(),
+                }),
+            ),
+            ("option name Threads type spin min 1 max 8", None),
+            ("option name Odd type radio default 1", None),
+            ("info depth 1", None),
+        ];
+        for (line, option) in cases {
+            assert_eq!(EngineOption::parse(line), option, "{}", line);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn startup_banner_is_kept_apart_from_the_options() {
+        let engine = crate::mock_engine::mock_engine_script(
+            "",
+            r#"echo "MyEngine 1.0 by Author"
+echo "options: see README"
+while read -r line; do
+  case "$line" in
+    uci) echo "id name MyEngine 1.0"; echo "option name Hash type spin default 16 min 1 max 1024"; echo "uciok" ;;
+    quit) exit 0 ;;
+  esac
+done
+"#,
+        );
+        assert_eq!(
+            engine.banner,
+            ["MyEngine 1.0 by Author", "options: see README"]
+        );
+        assert_eq!(engine.name, "MyEngine 1.0");
+        assert!(matches!(
+            engine.engine_options.as_slice(),
+            [EngineOption::SPIN { value: 16, .. }]
+        ));
+        assert_eq!(engine.options()[0].name(), "Hash");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reported_name_is_the_default_name() {
+        let engine = crate::mock_engine::mock_engine("Mock 1.0", &[]);
+        assert_eq!(engine.reported_name.as_deref(), Some("Mock 1.0"));
+        let unnamed = Engine::new(&engine.path, "");
+        assert_eq!(unnamed.name, "Mock 1.0");
+        let named = Engine::new(&engine.path, "Mine");
+        assert_eq!(named.name, "Mine");
+    }
+
+    #[test]
+    fn copy_protection_error_fails_the_handshake() {
+        let mut stdout = std::io::Cursor::new(
+            "id name Fritz\ncopyprotection checking\ncopyprotection error\nuciok\n",
+        );
+        assert_eq!(
+            uci_handshake(&mut Vec::new(), &mut stdout),
+            Err(EngineError::CopyProtection)
+        );
+        let mut stdout = std::io::Cursor::new("id name Silent\n");
+        assert_eq!(
+            uci_handshake(&mut Vec::new(), &mut stdout),
+            Err(EngineError::NotUci)
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn analysis_stays_bounded() {
+        let mut engine =
+            crate::mock_engine::mock_engine("Chatty", &[]).with_analysis_limits(100, 8);
+        let pv = ["e2e4", "e7e5"].repeat(200).join(" ");
+        for depth in 1..=5000 {
+            engine.record_analysis(format!(
+                "info depth {} score cp 20 nodes 99 pv {}",
+                depth, pv
+            ));
+        }
+        engine.record_analysis("bestmove e2e4".to_string());
+
+        assert_eq!(engine.analysis.len(), 100);
+        assert!(engine.analysis.iter().all(|line| match line {
+            AnalysisLine::Depth { pv, .. } => pv.len() == 8,
+            _ => true,
+        }));
+        // the newest lines are the ones kept
+        match &engine.analysis[98] {
+            AnalysisLine::Depth { depth, .. } => assert_eq!(depth.as_deref(), Some("5000")),
+            other => panic!("expected a depth line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pv_runs_to_the_next_keyword() {
+        match AnalysisLine::new("info depth 3 pv e2e4 e7e5 g1f3 nps 1000".to_string()) {
+            Some(AnalysisLine::Depth { pv, .. }) => assert_eq!(pv, ["e2e4", "e7e5", "g1f3"]),
+            other => panic!("expected a depth line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bare_bestmove_is_the_null_move() {
+        for line in ["bestmove", "bestmove ponder e7e5"] {
+            match AnalysisLine::new(line.to_string()) {
+                Some(AnalysisLine::Move(best_move, _)) => assert_eq!(best_move, "0000"),
+                other => panic!("expected a bestmove, got {:?}", other),
+            }
+        }
+        match AnalysisLine::new("bestmove e2e4 ponder".to_string()) {
+            Some(AnalysisLine::Move(best_move, None)) => assert_eq!(best_move, "e2e4"),
+            other => panic!("expected a bestmove without ponder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn failed_spawns_are_retried_with_backoff() {
+        let engine = crate::mock_engine::mock_engine("Broken", &[])
+            .with_spawn_retry(2, Duration::from_millis(20));
+        // executable, but not a format the OS can run, which counts as transient
+        fs::write(&engine.path, [0u8, 1, 2, 3]).unwrap();
+        let start = std::time::Instant::now();
+        let error = engine.try_spawn_tagged_process(None, None).err();
+        assert!(matches!(error, Some(EngineError::SpawnFailed(_))));
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        // a missing binary fails at once
+        fs::remove_file(&engine.path).unwrap();
+        let start = std::time::Instant::now();
+        assert!(engine.try_spawn_tagged_process(None, None).is_err());
+        assert!(start.elapsed() < Duration::from_millis(20));
+
+        // the handshake of a new engine follows the policy it is given
+        let path = crate::mock_engine::mock_script("");
+        fs::write(&path, [0u8, 1, 2, 3]).unwrap();
+        let path = path.to_str().unwrap();
+        let start = std::time::Instant::now();
+        let error = Engine::try_new_with_spawn_retry(path, "Broken", 2, Duration::from_millis(20));
+        assert!(matches!(error.err(), Some(EngineError::SpawnFailed(_))));
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        let start = std::time::Instant::now();
+        assert!(
+            Engine::try_new_with_spawn_retry(path, "Broken", 0, Duration::from_secs(1)).is_err()
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn extensionless_engines_are_started() {
+        let engine = crate::mock_engine::mock_engine("Bare", &[]);
+        let bare = Path::new(&engine.path).with_extension("");
+        fs::rename(&engine.path, &bare).unwrap();
+        let engine = Engine::try_new(bare.to_str().unwrap(), "Bare").unwrap();
+        assert_eq!(engine.name, "Bare");
+
+        // any other extension is not taken for an executable
+        let script = bare.with_extension("sh");
+        fs::rename(&bare, &script).unwrap();
+        assert!(matches!(
+            Engine::try_new(script.to_str().unwrap(), "Script"),
+            Err(EngineError::NotExecutable(_))
+        ));
+    }
+
+    #[test]
+    fn unwritable_log_dir_is_an_error() {
+        let engine = crate::mock_engine::mock_engine("Logged", &[]);
+        // a file where the log directory should go
+        let log_dir = Path::new(&engine.path).to_path_buf();
+        let tag = ProcessTag {
+            round: None,
+            white: true,
+            engine_name: engine.name.clone(),
+        };
+        let error = engine
+            .try_spawn_tagged_process(Some(tag), Some(&log_dir))
+            .err();
+        assert!(matches!(error, Some(EngineError::LogFailed(_))));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn engines_without_uciok_can_be_assumed_to_speak_uci() {
+        use crate::game::{Game, TimeControl};
+        use queenfish::board::bishop_magic::init_bishop_magics;
+        use queenfish::board::rook_magic::init_rook_magics;
+        init_bishop_magics();
+        init_rook_magics();
+
+        // lists an option, then never confirms the handshake
+        let path = crate::mock_engine::mock_script(
+            r#"set -- e7e5 d8h4
+while read -r line; do
+  case "$line" in
+    uci) echo "id name Mute"; echo "option name Hash type spin default 16 min 1 max 64" ;;
+    isready) echo "readyok" ;;
+    go*) echo "bestmove $1"; shift ;;
+    quit) exit 0 ;;
+  esac
+done
+"#,
+        );
+        let black = Engine::try_new_assuming_uci(path.to_str().unwrap(), "").unwrap();
+        assert!(black.assume_uci);
+        assert_eq!(black.name, "engine");
+        assert!(matches!(
+            black.engine_options.as_slice(),
+            [EngineOption::SPIN { max: Some(64), .. }]
+        ));
+
+        let white = crate::mock_engine::mock_engine("White", &["f2f3", "g2g4"]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10)).play();
+        assert_eq!(result.moves_list(), ["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(result.result(), -1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn line_callback_sees_every_line() {
+        let engine = crate::mock_engine::MockEngine::new("Talker")
+            .with_go(
+                r#"echo "info depth 1 score cp 12 pv e2e4"; echo "info string custom 42"; echo "bestmove e2e4""#,
+            )
+            .build();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut process = engine.spawn_process();
+        let sink = seen.clone();
+        process.on_line(Box::new(move |line| {
+            sink.lock().unwrap().push(line.to_string())
+        }));
+        process.send_command("uci\n");
+        assert!(process.wait_for_uciok());
+        process.send_command("go depth 1\n");
+        let best = crate::game::wait_for_bestmove(&process, None).unwrap();
+        assert_eq!(best.best_move, "e2e4");
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "id name Talker",
+                "uciok",
+                "info depth 1 score cp 12 pv e2e4",
+                "info string custom 42",
+                "bestmove e2e4",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_single_position_is_analyzed() {
+        use crate::mock_engine::commands_received;
+        let engine = crate::mock_engine::MockEngine::new("Analyst")
+            .with_go(
+                r#"
+      echo "info depth 6 score cp 20 nodes 900 pv d2d4 d7d5"
+      echo "info depth 7 score cp 35 nodes 2000 pv e2e4 e7e5 g1f3"
+      echo "info depth 7 currmove e2e4 currmovenumber 1"
+      echo "bestmove e2e4 ponder e7e5""#,
+            )
+            .build();
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+        let result = engine.analyze(fen, TimeControl::TimePerMove(50));
+        assert_eq!(
+            result,
+            AnalysisResult {
+                best_move: "e2e4".to_string(),
+                ponder: Some("e7e5".to_string()),
+                score: Some(Score::Cp(35)),
+                depth: Some(7),
+                pv: vec!["e2e4".to_string(), "e7e5".to_string(), "g1f3".to_string()],
+            }
+        );
+        let commands = commands_received(&engine);
+        assert!(commands.contains(&format!("position fen {}", fen)));
+        assert!(commands.contains(&"go movetime 50".to_string()));
+
+        assert!(matches!(
+            engine.try_analyze("8/8/8/8/8/8/8/8 w - - 0 1", TimeControl::TimePerMove(50)),
+            Err(EngineError::InvalidPosition(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dropped_processes_are_told_to_quit_then_killed() {
+        // takes a moment to shut down cleanly, leaving a file behind when it does
+        let polite = crate::mock_engine::mock_engine_script(
+            "Polite",
+            r#"while read -r line; do
+  case "$line" in
+    uci) echo "id name Polite"; echo "uciok" ;;
+    quit) sleep 0.02; touch "$(dirname "$0")/quit"; exit 0 ;;
+  esac
+done
+"#,
+        );
+        // the handshake in Engine::new quit already
+        let quit_file = Path::new(&polite.path).with_file_name("quit");
+        fs::remove_file(&quit_file).unwrap();
+        drop(polite.spawn_process());
+        assert!(quit_file.exists());
+
+        // quits the handshake, but every later process ignores quit and would run forever
+        let stubborn = crate::mock_engine::mock_engine_script(
+            "Stubborn",
+            r#"dir="$(dirname "$0")"
+first=1
+[ -e "$dir/handshaken" ] && first=0
+touch "$dir/handshaken"
+while :; do
+  read -r line || { sleep 0.01; continue; }
+  case "$line" in
+    uci) echo "id name Stubborn"; echo "uciok" ;;
+    go*) echo $$ > "$dir/pid" ;;
+    quit) [ $first = 1 ] && exit 0 ;;
+  esac
+done
+"#,
+        );
+        let process = stubborn.spawn_process();
+        process.send_command("go\n");
+        let pid_file = Path::new(&stubborn.path).with_file_name("pid");
+        let started = Instant::now();
+        while !fs::read_to_string(&pid_file).is_ok_and(|pid| pid.ends_with('\n')) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        let dropped = Instant::now();
+        drop(process);
+        assert!(dropped.elapsed() < QUIT_GRACE * 5);
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .status()
+            .unwrap()
+            .success();
+        assert!(!alive);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn batches_survive_crashing_processes() {
+        // crashes on the rook position the first time it sees it, and on the doomed one always
+        let engine = crate::mock_engine::MockEngine::new("Pool")
+            .with_case(r#""position fen 4k3/8/8/8/8/8/8/R3K3"*) position=rook ;;"#)
+            .with_case(r#""position fen 4k3/8/8/8/8/8/8/4K2R"*) position=doomed ;;"#)
+            .with_case("position*) position=start ;;")
+            .with_go(
+                r#"
+      crashed="$(dirname "$0")/crashed"
+      case "$position" in
+        rook) if [ -e "$crashed" ]; then echo "bestmove a1a8"; else touch "$crashed"; exit 1; fi ;;
+        doomed) exit 1 ;;
+        *) echo "info depth 3 score cp 25 pv e2e4 e7e5"; echo "bestmove e2e4" ;;
+      esac"#,
+            )
+            .build();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+            "not a position",
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ]
+        .map(str::to_string);
+
+        let results = engine.analyze_batch(&fens, TimeControl::TimePerMove(10), 2);
+        assert_eq!(results.len(), 5);
+        for index in [0, 4] {
+            let result = results[index].as_ref().unwrap();
+            assert_eq!(result.best_move, "e2e4");
+            assert_eq!(result.score, Some(Score::Cp(25)));
+        }
+        assert_eq!(results[1].as_ref().unwrap().best_move, "a1a8");
+        assert!(matches!(results[2], Err(EngineError::InvalidPosition(_))));
+        assert_eq!(results[3], Err(EngineError::NoBestMove));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn best_move_off_the_pv_is_flagged() {
+        let engine = crate::mock_engine::MockEngine::new("Fickle")
+            .with_go(r#"echo "info depth 9 score cp 30 pv e2e4 e7e5"; echo "bestmove d2d4""#)
+            .build();
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let result = engine.analyze(fen, TimeControl::TimePerMove(10));
+        assert_eq!(result.best_move, "d2d4");
+        assert_eq!(result.pv.first().map(String::as_str), Some("e2e4"));
+        assert!(result.best_move_left_pv());
+
+        let agreeing = AnalysisResult {
+            best_move: "e2e4".to_string(),
+            ..result
+        };
+        assert!(!agreeing.best_move_left_pv());
+    }
+
+    // Serves a tiny UCI engine on a local port, a fresh one per connection, that plays `moves`
+    // in turn.
+    fn tcp_engine(name: &'static str, moves: &'static [&'static str]) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                thread::spawn(move || {
+                    let mut writer = stream.try_clone().unwrap();
+                    let mut moves = moves.iter();
+                    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                        let reply = match line.split_whitespace().next() {
+                            Some("uci") => format!(
+                                "id name {}\noption name Hash type spin default 16 min 1 max 1024\nuciok\n",
+                                name
+                            ),
+                            Some("isready") => "readyok\n".to_string(),
+                            Some("go") => format!("bestmove {}\n", moves.next().unwrap_or(&"0000")),
+                            Some("quit") => break,
+                            _ => continue,
+                        };
+                        if writer.write_all(reply.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn remote_engines_play_over_tcp() {
+        use crate::game::{Game, TimeControl};
+        use queenfish::board::bishop_magic::init_bishop_magics;
+        use queenfish::board::rook_magic::init_rook_magics;
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = Engine::remote(&tcp_engine("Echo White", &["f2f3", "g2g4"]), "");
+        assert_eq!(white.name, "Echo White");
+        assert_eq!(white.location, EngineLocation::Remote(white.path.clone()));
+        assert!(matches!(
+            white.engine_options.as_slice(),
+            [EngineOption::SPIN { value: 16, .. }]
+        ));
+        let black = Engine::remote(&tcp_engine("Echo Black", &["e7e5", "d8h4"]), "Black");
+
+        let result = Game::new(white, black, TimeControl::TimePerMove(10)).play();
+        assert_eq!(result.moves_list(), ["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(result.result(), -1);
+
+        // nothing listening, even after the retries
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_address = closed.local_addr().unwrap().to_string();
+        drop(closed);
+        assert!(matches!(
+            Engine::try_remote(&closed_address, ""),
+            Err(EngineError::SpawnFailed(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strength_is_limited_within_the_advertised_elo_range() {
+        use crate::mock_engine::commands_received;
+        let engine = crate::mock_engine::MockEngine::new("Weak")
+            .with_uci_line("option name UCI_LimitStrength type check default false")
+            .with_uci_line("option name UCI_Elo type spin default 1350 min 1320 max 3190")
+            .with_go(r#"echo "bestmove e2e4""#)
+            .build();
+
+        assert!(matches!(
+            engine.clone().try_limit_strength(1000),
+            Err(EngineError::EloOutOfRange {
+                elo: 1000,
+                min: Some(1320),
+                max: Some(3190),
+                ..
+            })
+        ));
+        let weak = engine.limit_strength(1500);
+        assert_eq!(
+            weak.configured_options,
+            [
+                ("UCI_LimitStrength".to_string(), "true".to_string()),
+                ("UCI_Elo".to_string(), "1500".to_string())
+            ]
+        );
+        assert!(weak.check_configured_options().is_empty());
+        weak.analyze(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            TimeControl::TimePerMove(50),
+        );
+        let commands = commands_received(&weak);
+        let uciok = commands.iter().position(|c| c == "uci").unwrap();
+        let go = commands.iter().position(|c| c.starts_with("go")).unwrap();
+        let limit = commands
+            .iter()
+            .position(|c| c == "setoption name UCI_LimitStrength value true")
+            .unwrap();
+        let elo = commands
+            .iter()
+            .position(|c| c == "setoption name UCI_Elo value 1500")
+            .unwrap();
+        assert!(uciok < limit && limit < elo && elo < go);
+
+        let plain = crate::mock_engine::mock_engine("Plain", &[]);
+        assert!(matches!(
+            plain.try_limit_strength(1500),
+            Err(EngineError::UnknownOption { name, .. }) if name == "UCI_LimitStrength"
+        ));
+    }
 }
@@ -0,0 +1,253 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    NotFound(String),      // path
+    NotExecutable(String), // path
+    SpawnFailed(String),   // os error
+    LogFailed(String),     // os error creating the transcript under the log dir
+    NotUci,                // output ended before `uciok`
+    CopyProtection,        // the engine reported `copyprotection error`
+    UnknownOption {
+        engine: String,
+        name: String,
+    },
+    InvalidOptionValue {
+        engine: String,
+        name: String,
+        value: String,
+    },
+    Oversubscribed {
+        threads: u32, // summed over the engines running at the same time
+        cpus: u32,
+    },
+    EloOutOfRange {
+        engine: String,
+        elo: i32,
+        min: Option<i32>, // as advertised for UCI_Elo
+        max: Option<i32>,
+    },
+    InvalidPosition(FenError),
+    NoBestMove, // never answered a search, even when told to stop
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::NotFound(path) => write!(f, "Engine path does not exist: {}", path),
+            EngineError::NotExecutable(path) => {
+                write!(f, "Engine file is not an executable: {}", path)
+            }
+            EngineError::SpawnFailed(error) => {
+                write!(f, "Failed to start engine process: {}", error)
+            }
+            EngineError::LogFailed(error) => write!(f, "Failed to create engine log: {}", error),
+            EngineError::NotUci => write!(f, "Engine is not UCI compatible"),
+            EngineError::CopyProtection => write!(f, "Engine failed its copy protection check"),
+            EngineError::UnknownOption { engine, name } => {
+                write!(f, "{} has no option named '{}'", engine, name)
+            }
+            EngineError::InvalidOptionValue {
+                engine,
+                name,
+                value,
+            } => write!(
+                f,
+                "'{}' is not a valid value of {}'s {}",
+                value, engine, name
+            ),
+            EngineError::Oversubscribed { threads, cpus } => write!(
+                f,
+                "Engines use {} threads at once but only {} cpus are available",
+                threads, cpus
+            ),
+            EngineError::EloOutOfRange {
+                engine,
+                elo,
+                min,
+                max,
+            } => {
+                let bound = |bound: &Option<i32>| bound.map_or("?".to_string(), |b| b.to_string());
+                write!(
+                    f,
+                    "{} cannot play at {} Elo, its UCI_Elo range is {}..{}",
+                    engine,
+                    elo,
+                    bound(min),
+                    bound(max)
+                )
+            }
+            EngineError::InvalidPosition(error) => write!(f, "Cannot analyze: {}", error),
+            EngineError::NoBestMove => write!(f, "Engine never answered with a bestmove"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    Malformed(String),
+    MissingKing { white: bool },
+    TooManyKings { white: bool },
+    PawnOnBackRank,
+    OpponentInCheck, // the side that just moved is in check
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let color = |white: &bool| if *white { "white" } else { "black" };
+        match self {
+            FenError::Malformed(reason) => write!(f, "Malformed FEN: {}", reason),
+            FenError::MissingKing { white } => write!(f, "FEN has no {} king", color(white)),
+            FenError::TooManyKings { white } => {
+                write!(f, "FEN has more than one {} king", color(white))
+            }
+            FenError::PawnOnBackRank => write!(f, "FEN has a pawn on the first or last rank"),
+            FenError::OpponentInCheck => write!(f, "FEN leaves the side not to move in check"),
+            FenError::InvalidCastling(castling) => {
+                write!(
+                    f,
+                    "FEN castling rights '{}' don't match the board",
+                    castling
+                )
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "FEN en passant square '{}' is impossible", square)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    Io(String), // os error
+    Malformed(String),
+    InvalidFen(FenError),
+    IllegalMove { ply: usize, san: String }, // ply counted from 1
+    Engine(EngineError),                     // the analyzing engine failed to start
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Io(error) => write!(f, "Failed to read PGN: {}", error),
+            PgnError::Malformed(reason) => write!(f, "Malformed PGN: {}", reason),
+            PgnError::InvalidFen(error) => write!(f, "PGN has an invalid FEN tag: {}", error),
+            PgnError::IllegalMove { ply, san } => {
+                write!(f, "PGN move '{}' at ply {} is not legal", san, ply)
+            }
+            PgnError::Engine(error) => write!(f, "Cannot analyze PGN: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpeningBookError {
+    Io(String),                                       // os error
+    InvalidPosition { line: usize, error: FenError }, // line counted from 1
+}
+
+impl fmt::Display for OpeningBookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpeningBookError::Io(error) => write!(f, "Failed to read opening book: {}", error),
+            OpeningBookError::InvalidPosition { line, error } => {
+                write!(f, "Opening book line {}: {}", line, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OpeningBookError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpeningError {
+    InvalidFen {
+        index: usize,
+        error: FenError,
+    },
+    IllegalMove {
+        index: usize,
+        ply: usize, // counted from 1
+        uci: String,
+    },
+}
+
+impl fmt::Display for OpeningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpeningError::InvalidFen { index, error } => write!(f, "Opening {}: {}", index, error),
+            OpeningError::IllegalMove { index, ply, uci } => write!(
+                f,
+                "Opening {}: move '{}' at ply {} is not legal",
+                index, uci, ply
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OpeningError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeControlError {
+    Empty,
+    InvalidNumber(String), // the part that didn't parse
+    Unknown(String),
+    Tablebase, // `tb`, which configures tablebases in cutechess
+}
+
+impl fmt::Display for TimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeControlError::Empty => write!(f, "Time control is empty"),
+            TimeControlError::InvalidNumber(number) => {
+                write!(f, "'{}' is not a valid number in a time control", number)
+            }
+            TimeControlError::Unknown(text) => write!(
+                f,
+                "Unknown time control '{}', expected inf, [moves/]time[+increment], movetime=ms, nodes=n, depth=n or mate=n",
+                text
+            ),
+            TimeControlError::Tablebase => write!(
+                f,
+                "'tb' sets the tablebases, not a time control; see Game::with_tablebase"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimeControlError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TournamentStateError {
+    Io(String), // os error
+    Malformed(String),
+    Mismatch(String), // the setting the saved tournament had differently
+}
+
+impl fmt::Display for TournamentStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TournamentStateError::Io(error) => {
+                write!(f, "Failed to access tournament state: {}", error)
+            }
+            TournamentStateError::Malformed(reason) => {
+                write!(f, "Malformed tournament state: {}", reason)
+            }
+            TournamentStateError::Mismatch(setting) => write!(
+                f,
+                "Tournament state was saved by a tournament with a different {}",
+                setting
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TournamentStateError {}
@@ -0,0 +1,272 @@
+use crate::error::FenError;
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+// Pieces indexed like queenfish squares: a1 = 0, h1 = 7, a8 = 56.
+type Squares = [Option<char>; 64];
+
+/// Checks that `fen` describes a position a game can start from: well formed, one king per side,
+/// no pawns on the back ranks, the side that just moved not left in check, castling rights
+/// matching the king and rook squares and a plausible en passant square.
+pub fn validate_fen(fen: &str) -> Result<(), FenError> {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    if !(4..=6).contains(&fields.len()) {
+        return Err(FenError::Malformed(format!(
+            "expected 4 to 6 fields, found {}",
+            fields.len()
+        )));
+    }
+    let squares = parse_placement(fields[0])?;
+    let white_to_move = match fields[1] {
+        "w" => true,
+        "b" => false,
+        side => {
+            return Err(FenError::Malformed(format!(
+                "unknown side to move '{}'",
+                side
+            )));
+        }
+    };
+
+    for (king, white) in [('K', true), ('k', false)] {
+        match squares.iter().filter(|&&piece| piece == Some(king)).count() {
+            0 => return Err(FenError::MissingKing { white }),
+            1 => {}
+            _ => return Err(FenError::TooManyKings { white }),
+        }
+    }
+    if (0..8)
+        .chain(56..64)
+        .any(|square| matches!(squares[square], Some('P' | 'p')))
+    {
+        return Err(FenError::PawnOnBackRank);
+    }
+    let opponent_king = if white_to_move { 'k' } else { 'K' };
+    let opponent_king_square = squares
+        .iter()
+        .position(|&piece| piece == Some(opponent_king))
+        .unwrap();
+    if is_attacked(&squares, opponent_king_square, white_to_move) {
+        return Err(FenError::OpponentInCheck);
+    }
+
+    if fields[2] != "-" {
+        for right in fields[2].chars() {
+            let (king_square, rook_square, rook) = match right {
+                'K' => (4, 7, 'R'),
+                'Q' => (4, 0, 'R'),
+                'k' => (60, 63, 'r'),
+                'q' => (60, 56, 'r'),
+                _ => return Err(FenError::InvalidCastling(fields[2].to_string())),
+            };
+            let king = if rook == 'R' { 'K' } else { 'k' };
+            if squares[king_square] != Some(king) || squares[rook_square] != Some(rook) {
+                return Err(FenError::InvalidCastling(fields[2].to_string()));
+            }
+        }
+    }
+
+    // the capturing side is the one to move, so the square is behind a pawn that just advanced two
+    let en_passant_rank = if white_to_move { '6' } else { '3' };
+    if fields[3] != "-"
+        && !matches!(fields[3].as_bytes(), [b'a'..=b'h', rank] if *rank as char == en_passant_rank)
+    {
+        return Err(FenError::InvalidEnPassant(fields[3].to_string()));
+    }
+
+    if fields[4..]
+        .iter()
+        .any(|number| number.parse::<u32>().is_err())
+    {
+        return Err(FenError::Malformed(
+            "move counters must be numbers".to_string(),
+        ));
+    }
+    Ok(())
+} //
+
+/// Whether the side to move in `fen` is in check, worked out from the piece placement alone and
+/// independently of queenfish, so it can be used to double-check the board's own answer.
+pub fn side_to_move_in_check(fen: &str) -> Result<bool, FenError> {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    if fields.len() < 2 {
+        return Err(FenError::Malformed(
+            "expected a placement and a side to move".to_string(),
+        ));
+    }
+    let squares = parse_placement(fields[0])?;
+    let white = match fields[1] {
+        "w" => true,
+        "b" => false,
+        side => {
+            return Err(FenError::Malformed(format!(
+                "unknown side to move '{}'",
+                side
+            )));
+        }
+    };
+    let king = if white { 'K' } else { 'k' };
+    let king_square = squares
+        .iter()
+        .position(|&piece| piece == Some(king))
+        .ok_or(FenError::MissingKing { white })?;
+    Ok(is_attacked(&squares, king_square, !white))
+} //
+
+fn parse_placement(placement: &str) -> Result<Squares, FenError> {
+    let mut squares = [None; 64];
+    let ranks = placement.split('/').collect::<Vec<_>>();
+    if ranks.len() != 8 {
+        return Err(FenError::Malformed(format!(
+            "expected 8 ranks, found {}",
+            ranks.len()
+        )));
+    }
+    // ranks are listed from the 8th down to the 1st
+    for (row, rank) in ranks.iter().enumerate() {
+        let mut file = 0;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10).filter(|empty| (1..=8).contains(empty)) {
+                file += empty as usize;
+            } else if "PNBRQKpnbrqk".contains(c) {
+                if file < 8 {
+                    squares[(7 - row) * 8 + file] = Some(c);
+                }
+                file += 1;
+            } else {
+                return Err(FenError::Malformed(format!("unexpected '{}' in board", c)));
+            }
+        }
+        if file != 8 {
+            return Err(FenError::Malformed(format!(
+                "rank {} has {} squares",
+                8 - row,
+                file
+            )));
+        }
+    }
+    Ok(squares)
+} //
+
+// Whether any piece of the given color attacks `square`.
+fn is_attacked(squares: &Squares, square: usize, by_white: bool) -> bool {
+    let (rank, file) = ((square / 8) as i32, (square % 8) as i32);
+    let piece_at = |rank: i32, file: i32| {
+        if (0..8).contains(&rank) && (0..8).contains(&file) {
+            squares[(rank * 8 + file) as usize]
+        } else {
+            None
+        }
+    };
+    let own = |piece: char| {
+        if by_white {
+            piece
+        } else {
+            piece.to_ascii_lowercase()
+        }
+    };
+
+    // white pawns attack upwards, so they sit one rank below the square they attack
+    let pawn_rank = if by_white { rank - 1 } else { rank + 1 };
+    if [file - 1, file + 1]
+        .iter()
+        .any(|&pawn_file| piece_at(pawn_rank, pawn_file) == Some(own('P')))
+    {
+        return true;
+    }
+    for (steps, piece) in [(KNIGHT_STEPS, 'N'), (KING_STEPS, 'K')] {
+        if steps
+            .iter()
+            .any(|(dr, df)| piece_at(rank + dr, file + df) == Some(own(piece)))
+        {
+            return true;
+        }
+    }
+    for (dr, df) in KING_STEPS {
+        let slider = if dr == 0 || df == 0 { 'R' } else { 'B' };
+        let (mut r, mut f) = (rank + dr, file + df);
+        while (0..8).contains(&r) && (0..8).contains(&f) {
+            if let Some(piece) = piece_at(r, f) {
+                if piece == own(slider) || piece == own('Q') {
+                    return true;
+                }
+                break;
+            }
+            r += dr;
+            f += df;
+        }
+    }
+    false
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn legal_positions_pass() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/8/4Pp2/8/PPPP2PP/RNBQKBNR b KQkq e3 0 3",
+            "4k3/8/8/8/8/8/8/4K2R b K -",
+        ] {
+            assert_eq!(validate_fen(fen), Ok(()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn kings_are_required_once_per_side() {
+        assert_eq!(
+            validate_fen("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1"),
+            Err(FenError::MissingKing { white: false })
+        );
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/8/K3K3 w - - 0 1"),
+            Err(FenError::TooManyKings { white: true })
+        );
+    }
+
+    #[test]
+    fn illegal_positions_fail() {
+        // white to move while black's king is attacked by the rook
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            Err(FenError::OpponentInCheck)
+        );
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1"),
+            Err(FenError::InvalidCastling("K".to_string()))
+        );
+        assert_eq!(
+            validate_fen("4k3/8/8/8/8/8/8/4K3 w - e3 0 1"),
+            Err(FenError::InvalidEnPassant("e3".to_string()))
+        );
+        assert_eq!(
+            validate_fen("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::PawnOnBackRank)
+        );
+        assert!(matches!(
+            validate_fen("4k3/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::Malformed(_))
+        ));
+    }
+}
@@ -1,10 +1,182 @@
-use crate::engine::Engine;
+use crate::engine::{
+    AnalysisLine, Engine, EngineHandle, LineCallback, ProcessTag, Score, canonical_uci,
+};
+use crate::error::{FenError, OpeningError, TimeControlError};
+use crate::fen::{side_to_move_in_check, validate_fen};
+use crate::move_cache::MoveCache;
+use crate::oracle::{OracleReport, consult_oracle};
+use crate::polyglot::{PolyglotBook, SplitMix64, polyglot_key};
+#[cfg(feature = "syzygy")]
+use crate::tablebase::Tablebase;
 use queenfish::board::{Board, Turn, Move};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Copy)]
+pub(crate) const DEFAULT_SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+const STOP_GRACE: Duration = Duration::from_secs(1);
+pub(crate) const DEFAULT_MAX_PLIES: usize = 400;
+const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(10);
+const DEFAULT_TIMEOUT_GRACE: Duration = Duration::from_millis(50);
+const DEFAULT_MOVETIME_MARGIN: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TimeControl {
     Infinite,
     TimePerMove(i32), // in ms
+    Mate(u32),        // search for a mate in n moves
+    /// Search n plies deep. Like `Nodes`, this ignores wall-clock time: no clocks are kept or
+    /// sent and nobody loses on time, only `Game::with_search_timeout` catches a hung engine. A
+    /// deterministic engine plays the same game on any hardware, the usual way to test a change.
+    Depth(u32),
+    /// Search n nodes. Deterministic and blind to the clock in the same way as `Depth`.
+    Nodes(u64),
+    Increment { base_ms: i32, inc_ms: i32 },
+    // `moves` moves in `base_ms`, after which the clock gets `base_ms` more, plus an increment
+    MovesToGo { moves: u32, base_ms: i32, inc_ms: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition, // the same position, with the same side to move and rights, a third time
+    FiftyMoves, // fifty moves by each side without a capture or a pawn move
+    InsufficientMaterial, // no mate is possible: bare kings, one minor piece, same-colored bishops
+    TimeForfeit,
+    EngineCrash,     // stopped answering, or never produced a bestmove
+    IllegalMove,     // bestmove missing, null or not legal in the position
+    Adjudicated,     // by a tablebase, or by score with `Game::with_resign_adjudication`
+    DrawAdjudicated, // both sides' scores near zero for long, see `Game::with_draw_adjudication`
+    Aborted,         // stopped through the cancel token; not a real result
+    MaxLength,       // drawn after `Game::with_max_plies` plies; moves_list holds exactly that many
+}
+
+/// How a game ended for the two sides. Converts to and from the older `i32` results: 1 for a
+/// white win, -1 for a black win, 0 for a draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl Outcome {
+    /// The side to move `turn` has lost: mated, flagged or forfeited.
+    pub fn loss_for(turn: Turn) -> Self {
+        match turn {
+            Turn::WHITE => Outcome::BlackWins,
+            Turn::BLACK => Outcome::WhiteWins,
+        }
+    } //
+
+    pub fn is_decisive(self) -> bool {
+        self != Outcome::Draw
+    } //
+
+    /// The PGN result token: `1-0`, `0-1` or `1/2-1/2`.
+    pub fn pgn_token(self) -> &'static str {
+        match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    } //
+}
+
+// any positive result is a white win and any negative one a black win
+impl From<i32> for Outcome {
+    fn from(result: i32) -> Self {
+        match result.signum() {
+            1 => Outcome::WhiteWins,
+            -1 => Outcome::BlackWins,
+            _ => Outcome::Draw,
+        }
+    }
+}
+
+impl From<Outcome> for i32 {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::WhiteWins => 1,
+            Outcome::BlackWins => -1,
+            Outcome::Draw => 0,
+        }
+    }
+}
+
+/// When repetitions and quiet moves draw a game.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DrawRules {
+    /// Drawn as soon as a draw could be claimed: on the third repetition, or after fifty moves
+    /// by each side without a capture or pawn move. Engine matches are run this way, as engines
+    /// can't claim and an arbiter always would.
+    #[default]
+    Claimed,
+    /// Only the draws FIDE makes automatic: the fifth repetition, or seventy-five moves by each
+    /// side. The claimable thresholds are played through.
+    StrictFide,
+}
+
+impl DrawRules {
+    // occurrences of a position that draw the game
+    fn repetitions(self) -> usize {
+        match self {
+            DrawRules::Claimed => 3,
+            DrawRules::StrictFide => 5,
+        }
+    } //
+
+    // plies without a capture or pawn move that draw the game
+    fn quiet_plies(self) -> u32 {
+        match self {
+            DrawRules::Claimed => 100,
+            DrawRules::StrictFide => 150,
+        }
+    } //
+}
+
+/// How a searched move came about: the time from sending `go` (or `ponderhit`) to the
+/// `bestmove`, and the last depth, selective depth, node count and speed the engine reported
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveStats {
+    pub time: Duration,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub seldepth: Option<u32>,
+    pub nps: Option<u64>,
+}
+
+/// What an engine is told to forget before each game. `NewGame` is the safe choice: engines
+/// clear their hash and any learned state on `ucinewgame`. `ClearHashOnly` presses the common
+/// `Clear Hash` button instead, for engines that do more than that on `ucinewgame` (reloading
+/// networks, say) and are slow about it. `Nothing` saves that time too, at the risk of one game's
+/// hash entries steering the next; it is meant for deterministic, node-limited runs where the
+/// throughput matters.
+///
+/// Every game currently starts its own processes, so there is no state carried over yet and the
+/// policy only decides what is sent; it matters once processes outlive a game.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BetweenGames {
+    #[default]
+    NewGame,
+    ClearHashOnly,
+    Nothing,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Opening {
+    pub fen: Option<String>, // position the moves start from, None for the standard start
+    pub moves: Vec<String>,  // uci moves played before the engines take over
 }
 
 pub struct Game {
@@ -12,24 +184,246 @@ pub struct Game {
     black: Engine,
     moves_list: Vec<String>,
     board: Board,
+    start_fen: Option<String>, // None for the standard start position
+    position: PositionCommand, // kept in step with moves_list by position_command
+    legal_moves: MoveCache,
+    position_keys: Vec<u64>, // polyglot key of every position reached, for repetitions
+    halfmove_clock: u32,     // plies since the last capture or pawn move
+    draw_rules: DrawRules,
     time_control: TimeControl,
+    opening_index: Option<usize>,
+    book_ply_count: usize,
+    scores: Vec<Option<Score>>,
+    move_stats: Vec<Option<MoveStats>>,
+    pvs: Vec<Vec<String>>,
+    search_timeout: Duration,
+    white_slot: usize,
+    black_slot: usize,
+    tablebase_path: Option<PathBuf>,
+    #[cfg(feature = "syzygy")]
+    tb_adjudicate: bool,
+    clocks: Option<[i64; 2]>, // remaining ms for white and black under TimeControl::Increment
+    move_overhead: Duration,  // held back from the clocks the engines are told about
+    timeout_grace: Duration,  // how far past zero a clock may run before the side is flagged
+    movetime_margin: Duration, // how far past a fixed movetime a search may run
+    time_jitter: Option<(u32, u64)>, // most ms a movetime may move either way, and the seed
+    ponder: bool,
+    max_plies: Option<usize>,
+    resign: Option<(i32, u32)>, // centipawns a side must be down, and for how many of its moves
+    draw_adjudication: Option<(i32, u32, u32)>, // most centipawns either way, plies, last move
+    round: Option<i32>,
+    log_dir: Option<PathBuf>,
+    cancel_token: Option<Arc<AtomicBool>>,
+    verify_endings: bool,
+    oracle: Option<(Engine, TimeControl)>,
+    between_games: BetweenGames,
+    line_callbacks: [Option<LineCallback>; 2], // white's and black's, see `with_line_callback`
 }
 
+/// With the `serde` feature, serializes with everything but what `replay` needs, so a result
+/// read back from JSON can be looked at but not replayed.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GameResult {
     white: String,
     black: String,
     moves_list: Vec<String>,
-    result: i32,
+    outcome: Outcome,
+    opening_index: Option<usize>,
+    book_ply_count: usize, // leading moves of moves_list that came from an opening or book
+    scores: Vec<Option<Score>>, // last reported score for each move in moves_list
+    move_stats: Vec<Option<MoveStats>>, // likewise, None for opening and book moves
+    #[cfg_attr(feature = "serde", serde(default))] // not in the first state files
+    pvs: Vec<Vec<String>>, // likewise the last pv, empty for opening and book moves
+    white_slot: usize,
+    black_slot: usize,
+    reason: Termination,
+    clocks: Option<[i64; 2]>, // remaining ms for white and black when the game ended
+    start_fen: Option<String>,
+    final_fen: String,
+    ending_mismatch: Option<String>, // why the reference check disagreed with the arena's ending
+    #[cfg_attr(feature = "serde", serde(default))]
+    oracle_report: Option<OracleReport>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    engine_stderr: Vec<String>, // the losing engine's, in a game lost to a crash, illegal move or flag
+    #[cfg_attr(feature = "serde", serde(skip))]
+    setup: Option<Box<ReplaySetup>>, // None once the result has been through serde
 }
 impl GameResult {
     pub fn winner(&self) -> String {
-        match self.result {
-            1 => self.white.clone(),
-            -1 => self.black.clone(),
-            _ => String::new(),
+        match self.outcome {
+            Outcome::WhiteWins => self.white.clone(),
+            Outcome::BlackWins => self.black.clone(),
+            Outcome::Draw => String::new(),
+        }
+    }
+    /// Slot of the winning engine, independent of its name.
+    pub fn winner_slot(&self) -> Option<usize> {
+        match self.outcome {
+            Outcome::WhiteWins => Some(self.white_slot),
+            Outcome::BlackWins => Some(self.black_slot),
+            Outcome::Draw => None,
         }
     }
+    pub fn white_slot(&self) -> usize {
+        self.white_slot
+    }
+    pub fn black_slot(&self) -> usize {
+        self.black_slot
+    }
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+    /// `outcome` as 1, -1 or 0.
+    pub fn result(&self) -> i32 {
+        self.outcome.into()
+    }
+    pub fn moves_list(&self) -> &[String] {
+        &self.moves_list
+    }
+    pub fn white(&self) -> &str {
+        &self.white
+    }
+    pub fn black(&self) -> &str {
+        &self.black
+    }
+    pub fn opening_index(&self) -> Option<usize> {
+        self.opening_index
+    }
+    /// How many of the first moves were set up from an opening or book rather than searched.
+    pub fn book_ply_count(&self) -> usize {
+        self.book_ply_count
+    }
+    pub fn scores(&self) -> &[Option<Score>] {
+        &self.scores
+    }
+    /// Search time, depth and nodes of each move in `moves_list`; None for opening and book
+    /// moves.
+    pub fn move_stats(&self) -> &[Option<MoveStats>] {
+        &self.move_stats
+    }
+    /// The search time of each move in `moves_list` in milliseconds, from `go` (or `ponderhit`)
+    /// to `bestmove`; None for opening and book moves.
+    pub fn move_times_ms(&self) -> Vec<Option<u32>> {
+        self.move_stats
+            .iter()
+            .map(|stats| stats.map(|stats| stats.time.as_millis().min(u32::MAX as u128) as u32))
+            .collect()
+    }
+    /// The last principal variation reported for each move in `moves_list`, starting with the
+    /// move itself as the engine saw it; empty for opening and book moves and for engines that
+    /// sent none.
+    pub fn pvs(&self) -> &[Vec<String>] {
+        &self.pvs
+    }
+    pub fn reason(&self) -> Termination {
+        self.reason
+    }
+    pub fn clocks(&self) -> Option<[i64; 2]> {
+        self.clocks
+    }
+    pub fn start_fen(&self) -> Option<&str> {
+        self.start_fen.as_deref()
+    }
+    /// Position the game ended in (queenfish FEN, without move counters).
+    pub fn final_fen(&self) -> &str {
+        &self.final_fen
+    }
+    /// Set when `Game::with_ending_verification` was on and the reference check disagreed with
+    /// the checkmate or stalemate the arena declared; the result should not be trusted.
+    pub fn ending_mismatch(&self) -> Option<&str> {
+        self.ending_mismatch.as_deref()
+    }
+    /// The oracle's verdict on every searched move, when the game had one, see
+    /// `Game::with_oracle`.
+    pub fn oracle_report(&self) -> Option<&OracleReport> {
+        self.oracle_report.as_ref()
+    }
+    /// What the losing engine wrote to stderr, when it lost by crashing, by an illegal move or
+    /// on time: usually the best clue to what went wrong. Empty for any other ending.
+    pub fn engine_stderr(&self) -> &[String] {
+        &self.engine_stderr
+    }
+
+    /// Plays the game again from the same start: the same engines and settings, the same start
+    /// position and the same opening or book moves, for checking whether a suspicious result
+    /// reproduces. Only a `Depth`, `Nodes` or `Mate` search can repeat the game move for move;
+    /// on the clock the engines search a little differently each time, and the replay is
+    /// expected to drift. None for a deserialized result, which doesn't know its engines.
+    pub fn replay(&self) -> Option<GameResult> {
+        let setup = self.setup.as_deref()?;
+        if !setup.time_control.is_deterministic() {
+            log::info!(
+                "Replaying {} - {} under {:?}: searches on time need not repeat the game",
+                self.white,
+                self.black,
+                setup.time_control
+            );
+        }
+        let mut game = Game::new(setup.white.clone(), setup.black.clone(), setup.time_control)
+            .with_slots(self.white_slot, self.black_slot)
+            .with_search_timeout(setup.search_timeout)
+            .with_move_overhead(setup.move_overhead)
+            .with_timeout_grace(setup.timeout_grace)
+            .with_movetime_margin(setup.movetime_margin)
+            .with_ponder(setup.ponder)
+            .with_max_plies(setup.max_plies)
+            .with_draw_rules(setup.draw_rules);
+        game.time_jitter = setup.time_jitter;
+        game.resign = setup.resign;
+        game.draw_adjudication = setup.draw_adjudication;
+        game.round = setup.round;
+        game.verify_endings = setup.verify_endings;
+        game.oracle = setup.oracle.clone();
+        game.between_games = setup.between_games;
+        game.tablebase_path = setup.tablebase_path.clone();
+        #[cfg(feature = "syzygy")]
+        {
+            game.tb_adjudicate = setup.tb_adjudicate;
+        }
+        if let Some(fen) = &self.start_fen {
+            // checked when the game was first set up
+            game = game.with_start_fen(fen).expect("start FEN was validated");
+        }
+        game = game.with_opening_moves(&self.moves_list[..self.book_ply_count]);
+        game.opening_index = self.opening_index;
+        Some(game.play())
+    } //
+}
+
+// What `GameResult::replay` needs to set a game up again, beyond what the result records.
+#[derive(Clone)]
+struct ReplaySetup {
+    white: Engine,
+    black: Engine,
+    time_control: TimeControl,
+    search_timeout: Duration,
+    move_overhead: Duration,
+    timeout_grace: Duration,
+    movetime_margin: Duration,
+    time_jitter: Option<(u32, u64)>,
+    ponder: bool,
+    max_plies: Option<usize>,
+    resign: Option<(i32, u32)>,
+    draw_adjudication: Option<(i32, u32, u32)>,
+    draw_rules: DrawRules,
+    round: Option<i32>,
+    tablebase_path: Option<PathBuf>,
+    #[cfg(feature = "syzygy")]
+    tb_adjudicate: bool,
+    verify_endings: bool,
+    oracle: Option<(Engine, TimeControl)>,
+    between_games: BetweenGames,
+}
+
+impl std::fmt::Debug for ReplaySetup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplaySetup")
+            .field("white", &self.white.name)
+            .field("black", &self.black.name)
+            .field("time_control", &self.time_control)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Game {
@@ -39,73 +433,2308 @@ impl Game {
             black,
             moves_list: Vec::new(),
             board: Board::new(),
+            start_fen: None,
+            position: PositionCommand::new(None),
+            legal_moves: MoveCache::default(),
+            position_keys: Vec::new(),
+            halfmove_clock: 0,
+            draw_rules: DrawRules::default(),
             time_control,
+            opening_index: None,
+            book_ply_count: 0,
+            scores: Vec::new(),
+            move_stats: Vec::new(),
+            pvs: Vec::new(),
+            search_timeout: DEFAULT_SEARCH_TIMEOUT,
+            white_slot: 0,
+            black_slot: 1,
+            tablebase_path: None,
+            #[cfg(feature = "syzygy")]
+            tb_adjudicate: false,
+            clocks: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            timeout_grace: DEFAULT_TIMEOUT_GRACE,
+            movetime_margin: DEFAULT_MOVETIME_MARGIN,
+            time_jitter: None,
+            ponder: false,
+            max_plies: Some(DEFAULT_MAX_PLIES),
+            resign: None,
+            draw_adjudication: None,
+            round: None,
+            log_dir: None,
+            cancel_token: None,
+            verify_endings: false,
+            oracle: None,
+            between_games: BetweenGames::default(),
+            line_callbacks: [None, None],
+        }
+    } //
+
+    /// `Game::new` starting from `fen`; see `with_start_fen`.
+    pub fn from_fen(
+        white: Engine,
+        black: Engine,
+        time_control: TimeControl,
+        fen: &str,
+    ) -> Result<Self, FenError> {
+        Game::new(white, black, time_control).with_start_fen(fen)
+    } //
+
+    /// Once `cancel_token` is set the game is aborted before the next move, with both engines
+    /// told to quit, and ends as `Termination::Aborted`.
+    pub fn with_cancel_token(mut self, cancel_token: Arc<AtomicBool>) -> Self {
+        self.cancel_token = Some(cancel_token);
+        self
+    } //
+
+    /// Round number used to tag this game's engine processes and name their logs.
+    pub fn with_round(mut self, round: i32) -> Self {
+        self.round = Some(round);
+        self
+    } //
+
+    /// Write a transcript of each engine process to its own file in `log_dir`, named after the
+    /// process tag (round, color and engine name).
+    pub fn with_log_dir(mut self, log_dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = Some(log_dir.into());
+        self
+    } //
+
+    /// Adjudicate the game as a draw once this many plies (opening moves included) have been
+    /// played, whatever the position. Defaults to 400; `None` lets the game run until the rules
+    /// end it.
+    pub fn with_max_plies(mut self, max_plies: Option<usize>) -> Self {
+        self.max_plies = max_plies;
+        self
+    } //
+
+    /// Adjudicate the game as lost for a side whose engine reports a score of `score` centipawns
+    /// or more against itself on `moves` of its moves in a row, or a mate against it. A move
+    /// with a better score, or none, starts the count again. The rule is the same for both
+    /// sides and each is counted on its own. Off by default.
+    pub fn with_resign_adjudication(mut self, score: i32, moves: u32) -> Self {
+        self.resign = Some((score, moves));
+        self
+    } //
+
+    /// Adjudicate the game as a draw once the engines have scored it within `score` centipawns
+    /// of level for the last `plies` plies in a row, but only after move `after_move` (opening
+    /// moves included). A mate score, or a move without a score, starts the count again. Ends
+    /// as `Termination::DrawAdjudicated`. Off by default.
+    pub fn with_draw_adjudication(mut self, score: i32, plies: u32, after_move: u32) -> Self {
+        self.draw_adjudication = Some((score, plies, after_move));
+        self
+    } //
+
+    /// What the engines are sent to reset them before the game. Defaults to
+    /// `BetweenGames::NewGame`.
+    pub fn with_between_games(mut self, between_games: BetweenGames) -> Self {
+        self.between_games = between_games;
+        self
+    } //
+
+    /// Has `oracle` analyze every position of the game once it is over, under `time_control`,
+    /// and compare its choice with each searched move: see `GameResult::oracle_report` for
+    /// where the players diverged and their centipawn loss. The oracle runs as a third process,
+    /// after the players have quit, so it takes nothing from their time.
+    pub fn with_oracle(mut self, oracle: Engine, time_control: TimeControl) -> Self {
+        self.oracle = Some((oracle, time_control));
+        self
+    } //
+
+    /// Double-checks every checkmate and stalemate the game ends in against a reference, see
+    /// `GameResult::ending_mismatch`. Meant for unattended runs, where a move generation or board
+    /// state bug would otherwise quietly skew the results.
+    pub fn with_ending_verification(mut self, verify: bool) -> Self {
+        self.verify_endings = verify;
+        self
+    } //
+
+    /// Whether repetitions and the fifty-move rule draw at the claimable or only at the automatic
+    /// thresholds. Defaults to `DrawRules::Claimed`.
+    pub fn with_draw_rules(mut self, draw_rules: DrawRules) -> Self {
+        self.draw_rules = draw_rules;
+        self
+    } //
+
+    /// Let engines think on the opponent's time about the reply they predicted (`bestmove ..
+    /// ponder ..`). On a hit they get `ponderhit` and keep searching on their own clock; on a miss
+    /// the speculative search is stopped and discarded without being charged.
+    pub fn with_ponder(mut self, ponder: bool) -> Self {
+        self.ponder = ponder;
+        self
+    } //
+
+    /// Move each `movetime` sent to the engines by up to `jitter` either way, so that games
+    /// started together don't search in lock step and hit the machine's scheduling at the same
+    /// moments. Only `TimeControl::TimePerMove` is affected. Off by default. Without a `seed` the
+    /// offsets differ from run to run and the games can't be reproduced; with one, the same game
+    /// gets the same offsets every time.
+    pub fn with_time_jitter(mut self, jitter: Duration, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos() as u64)
+        });
+        self.time_jitter = Some((jitter.as_millis() as u32, seed));
+        self
+    } //
+
+    /// Taken off the `wtime`/`btime` sent to the engines, so they budget for the time lost
+    /// passing moves around. It changes what the engines are told, not how they are judged; see
+    /// `with_timeout_grace` for that. Defaults to 10ms.
+    pub fn with_move_overhead(mut self, move_overhead: Duration) -> Self {
+        self.move_overhead = move_overhead;
+        self
+    } //
+
+    /// How far past zero the arbiter lets a clock run before flagging, to absorb scheduling
+    /// jitter on a loaded machine. A move made within the grace leaves the clock at zero. The
+    /// engines are not told about it. Defaults to 50ms.
+    pub fn with_timeout_grace(mut self, timeout_grace: Duration) -> Self {
+        self.timeout_grace = timeout_grace;
+        self
+    } //
+
+    /// How long past a `TimeControl::TimePerMove` movetime a search may run before the side
+    /// loses on time. Engines commonly overshoot a movetime by a few dozen ms, more than they
+    /// would run a clock past zero, hence a separate, wider allowance than `with_timeout_grace`.
+    /// Defaults to 200ms.
+    pub fn with_movetime_margin(mut self, movetime_margin: Duration) -> Self {
+        self.movetime_margin = movetime_margin;
+        self
+    } //
+
+    /// Have `callback` see every line the engine playing `side` sends during the game, see
+    /// `EngineHandle::on_line`.
+    pub fn with_line_callback(mut self, side: Turn, callback: LineCallback) -> Self {
+        let side = match side {
+            Turn::WHITE => 0,
+            Turn::BLACK => 1,
+        };
+        self.line_callbacks[side] = Some(callback);
+        self
+    } //
+
+    /// Syzygy directory handed to both engines through their `SyzygyPath` option.
+    pub fn with_tablebase(mut self, path: impl Into<PathBuf>) -> Self {
+        self.tablebase_path = Some(path.into());
+        self
+    } //
+
+    /// End the game with the tablebase result as soon as the position is covered by the tables
+    /// configured with `with_tablebase`. A game whose tables can't be read is aborted.
+    #[cfg(feature = "syzygy")]
+    pub fn with_tb_adjudication(mut self, tb_adjudicate: bool) -> Self {
+        self.tb_adjudicate = tb_adjudicate;
+        self
+    } //
+
+    /// Identifies which participant (e.g. tournament engine index) plays each color, so results
+    /// can be attributed without comparing names. Defaults to white = 0, black = 1.
+    pub fn with_slots(mut self, white_slot: usize, black_slot: usize) -> Self {
+        self.white_slot = white_slot;
+        self.black_slot = black_slot;
+        self
+    } //
+
+    /// Longest a single search may run before the engine is told to stop, the only limit on a
+    /// `TimeControl::Infinite` search. An engine that ignores the `stop` too loses the game.
+    pub fn with_search_timeout(mut self, search_timeout: Duration) -> Self {
+        self.search_timeout = search_timeout;
+        self
+    } //
+
+    /// Start from `fen` instead of the standard position. The FEN is checked with `validate_fen`
+    /// first, so engines are never started on an illegal position. Call before `with_opening`.
+    pub fn with_start_fen(mut self, fen: &str) -> Result<Self, FenError> {
+        validate_fen(fen)?;
+        self.board.load_from_fen(fen);
+        self.legal_moves.invalidate();
+        self.start_fen = Some(fen.to_string());
+        self.position = PositionCommand::new(Some(fen));
+        self.position_keys.clear();
+        // validated above, so the halfmove clock field is there and a number
+        self.halfmove_clock = fen
+            .split_whitespace()
+            .nth(4)
+            .and_then(|clock| clock.parse().ok())
+            .unwrap_or(0);
+        Ok(self)
+    } //
+
+    /// Starts from the opening's FEN, if it has one, and plays its moves. Panics on an invalid
+    /// opening, see `try_with_opening`; `OpeningBook` checks its FENs as it loads.
+    pub fn with_opening(self, index: usize, opening: &Opening) -> Self {
+        self.try_with_opening(index, opening)
+            .unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    /// Like `with_opening`, but returns `OpeningError::InvalidFen` when the FEN fails
+    /// `validate_fen` and `OpeningError::IllegalMove` at the first move that isn't legal.
+    pub fn try_with_opening(
+        mut self,
+        index: usize,
+        opening: &Opening,
+    ) -> Result<Self, OpeningError> {
+        if let Some(fen) = &opening.fen {
+            self = self
+                .with_start_fen(fen)
+                .map_err(|error| OpeningError::InvalidFen { index, error })?;
+        }
+        self.play_opening_moves(&opening.moves)
+            .map_err(|(ply, uci)| OpeningError::IllegalMove { index, ply, uci })?;
+        self.opening_index = Some(index);
+        Ok(self)
+    } //
+
+    /// Plays uci `moves` before the engines take over, without scores. Panics on a move that
+    /// isn't legal; `try_with_opening` returns an error instead.
+    pub fn with_opening_moves(mut self, moves: &[String]) -> Self {
+        if let Err((ply, uci)) = self.play_opening_moves(moves) {
+            panic!("Opening move '{}' at ply {} is not legal", uci, ply);
+        }
+        self
+    } //
+
+    /// Continues the opening from `book`, see `PolyglotBook::play_line`. A book move that isn't
+    /// legal (a hash collision, say) ends the line there.
+    pub fn with_book(mut self, book: &PolyglotBook, max_plies: usize, seed: u64) -> Self {
+        let line = book.play_line(&self.board, max_plies, seed);
+        if let Err((ply, uci)) = self.play_opening_moves(&line) {
+            log::warn!(
+                "Book move '{}' at ply {} is not legal, leaving the book",
+                uci,
+                ply
+            );
+        }
+        self
+    } //
+
+    // Plays `moves` as opening moves, each checked against the legal moves as an engine's are.
+    // Stops at the first that isn't legal, returning its ply (counted from 1) and the move.
+    fn play_opening_moves(&mut self, moves: &[String]) -> Result<(), (usize, String)> {
+        for (ply, mv) in moves.iter().enumerate() {
+            let legal_moves = self.legal_moves.moves(&self.board);
+            let Some((uci, parsed)) = canonical_uci(mv).and_then(|uci| {
+                let parsed = legal_moves
+                    .iter()
+                    .find(|legal| legal.to_uci() == uci)
+                    .copied()?;
+                Some((uci, parsed))
+            }) else {
+                return Err((ply + 1, mv.clone()));
+            };
+            self.position_keys.push(polyglot_key(&self.board));
+            self.advance_halfmove_clock(parsed);
+            self.board.make_move(parsed);
+            self.legal_moves.invalidate();
+            self.moves_list.push(uci);
+            self.scores.push(None);
+            self.move_stats.push(None);
+            self.pvs.push(Vec::new());
+            self.book_ply_count += 1;
+        }
+        Ok(())
+    } //
+
+    pub fn play(&mut self) -> GameResult {
+        self.play_with_observer(|_, _| {})
+    } //
+
+    /// Like `play`, but calls `observer` with the board and the move just played after every
+    /// engine move. The observer runs on the game's thread, so a slow observer slows the game
+    /// (and eats into the engines' clocks) — hand the data off to a channel if it does real work.
+    pub fn play_with_observer<F: FnMut(&Board, &Move)>(&mut self, mut observer: F) -> GameResult {
+        let mut white_process = self
+            .white
+            .spawn_tagged_process(Some(self.process_tag(true)), self.log_dir.as_deref());
+        let mut black_process = self
+            .black
+            .spawn_tagged_process(Some(self.process_tag(false)), self.log_dir.as_deref());
+        let [white_callback, black_callback] = std::mem::take(&mut self.line_callbacks);
+        for (process, callback) in [
+            (&mut white_process, white_callback),
+            (&mut black_process, black_callback),
+        ] {
+            if let Some(callback) = callback {
+                process.on_line(callback);
+            }
+        }
+        let mut options_error = None;
+        let mut unready = None; // the side, and engine, that never answered `isready`
+        for (turn, process, engine) in [
+            (Turn::WHITE, &mut white_process, &self.white),
+            (Turn::BLACK, &mut black_process, &self.black),
+        ] {
+            process.send_command("uci\n");
+            process.wait_for_uciok();
+            if !process.sync() {
+                unready = Some((turn, engine.name.clone()));
+                break;
+            }
+            if let Err(error) = engine.send_options(process) {
+                options_error = Some(error);
+                break;
+            }
+            if engine.debug {
+                process.set_debug(true);
+            }
+            if let Some(path) = &self.tablebase_path {
+                process.set_option("SyzygyPath", &path.display().to_string());
+            }
+            if self.ponder {
+                process.set_option("Ponder", "true");
+            }
+            match self.between_games {
+                BetweenGames::NewGame => process.send_command("ucinewgame\n"),
+                BetweenGames::ClearHashOnly => process.send_command("setoption name Clear Hash\n"),
+                BetweenGames::Nothing => {}
+            }
+            if !process.sync() {
+                unready = Some((turn, engine.name.clone()));
+                break;
+            }
+        }
+        if let Some((turn, name)) = unready {
+            log::error!("{} didn't answer isready before the game", name);
+            return self.finish(
+                Outcome::loss_for(turn),
+                Termination::EngineCrash,
+                &mut white_process,
+                &mut black_process,
+            );
+        }
+        // a game the engines aren't set up for as asked isn't a real result
+        if let Some(error) = options_error {
+            log::error!("Cannot start the game: {}", error);
+            return self.finish(
+                Outcome::Draw,
+                Termination::Aborted,
+                &mut white_process,
+                &mut black_process,
+            );
+        }
+        #[cfg(feature = "syzygy")]
+        let tablebase = match self.open_tablebase() {
+            Ok(tablebase) => tablebase,
+            Err(error) => {
+                log::error!(
+                    "Cannot start the game: failed to open the tablebase: {}",
+                    error
+                );
+                return self.finish(
+                    Outcome::Draw,
+                    Termination::Aborted,
+                    &mut white_process,
+                    &mut black_process,
+                );
+            }
+        };
+        if let Some((base_ms, _)) = self.time_control.clock() {
+            self.clocks = Some([base_ms as i64; 2]);
+        }
+        // move each side's engine is currently pondering on, if any
+        let mut pondering: [Option<String>; 2] = [None, None];
+        // each side's moves in a row with a score past the resign threshold
+        let mut hopeless_moves = [0; 2];
+        // plies in a row scored within the draw threshold, both sides' together
+        let mut level_plies = 0;
+
+        let (outcome, reason) = loop {
+            if self
+                .cancel_token
+                .as_ref()
+                .is_some_and(|cancel_token| cancel_token.load(Ordering::Relaxed))
+            {
+                break (Outcome::Draw, Termination::Aborted);
+            }
+            #[cfg(feature = "syzygy")]
+            if let Some(outcome) = self.probe_tablebase(tablebase.as_ref()) {
+                break (outcome, Termination::Adjudicated);
+            }
+            if self.legal_moves.moves(&self.board).is_empty() {
+                let (outcome, reason) = if self.board.is_king_in_check(self.board.turn) {
+                    (Outcome::loss_for(self.board.turn), Termination::Checkmate)
+                } else {
+                    (Outcome::Draw, Termination::Stalemate)
+                };
+                break (outcome, reason);
+            }
+            // the polyglot key covers side to move, castling rights and a capturable en passant
+            // square, so only truly identical positions count as repeats
+            let key = polyglot_key(&self.board);
+            self.position_keys.push(key);
+            if self
+                .position_keys
+                .iter()
+                .filter(|&&seen| seen == key)
+                .count()
+                >= self.draw_rules.repetitions()
+            {
+                break (Outcome::Draw, Termination::Repetition);
+            }
+            // checked after mate, which stands even when delivered on the last quiet move
+            if self.halfmove_clock >= self.draw_rules.quiet_plies() {
+                break (Outcome::Draw, Termination::FiftyMoves);
+            }
+            if insufficient_material(&self.board) {
+                break (Outcome::Draw, Termination::InsufficientMaterial);
+            }
+            if self
+                .max_plies
+                .is_some_and(|max_plies| self.moves_list.len() >= max_plies)
+            {
+                break (Outcome::Draw, Termination::MaxLength);
+            }
+            let engine_process = match self.board.turn {
+                Turn::WHITE => &white_process,
+                Turn::BLACK => &black_process,
+            };
+            let side = match self.board.turn {
+                Turn::WHITE => 0,
+                Turn::BLACK => 1,
+            };
+
+            let ponder_hit = match pondering[side].take() {
+                Some(expected) => {
+                    let hit = self.moves_list.last() == Some(&expected);
+                    if !hit {
+                        // ponder miss: the speculative search is thrown away, and none of the
+                        // time it took is charged to this side
+                        engine_process.send_command("stop\n");
+                        wait_for_bestmove(engine_process, Some(STOP_GRACE));
+                    }
+                    hit
+                }
+                None => false,
+            };
+            // on a ponder hit the engine keeps its search, and from here on it runs on its own clock
+            let search_start = Instant::now();
+            if ponder_hit {
+                engine_process.send_command("ponderhit\n");
+            } else {
+                engine_process.send_command(self.position_command(None).as_str());
+                engine_process.send_command(format!("go {}\n", self.search_limits(false)).as_str());
+            }
+
+            let timeout = match (self.time_control, self.clocks) {
+                (_, Some(clocks)) => {
+                    Some(Duration::from_millis(clocks[side].max(0) as u64) + self.timeout_grace)
+                }
+                (TimeControl::TimePerMove(movetime), _) => Some(self.movetime_limit(movetime)),
+                _ => Some(self.search_timeout),
+            };
+            let reply = wait_for_bestmove(engine_process, timeout);
+            let elapsed = search_start.elapsed();
+
+            let moves_made = self.moves_made(false) + 1;
+            if let (Some((base_ms, inc_ms)), Some(clocks)) =
+                (self.time_control.clock(), self.clocks.as_mut())
+            {
+                // replying exactly on the flag, or within the grace past it, is still in time;
+                // the increment is only earned by a move made in time
+                clocks[side] -= elapsed.as_millis() as i64;
+                if clocks[side] < -(self.timeout_grace.as_millis() as i64) {
+                    break (Outcome::loss_for(self.board.turn), Termination::TimeForfeit);
+                }
+                clocks[side] = clocks[side].max(0) + inc_ms as i64;
+                // each completed control period earns the base time again
+                if let TimeControl::MovesToGo { moves, .. } = self.time_control
+                    && moves_made.is_multiple_of(moves.max(1) as usize)
+                {
+                    clocks[side] += base_ms as i64;
+                }
+            }
+            // a fixed movetime is a clock of its own, reset every move
+            if let TimeControl::TimePerMove(movetime) = self.time_control
+                && elapsed > self.movetime_limit(movetime)
+            {
+                break (Outcome::loss_for(self.board.turn), Termination::TimeForfeit);
+            }
+
+            match reply {
+                Some(reply) => {
+                    // the engine is only asked to move in positions that have legal moves, so a
+                    // missing or null move is as wrong as an illegal one
+                    let legal_moves = self.legal_moves.moves(&self.board);
+                    let Some((uci, mv)) = canonical_uci(&reply.best_move).and_then(|uci| {
+                        let mv = legal_moves.iter().find(|mv| mv.to_uci() == uci).copied()?;
+                        Some((uci, mv))
+                    }) else {
+                        break (Outcome::loss_for(self.board.turn), Termination::IllegalMove);
+                    };
+                    // kept canonical, so later `position` commands are well formed
+                    self.moves_list.push(uci);
+                    self.scores.push(reply.score);
+                    self.move_stats.push(Some(MoveStats {
+                        time: elapsed,
+                        depth: reply.depth,
+                        nodes: reply.nodes,
+                        seldepth: reply.seldepth,
+                        nps: reply.nps,
+                    }));
+                    self.pvs.push(reply.pv);
+                    let mover = self.board.turn;
+                    self.advance_halfmove_clock(mv);
+                    self.board.make_move(mv);
+                    self.legal_moves.invalidate();
+                    observer(&self.board, &mv);
+
+                    if let Some((resign_score, resign_moves)) = self.resign {
+                        // scores come from the mover's side, so one test serves both colors
+                        let hopeless = match reply.score {
+                            Some(Score::Cp(cp)) => cp <= -resign_score,
+                            Some(Score::Mate(moves)) => moves < 0,
+                            None => false,
+                        };
+                        hopeless_moves[side] = if hopeless {
+                            hopeless_moves[side] + 1
+                        } else {
+                            0
+                        };
+                        if hopeless_moves[side] >= resign_moves.max(1) {
+                            break (Outcome::loss_for(mover), Termination::Adjudicated);
+                        }
+                    }
+                    if let Some((draw_score, draw_plies, after_move)) = self.draw_adjudication {
+                        level_plies = match reply.score {
+                            Some(Score::Cp(cp)) if cp.abs() <= draw_score => level_plies + 1,
+                            _ => 0,
+                        };
+                        if level_plies >= draw_plies.max(1) as usize
+                            && self.moves_list.len() > 2 * after_move as usize
+                        {
+                            break (Outcome::Draw, Termination::DrawAdjudicated);
+                        }
+                    }
+
+                    // validated against the cache, so the next iteration doesn't generate again
+                    let legal_moves = self.legal_moves.moves(&self.board);
+                    let ponder_move = reply
+                        .ponder
+                        .and_then(|ponder_move| canonical_uci(&ponder_move))
+                        .filter(|ponder_move| {
+                            legal_moves.iter().any(|mv| mv.to_uci() == *ponder_move)
+                        });
+                    if let Some(ponder_move) = ponder_move.filter(|_| self.ponder) {
+                        engine_process
+                            .send_command(self.position_command(Some(&ponder_move)).as_str());
+                        engine_process.send_command(
+                            format!("go ponder {}\n", self.search_limits(true)).as_str(),
+                        );
+                        pondering[side] = Some(ponder_move);
+                    }
+                }
+                None => {
+                    // the engine never answered, even after being told to stop
+                    break (Outcome::loss_for(self.board.turn), Termination::EngineCrash);
+                }
+            }
+        };
+        self.finish(outcome, reason, &mut white_process, &mut black_process)
+    } //
+
+    // Ends the game: both engines are told to quit and closed, then the result is built. When
+    // one side lost by its own fault (a crash, an illegal move or its flag), the result gets all
+    // that engine wrote to stderr.
+    fn finish(
+        &self,
+        outcome: Outcome,
+        reason: Termination,
+        white_process: &mut EngineHandle,
+        black_process: &mut EngineHandle,
+    ) -> GameResult {
+        white_process.close();
+        black_process.close();
+        let forfeited = matches!(
+            reason,
+            Termination::TimeForfeit | Termination::IllegalMove | Termination::EngineCrash
+        );
+        let (loser, process) = match outcome {
+            Outcome::BlackWins if forfeited => (&self.white, white_process),
+            Outcome::WhiteWins if forfeited => (&self.black, black_process),
+            _ => return self.game_result(outcome, reason),
+        };
+        let stderr = process.take_stderr();
+        if !stderr.is_empty() {
+            log::error!(
+                "{} lost by {:?}, its stderr ends with:\n{}",
+                loser.name,
+                reason,
+                stderr.join("\n")
+            );
+        }
+        let mut game_result = self.game_result(outcome, reason);
+        game_result.engine_stderr = stderr;
+        game_result
+    } //
+
+    // Call before `mv` is made on the board.
+    fn advance_halfmove_clock(&mut self, mv: Move) {
+        // white and black pawns
+        let is_pawn =
+            self.board.piece_at[mv.from()].is_some_and(|piece| matches!(piece as usize, 0 | 6));
+        // en passant captures are pawn moves, so an empty target square is enough here
+        if is_pawn || self.board.piece_at[mv.to()].is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+    } //
+
+    // The tables to adjudicate with, if asked to. Tables that can't be read (a mistyped path,
+    // say) are an error rather than no adjudication, which would quietly change the results.
+    #[cfg(feature = "syzygy")]
+    fn open_tablebase(&self) -> std::io::Result<Option<Tablebase>> {
+        match &self.tablebase_path {
+            Some(path) if self.tb_adjudicate => Tablebase::open(path).map(Some),
+            _ => Ok(None),
+        }
+    } //
+    #[cfg(feature = "syzygy")]
+    fn probe_tablebase(&self, tablebase: Option<&Tablebase>) -> Option<Outcome> {
+        let tablebase = tablebase?;
+        let pieces = self
+            .board
+            .piece_at
+            .iter()
+            .filter(|piece| piece.is_some())
+            .count();
+        if pieces > tablebase.max_pieces() {
+            return None;
+        }
+        tablebase.probe(&self.board).map(Outcome::from)
+    } //
+
+    fn process_tag(&self, white: bool) -> ProcessTag {
+        let engine = if white { &self.white } else { &self.black };
+        ProcessTag {
+            round: self.round,
+            white,
+            engine_name: engine.name.clone(),
+        }
+    } //
+
+    // Checks a declared checkmate or stalemate against a reference: the final position is loaded
+    // into a fresh board, so state carried along the game plays no part, and the check is worked
+    // out from the FEN on its own. Any disagreement means a move generation or board state bug.
+    fn verify_ending(&self, reason: Termination) -> Option<String> {
+        if !matches!(reason, Termination::Checkmate | Termination::Stalemate) {
+            return None;
+        }
+        let fen = self.board.to_fen();
+        let mut fresh = Board::new();
+        fresh.load_from_fen(&fen);
+        let moves = fresh.generate_moves();
+        let mismatch = match side_to_move_in_check(&fen) {
+            Err(error) => Some(format!("final position {} does not parse: {}", fen, error)),
+            Ok(_) if !moves.is_empty() => Some(format!(
+                "{:?} declared but {} legal moves remain in {}",
+                reason,
+                moves.len(),
+                fen
+            )),
+            Ok(in_check) if in_check != (reason == Termination::Checkmate) => Some(format!(
+                "{:?} declared but the side to move is {}in check in {}",
+                reason,
+                if in_check { "" } else { "not " },
+                fen
+            )),
+            Ok(_) => None,
+        };
+        if let Some(mismatch) = &mismatch {
+            log::error!(
+                "{} - {}: ending does not verify, {}",
+                self.white.name,
+                self.black.name,
+                mismatch
+            );
+        }
+        mismatch
+    } //
+
+    fn oracle_report(&self, reason: Termination) -> Option<OracleReport> {
+        let (oracle, time_control) = self.oracle.as_ref()?;
+        // an aborted game is being torn down, not analyzed
+        if reason == Termination::Aborted {
+            return None;
+        }
+        let report = consult_oracle(
+            oracle,
+            *time_control,
+            self.start_fen.as_deref(),
+            &self.moves_list,
+            self.book_ply_count,
+        );
+        let summary = |white: bool| {
+            report
+                .average_loss(white)
+                .map_or("-".to_string(), |loss| format!("{:.0}", loss))
+        };
+        log::info!(
+            "{} - {}: average centipawn loss {} / {} by {}",
+            self.white.name,
+            self.black.name,
+            summary(true),
+            summary(false),
+            oracle.name
+        );
+        Some(report)
+    } //
+
+    fn game_result(&self, outcome: Outcome, reason: Termination) -> GameResult {
+        let ending_mismatch = if self.verify_endings {
+            self.verify_ending(reason)
+        } else {
+            None
+        };
+        match outcome {
+            Outcome::WhiteWins => log::info!("{} wins as white ({:?})", self.white.name, reason),
+            Outcome::BlackWins => log::info!("{} wins as black ({:?})", self.black.name, reason),
+            Outcome::Draw => log::info!(
+                "{} - {} drawn ({:?})",
+                self.white.name,
+                self.black.name,
+                reason
+            ),
+        }
+        GameResult {
+            white: self.white.name.clone(),
+            black: self.black.name.clone(),
+            moves_list: self.moves_list.clone(),
+            outcome,
+            opening_index: self.opening_index,
+            book_ply_count: self.book_ply_count,
+            scores: self.scores.clone(),
+            move_stats: self.move_stats.clone(),
+            pvs: self.pvs.clone(),
+            white_slot: self.white_slot,
+            black_slot: self.black_slot,
+            reason,
+            clocks: self.clocks,
+            start_fen: self.start_fen.clone(),
+            final_fen: self.board.to_fen(),
+            ending_mismatch,
+            oracle_report: self.oracle_report(reason),
+            engine_stderr: Vec::new(),
+            setup: Some(Box::new(self.replay_setup())),
+        }
+    } //
+
+    // A result read back from a tournament state file, for a game set up as this one was: it
+    // gets back the setup `replay` needs, which isn't serialized.
+    #[cfg(feature = "serde")]
+    pub(crate) fn restored_result(&self, mut game_result: GameResult) -> GameResult {
+        game_result.setup = Some(Box::new(self.replay_setup()));
+        game_result
+    } //
+
+    fn replay_setup(&self) -> ReplaySetup {
+        ReplaySetup {
+            white: self.white.clone(),
+            black: self.black.clone(),
+            time_control: self.time_control,
+            search_timeout: self.search_timeout,
+            move_overhead: self.move_overhead,
+            timeout_grace: self.timeout_grace,
+            movetime_margin: self.movetime_margin,
+            time_jitter: self.time_jitter,
+            ponder: self.ponder,
+            max_plies: self.max_plies,
+            resign: self.resign,
+            draw_adjudication: self.draw_adjudication,
+            draw_rules: self.draw_rules,
+            round: self.round,
+            tablebase_path: self.tablebase_path.clone(),
+            #[cfg(feature = "syzygy")]
+            tb_adjudicate: self.tb_adjudicate,
+            verify_endings: self.verify_endings,
+            oracle: self.oracle.clone(),
+            between_games: self.between_games,
+        }
+    } //
+
+    // `position` for the current game, optionally followed by one speculative move to ponder on
+    fn position_command(&mut self, ponder_move: Option<&str>) -> String {
+        for mv in &self.moves_list[self.position.plies()..] {
+            self.position.push(mv);
+        }
+        self.position.command(ponder_move)
+    } //
+
+    // everything after `go` (or `go ponder`), with the move overhead held back from the clocks
+    fn search_limits(&self, ponder: bool) -> String {
+        let overhead = self.move_overhead.as_millis() as i64;
+        let clocks = self
+            .clocks
+            .unwrap_or_default()
+            .map(|clock| (clock - overhead).max(0));
+        let time_control = match (self.time_control, self.time_jitter) {
+            (TimeControl::TimePerMove(time), Some((jitter, seed))) => {
+                // one offset per ply, drawn from the seed alone so that a replay matches
+                let ply = self.moves_list.len() + usize::from(ponder);
+                let draw = SplitMix64(seed ^ ply as u64).next_u64();
+                let offset = (draw % (2 * jitter as u64 + 1)) as i32 - jitter as i32;
+                TimeControl::TimePerMove((time + offset).max(1))
+            }
+            (time_control, _) => time_control,
+        };
+        time_control.search_limits(clocks, self.moves_made(ponder))
+    } //
+
+    // How long a `TimePerMove` search may take before the side loses on time. Jittered movetimes
+    // are judged against the longest one the jitter can produce.
+    fn movetime_limit(&self, movetime: i32) -> Duration {
+        let jitter = self.time_jitter.map_or(0, |(jitter, _)| jitter as u64);
+        Duration::from_millis(movetime.max(0) as u64 + jitter) + self.movetime_margin
+    } //
+
+    // Moves the side to move has searched so far; sides alternate from the first move after the
+    // book, so that is half the searched plies. A ponder search is for the move after next.
+    fn moves_made(&self, ponder: bool) -> usize {
+        let plies = self.moves_list.len() - self.book_ply_count + usize::from(ponder);
+        plies / 2
+    } //
+} //
+
+// Whether neither side has the pieces left to mate, in any line of play: kings alone, a single
+// knight or bishop, or any number of bishops that all stand on squares of one color.
+fn insufficient_material(board: &Board) -> bool {
+    let mut knights = 0;
+    let mut bishops_on = [0; 2]; // on dark and on light squares
+    for (square, piece) in board.piece_at.iter().enumerate() {
+        let Some(piece) = piece else {
+            continue;
+        };
+        match *piece as usize % 6 {
+            1 => knights += 1,
+            2 => bishops_on[(square / 8 + square % 8) % 2] += 1,
+            5 => {}
+            _ => return false,
+        }
+    }
+    match knights {
+        0 => bishops_on.contains(&0),
+        1 => bishops_on == [0, 0],
+        _ => false,
+    }
+} //
+
+// Only a real `bestmove` ends the search; `info string` chatter is skipped. Once `timeout` has
+// passed the engine is sent `stop`, and None is returned if even that gets no answer. With an idle
+// timeout on the process, a silent engine is sent `isready` and None is returned if it doesn't
+// answer within STOP_GRACE: it is hung.
+pub(crate) fn wait_for_bestmove(
+    process: &EngineHandle,
+    timeout: Option<Duration>,
+) -> Option<BestMove> {
+    let mut deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut stopped = false;
+    let mut ping_deadline: Option<Instant> = None;
+    let mut score = None;
+    let mut depth = None;
+    let mut nodes = None;
+    let mut seldepth = None;
+    let mut nps = None;
+    let mut pv = Vec::new();
+    loop {
+        let idle_deadline =
+            ping_deadline.or_else(|| process.idle_timeout().map(|idle| Instant::now() + idle));
+        let line = match deadline.into_iter().chain(idle_deadline).min() {
+            Some(wake_up) => {
+                process.read_line_timeout(wake_up.saturating_duration_since(Instant::now()))
+            }
+            None => process.read_line(),
+        };
+        let Some(line) = line else {
+            // no use waiting out the deadline for an engine that has gone
+            if process.output_ended() {
+                log::warn!("Engine output ended during its search, it has crashed");
+                return None;
+            }
+            let now = Instant::now();
+            if ping_deadline.is_some_and(|ping_deadline| now >= ping_deadline) {
+                log::warn!("Engine didn't answer isready during its search, it is hung");
+                return None;
+            }
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                if stopped {
+                    return None;
+                }
+                process.send_command("stop\n");
+                stopped = true;
+                deadline = Some(now + STOP_GRACE);
+            } else if ping_deadline.is_none() {
+                process.send_command("isready\n");
+                ping_deadline = Some(now + STOP_GRACE);
+            }
+            continue;
+        };
+        // any output, `readyok` or not, shows the engine is alive
+        ping_deadline = None;
+        match AnalysisLine::new(line) {
+            Some(AnalysisLine::Move(best_move, ponder)) => {
+                // legal, but worth a look from the engine's author: a search cut short by `stop`
+                // can do it, and so can a pv and a best move kept out of step
+                if let Some(pv_move) = pv.first().filter(|&pv_move| *pv_move != best_move) {
+                    log::info!(
+                        "{} played {} although its last pv started with {}",
+                        process
+                            .tag()
+                            .map_or("Engine".to_string(), |tag| tag.to_string()),
+                        best_move,
+                        pv_move
+                    );
+                }
+                return Some(BestMove {
+                    best_move,
+                    ponder,
+                    score,
+                    depth,
+                    nodes,
+                    seldepth,
+                    nps,
+                    pv,
+                });
+            }
+            Some(AnalysisLine::Depth {
+                score: reported_score,
+                depth: reported_depth,
+                nodes: reported_nodes,
+                selective_depth: reported_seldepth,
+                nps: reported_nps,
+                pv: reported_pv,
+                ..
+            }) => {
+                score = reported_score.or(score);
+                depth = reported_depth
+                    .and_then(|reported| reported.parse().ok())
+                    .or(depth);
+                nodes = reported_nodes
+                    .and_then(|reported| reported.parse().ok())
+                    .or(nodes);
+                seldepth = reported_seldepth
+                    .and_then(|reported| reported.parse().ok())
+                    .or(seldepth);
+                nps = reported_nps
+                    .and_then(|reported| reported.parse().ok())
+                    .or(nps);
+                if !reported_pv.is_empty() {
+                    pv = reported_pv;
+                }
+            }
+            _ => {}
+        }
+    }
+} //
+
+/// Builds the `position` commands of a growing game. UCI needs the whole move list every time,
+/// but the command is kept and extended by one move per ply rather than rejoined from scratch.
+#[derive(Debug, Clone)]
+pub struct PositionCommand {
+    command: String, // without the trailing newline
+    plies: usize,
+}
+
+impl PositionCommand {
+    pub fn new(start_fen: Option<&str>) -> Self {
+        let command = match start_fen {
+            Some(fen) => format!("position fen {}", fen),
+            None => "position startpos".to_string(),
+        };
+        PositionCommand { command, plies: 0 }
+    } //
+
+    pub fn push(&mut self, mv: &str) {
+        if self.plies == 0 {
+            self.command.push_str(" moves");
+        }
+        self.command.push(' ');
+        self.command.push_str(mv);
+        self.plies += 1;
+    } //
+
+    pub fn plies(&self) -> usize {
+        self.plies
+    } //
+
+    /// The command line to send, optionally with one speculative move to ponder on.
+    pub fn command(&self, ponder_move: Option<&str>) -> String {
+        let ponder_move = ponder_move.map(|mv| (if self.plies == 0 { " moves " } else { " " }, mv));
+        let extra = ponder_move.map_or(0, |(separator, mv)| separator.len() + mv.len());
+        let mut command = String::with_capacity(self.command.len() + extra + 1);
+        command.push_str(&self.command);
+        if let Some((separator, mv)) = ponder_move {
+            command.push_str(separator);
+            command.push_str(mv);
+        }
+        command.push('\n');
+        command
+    } //
+}
+
+pub(crate) struct BestMove {
+    pub(crate) best_move: String,
+    pub(crate) ponder: Option<String>,
+    pub(crate) score: Option<Score>, // last score reported before the bestmove
+    pub(crate) depth: Option<u32>,   // likewise the last of each below
+    pub(crate) nodes: Option<u64>,
+    pub(crate) seldepth: Option<u32>,
+    pub(crate) nps: Option<u64>,
+    pub(crate) pv: Vec<String>,
+}
+
+impl TimeControl {
+    /// Reads a time control in cutechess syntax: `inf`, `[moves/]time[+increment]` with times in
+    /// seconds or `minutes:seconds` (`40/60`, `40/1:30+0.5`, `5+0.1`, `300`), or one of
+    /// `movetime=ms`, `nodes=n`, `depth=n` and `mate=n`.
+    pub fn parse(text: &str) -> Result<TimeControl, TimeControlError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(TimeControlError::Empty);
+        }
+        if text == "inf" || text == "infinite" {
+            return Ok(TimeControl::Infinite);
+        }
+        // cutechess users reach for `tb` next to `tc`, but it names the Syzygy tables there;
+        // a tablebase changes how a game is judged, not how long the engines get to think
+        if text == "tb" {
+            return Err(TimeControlError::Tablebase);
+        }
+        if let Some((key, value)) = text.split_once('=') {
+            return match key {
+                "movetime" => Ok(TimeControl::TimePerMove(parse_number(value)?)),
+                "nodes" => Ok(TimeControl::Nodes(parse_number(value)?)),
+                "depth" => Ok(TimeControl::Depth(parse_number(value)?)),
+                "mate" => Ok(TimeControl::Mate(parse_number(value)?)),
+                _ => Err(TimeControlError::Unknown(text.to_string())),
+            };
         }
+        if !text.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            return Err(TimeControlError::Unknown(text.to_string()));
+        }
+
+        let (moves, clock) = match text.split_once('/') {
+            Some((moves, clock)) => (Some(parse_number::<u32>(moves)?), clock),
+            None => (None, text),
+        };
+        let (base, increment) = match clock.split_once('+') {
+            Some((base, increment)) => (base, Some(increment)),
+            None => (clock, None),
+        };
+        let base_ms = parse_seconds(base)?;
+        let inc_ms = increment.map(parse_seconds).transpose()?.unwrap_or(0);
+        Ok(match moves {
+            Some(0) => return Err(TimeControlError::InvalidNumber("0".to_string())),
+            Some(moves) => TimeControl::MovesToGo {
+                moves,
+                base_ms,
+                inc_ms,
+            },
+            None => TimeControl::Increment { base_ms, inc_ms },
+        })
     } //
 
-    // pub fn play(&mut self) -> GameResult {
-    //     let start_time = std::time::Instant::now();
-    //     let mut white_process = self.white.spawn_process();
-    //     let mut black_process = self.black.spawn_process();
-
-    //     loop {
-    //         let valid_moves = self.board.generate_moves();
-    //         if valid_moves.is_empty() {
-    //             match self.board.turn {
-    //                 Turn::WHITE => println!("{} wins as black", self.black.name),
-    //                 Turn::BLACK => println!("{} wins as white", self.white.name),
-    //             }
-    //             let result: i32;
-    //             if self.board.is_king_in_check(self.board.turn) {
-    //                 match self.board.turn {
-    //                     Turn::WHITE => result = -1,
-    //                     Turn::BLACK => result = 1,
-    //                 }
-    //             } else {
-    //                 result = 0;
-    //             }
-    //             return GameResult {
-    //                 white: self.white.name.clone(),
-    //                 black: self.black.name.clone(),
-    //                 moves_list: self.moves_list.clone(),
-    //                 result,
-    //             };
-    //         }
-    //         let engine_process = match self.board.turn {
-    //             Turn::WHITE => &mut white_process,
-    //             Turn::BLACK => &mut black_process,
-    //         };
-    //         if self.moves_list.is_empty() {
-    //             engine_process.send_command(format!("position startpos\n").as_str());
-    //         } else {
-    //             engine_process.send_command(
-    //                 format!("position startpos moves {}\n", self.moves_list.join(" ")).as_str(),
-    //             );
-    //         }
-
-    //         match self.time_control {
-    //             TimeControl::Infinite => {
-    //                 engine_process.send_command("go infinite\n");
-    //             }
-    //             TimeControl::TimePerMove(time) => {
-    //                 engine_process.send_command(format!("go movetime {}\n", time).as_str());
-    //             }
-    //         }
-
-    //         loop {
-    //             if let Some(line) = engine_process.read_line() {
-    //                 if line.starts_with("bestmove") {
-    //                     let best_move = line.split_whitespace().nth(1).unwrap();
-    //                     self.moves_list.push(best_move.to_string());
-    //                     self.board.make_move(Move::from_uci(best_move, &self.board));
-    //                     break;
-    //                 } else {
-    //                     // println!("{}", line);
-    //                 }
-    //             }
-    //         }
-    //     } //
-    //     white_process.disconnect();
-    //     black_process.disconnect();
-    // } //
+    // base and increment in ms, for the time controls that run a clock
+    pub(crate) fn clock(&self) -> Option<(i32, i32)> {
+        match *self {
+            TimeControl::Increment { base_ms, inc_ms }
+            | TimeControl::MovesToGo {
+                base_ms, inc_ms, ..
+            } => Some((base_ms, inc_ms)),
+            _ => None,
+        }
+    } //
+
+    /// Whether a search under this control ends in the same place every time: `Depth`, `Nodes`
+    /// and `Mate` do, anything on the clock doesn't.
+    pub fn is_deterministic(&self) -> bool {
+        matches!(
+            self,
+            TimeControl::Depth(_) | TimeControl::Nodes(_) | TimeControl::Mate(_)
+        )
+    } //
+
+    // everything after `go`; `clocks` are the remaining ms of white and black, `moves_made` the
+    // moves the side to move has already played on its clock
+    pub(crate) fn search_limits(&self, clocks: [i64; 2], moves_made: usize) -> String {
+        match *self {
+            TimeControl::Infinite => "infinite".to_string(),
+            TimeControl::TimePerMove(time) => format!("movetime {}", time),
+            TimeControl::Mate(moves) => format!("mate {}", moves),
+            TimeControl::Depth(depth) => format!("depth {}", depth),
+            TimeControl::Nodes(nodes) => format!("nodes {}", nodes),
+            TimeControl::Increment { inc_ms, .. } => format!(
+                "wtime {} btime {} winc {} binc {}",
+                clocks[0], clocks[1], inc_ms, inc_ms
+            ),
+            TimeControl::MovesToGo { moves, inc_ms, .. } => format!(
+                "wtime {} btime {} winc {} binc {} movestogo {}",
+                clocks[0],
+                clocks[1],
+                inc_ms,
+                inc_ms,
+                moves as usize - moves_made % moves as usize
+            ),
+        }
+    } //
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, TimeControlError> {
+    text.trim()
+        .parse()
+        .map_err(|_| TimeControlError::InvalidNumber(text.to_string()))
 } //
+
+// Seconds, possibly fractional, or `minutes:seconds`, to ms.
+fn parse_seconds(text: &str) -> Result<i32, TimeControlError> {
+    let invalid = || TimeControlError::InvalidNumber(text.to_string());
+    let (minutes, seconds) = match text.split_once(':') {
+        Some((minutes, seconds)) => (
+            parse_number::<u32>(minutes).map_err(|_| invalid())?,
+            seconds,
+        ),
+        None => (0, text),
+    };
+    let seconds = seconds.parse::<f64>().map_err(|_| invalid())?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(invalid());
+    }
+    let ms = (minutes as f64 * 60.0 + seconds) * 1000.0;
+    if ms > i32::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(ms.round() as i32)
+} //
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::mock_engine::{MockEngine, commands_received, mock_engine, mock_engine_script};
+    use crate::pgn::{PgnTags, parse_pgn};
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn observer_sees_every_move() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let mut game = Game::new(white, black, TimeControl::TimePerMove(10));
+
+        let mut observed = Vec::new();
+        let result = game.play_with_observer(|_, mv| observed.push(mv.to_uci()));
+
+        assert_eq!(observed, vec!["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(observed.len(), result.moves_list.len());
+        assert_eq!(result.outcome, Outcome::BlackWins);
+    }
+
+    #[test]
+    fn illegal_opening_moves_are_refused() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let game = || {
+            Game::new(
+                mock_engine("White", &[]),
+                mock_engine("Black", &[]),
+                TimeControl::Infinite,
+            )
+        };
+        let opening = |moves: &[&str]| Opening {
+            fen: None,
+            moves: moves.iter().map(|mv| mv.to_string()).collect(),
+        };
+        assert_eq!(
+            game()
+                .try_with_opening(3, &opening(&["e2e4", "e7e4"]))
+                .err(),
+            Some(OpeningError::IllegalMove {
+                index: 3,
+                ply: 2,
+                uci: "e7e4".to_string(),
+            })
+        );
+        assert!(
+            game()
+                .try_with_opening(3, &opening(&["e2e4", "castle"]))
+                .is_err()
+        );
+        // moves are kept canonical, as an engine's are
+        let game = game().try_with_opening(0, &opening(&["E2E4"])).unwrap();
+        assert_eq!(game.moves_list, ["e2e4"]);
+    }
+
+    #[test]
+    fn line_callback_sees_the_engines_output() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let mut game = Game::new(white, black, TimeControl::TimePerMove(10)).with_line_callback(
+            Turn::BLACK,
+            Box::new(move |line| sink.lock().unwrap().push(line.to_string())),
+        );
+        game.play();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[..2], ["id name Black", "uciok"]);
+        assert!(seen.contains(&"bestmove e7e5".to_string()));
+        assert!(seen.contains(&"bestmove d8h4".to_string()));
+        assert!(!seen.iter().any(|line| line.contains("f2f3")));
+    }
+
+    #[test]
+    fn ponder_hit_continues_the_search() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3 ponder e7e5", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let mut game = Game::new(
+            white.clone(),
+            black,
+            TimeControl::Increment {
+                base_ms: 10_000,
+                inc_ms: 100,
+            },
+        )
+        .with_ponder(true);
+        let result = game.play();
+
+        assert_eq!(result.moves_list, vec!["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let commands = commands_received(&white);
+        assert!(
+            commands
+                .iter()
+                .any(|command| command.starts_with("go ponder"))
+        );
+        assert!(commands.contains(&"ponderhit".to_string()));
+        assert!(!commands.contains(&"stop".to_string()));
+    }
+
+    #[test]
+    fn ponder_miss_restarts_the_search() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3 ponder e7e6", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let mut game = Game::new(
+            white.clone(),
+            black,
+            TimeControl::Increment {
+                base_ms: 10_000,
+                inc_ms: 100,
+            },
+        )
+        .with_ponder(true);
+        let result = game.play();
+
+        // the `bestmove 0000` answering `stop` is discarded, not played
+        assert_eq!(result.moves_list, vec!["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let commands = commands_received(&white);
+        assert!(commands.contains(&"stop".to_string()));
+        assert!(!commands.contains(&"ponderhit".to_string()));
+        assert!(commands.contains(&"position startpos moves f2f3 e7e5".to_string()));
+    }
+
+    #[test]
+    fn both_engines_are_told_to_quit_once_the_game_ends() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(10)).play();
+
+        assert_eq!(result.reason, Termination::Checkmate);
+        for engine in [&white, &black] {
+            let commands = commands_received(engine);
+            let last_go = commands
+                .iter()
+                .rposition(|command| command.starts_with("go"))
+                .unwrap();
+            assert_eq!(commands[last_go + 1..], ["quit"]);
+        }
+    }
+
+    #[test]
+    fn insufficient_material_is_a_draw() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let dead = |fen: &str| {
+            let mut board = Board::new();
+            board.load_from_fen(fen);
+            insufficient_material(&board)
+        };
+        assert!(dead("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(dead("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(dead("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1")); // both on dark squares
+        assert!(!dead("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1")); // light and dark
+        assert!(!dead("4k3/8/8/8/8/8/8/4KNN1 w - - 0 1"));
+        assert!(!dead("4kn2/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!dead("4k3/8/8/8/8/8/P7/4K3 w - - 0 1"));
+
+        // taking the last piece leaves bare kings
+        let white = mock_engine("White", &["e1d2"]);
+        let black = mock_engine("Black", &[]);
+        let result = Game::from_fen(
+            white,
+            black,
+            TimeControl::TimePerMove(10),
+            "4k3/8/8/8/8/8/3q4/4K3 w - - 0 1",
+        )
+        .unwrap()
+        .play();
+        assert_eq!(result.reason, Termination::InsufficientMaterial);
+        assert_eq!(result.outcome, Outcome::Draw);
+        assert_eq!(result.moves_list, ["e1d2"]);
+    }
+
+    #[test]
+    fn max_plies_adjudicates_a_draw() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["g1f3", "f3g1", "g1f3"]);
+        let black = mock_engine("Black", &["g8f6", "f6g8", "g8f6"]);
+        let mut game =
+            Game::new(white, black, TimeControl::TimePerMove(10)).with_max_plies(Some(4));
+        let result = game.play();
+
+        assert_eq!(result.reason, Termination::MaxLength);
+        assert_eq!(result.outcome, Outcome::Draw);
+        assert_eq!(result.moves_list.len(), 4);
+    }
+
+    #[test]
+    fn debug_is_only_sent_when_enabled() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]).with_debug(true);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let mut game = Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(10));
+        game.play();
+
+        assert!(commands_received(&white).contains(&"debug on".to_string()));
+        assert!(
+            !commands_received(&black)
+                .iter()
+                .any(|command| command.starts_with("debug"))
+        );
+    }
+
+    #[test]
+    fn log_dir_gets_one_transcript_per_process() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black Engine", &["e7e5", "d8h4"]);
+        let log_dir = Path::new(&white.path).with_file_name("logs");
+        let mut game = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_round(47)
+            .with_log_dir(&log_dir);
+        game.play();
+
+        let white_log = fs::read_to_string(log_dir.join("round-047-white-White.log")).unwrap();
+        let black_log =
+            fs::read_to_string(log_dir.join("round-047-black-Black_Engine.log")).unwrap();
+        assert!(white_log.contains("> uci\n"));
+        assert!(white_log.contains("< bestmove g2g4\n"));
+        assert!(!white_log.contains("d8h4"));
+        assert!(black_log.contains("< bestmove d8h4\n"));
+    }
+
+    #[test]
+    fn endings_are_verified_against_a_fresh_board() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(10))
+            .with_ending_verification(true)
+            .play();
+        assert_eq!(result.reason, Termination::Checkmate);
+        assert_eq!(result.ending_mismatch(), None);
+
+        // black is in check but can block, so neither ending holds
+        let game = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_start_fen("rnbqkbnr/ppppp1pp/8/5p1Q/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 2")
+            .unwrap();
+        let mismatch = game.verify_ending(Termination::Checkmate).unwrap();
+        assert!(mismatch.contains("legal moves remain"));
+        assert!(game.verify_ending(Termination::Stalemate).is_some());
+        assert_eq!(game.verify_ending(Termination::MaxLength), None);
+    }
+
+    #[test]
+    fn echoed_commands_are_ignored() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let echoing = |name: &str, moves: &[&str]| {
+            MockEngine::new(name)
+                .with_moves(moves)
+                .with_echo(true)
+                .build()
+        };
+        let white = echoing("White", &["f2f3", "g2g4"]);
+        assert!(white.banner.is_empty());
+        let result = Game::new(
+            white,
+            echoing("Black", &["e7e5", "d8h4"]),
+            TimeControl::TimePerMove(10),
+        )
+        .play();
+        assert_eq!(result.reason, Termination::Checkmate);
+        assert_eq!(result.moves_list, ["f2f3", "e7e5", "g2g4", "d8h4"]);
+    }
+
+    #[test]
+    fn upper_case_promotions_are_played_canonically() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let fen = "8/4P3/8/8/8/k7/8/K7 w - - 0 1";
+        let white = mock_engine("White", &["e7e8Q", "e8e4"]);
+        let black = mock_engine("Black", &["a3b3"]);
+        let result = Game::from_fen(white.clone(), black, TimeControl::TimePerMove(10), fen)
+            .unwrap()
+            .with_max_plies(Some(3))
+            .play();
+        assert_eq!(result.moves_list, ["e7e8q", "a3b3", "e8e4"]);
+        assert!(
+            commands_received(&white).contains(&format!("position fen {} moves e7e8q a3b3", fen))
+        );
+
+        let white = mock_engine("White", &["e7e8k"]);
+        let black = mock_engine("Black", &["a3b3"]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_start_fen(fen)
+            .unwrap()
+            .play();
+        assert_eq!(result.reason, Termination::IllegalMove);
+        assert!(result.moves_list.is_empty());
+    }
+
+    #[test]
+    fn bare_or_illegal_bestmove_loses() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // a null move resigns nothing: the position has legal moves, so it is as illegal as any
+        for (white_moves, plies) in [
+            (["e2e4", ""], 2),
+            (["e2e5", "e2e4"], 0),
+            (["e2e4", "0000"], 2),
+            (["(none)", "e2e4"], 0),
+            (["e2e4x", "e2e4"], 0),
+        ] {
+            let white = mock_engine("White", &white_moves);
+            let black = mock_engine("Black", &["e7e5", "d7d5"]);
+            let result = Game::new(white, black, TimeControl::TimePerMove(10)).play();
+
+            assert_eq!(result.reason, Termination::IllegalMove);
+            assert_eq!(result.outcome, Outcome::BlackWins);
+            assert_eq!(result.moves_list.len(), plies);
+        }
+    }
+
+    #[test]
+    fn start_fen_is_validated_and_sent() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["a1a8"]);
+        let black = mock_engine("Black", &[]);
+        assert!(matches!(
+            Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(10))
+                .with_start_fen("8/8/8/8/8/8/8/R3K3 w Q - 0 1"),
+            Err(FenError::MissingKing { white: false })
+        ));
+
+        let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w Q - 0 1";
+        let mut game = Game::new(white.clone(), black, TimeControl::TimePerMove(10))
+            .with_start_fen(fen)
+            .unwrap();
+        let result = game.play();
+
+        assert_eq!(result.reason, Termination::Checkmate);
+        assert_eq!(result.start_fen(), Some(fen));
+        assert!(result.final_fen().starts_with("R5k1/5ppp/8/8/8/8/8/4K3 b"));
+        assert!(commands_received(&white).contains(&format!("position fen {}", fen)));
+    }
+
+    #[test]
+    fn black_moves_first_from_a_black_to_move_fen() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &[]);
+        let black = mock_engine("Black", &["d8h4"]);
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 12";
+        let result = Game::new(white.clone(), black, TimeControl::TimePerMove(10))
+            .with_start_fen(fen)
+            .unwrap()
+            .play();
+
+        assert_eq!(result.moves_list, ["d8h4"]);
+        assert_eq!(result.reason, Termination::Checkmate);
+        assert_eq!(result.winner(), "Black");
+        assert!(
+            !commands_received(&white)
+                .iter()
+                .any(|command| command.starts_with("go"))
+        );
+        // numbering carries on from the FEN's fullmove counter
+        let pgn = result.to_pgn(&PgnTags::default());
+        assert!(pgn.contains("\n12... Qh4# { [%emt 0:00:"));
+        assert!(pgn.contains("] } {Final position:"));
+    }
+
+    #[test]
+    fn first_move_out_of_book_is_annotated() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f1c4", "d1h5", "h5f7"]);
+        let black = mock_engine("Black", &["b8c6", "g8f6"]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_opening_moves(&["e2e4".to_string(), "e7e5".to_string()])
+            .play();
+
+        assert_eq!(result.book_ply_count(), 2);
+        assert_eq!(result.moves_list.len(), 7);
+        let pgn = result.to_pgn(&PgnTags::default());
+        assert!(pgn.contains("\n1. e4 e5 2. Bc4 { out of book [%emt 0:00:"));
+        // the searched moves, and only those, carry their time
+        assert_eq!(pgn.matches("[%emt").count(), 5);
+        let times = result.move_times_ms();
+        assert_eq!(times.len(), result.moves_list.len());
+        assert!(times[..2].iter().all(Option::is_none) && times[2..].iter().all(Option::is_some));
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games[0].moves, result.moves_list);
+    }
+
+    #[test]
+    fn time_controls_are_parsed() {
+        let cases = [
+            ("inf", Ok(TimeControl::Infinite)),
+            (
+                "40/60",
+                Ok(TimeControl::MovesToGo {
+                    moves: 40,
+                    base_ms: 60_000,
+                    inc_ms: 0,
+                }),
+            ),
+            (
+                "40/60+0.5",
+                Ok(TimeControl::MovesToGo {
+                    moves: 40,
+                    base_ms: 60_000,
+                    inc_ms: 500,
+                }),
+            ),
+            (
+                "40/1:30",
+                Ok(TimeControl::MovesToGo {
+                    moves: 40,
+                    base_ms: 90_000,
+                    inc_ms: 0,
+                }),
+            ),
+            (
+                "5+0.1",
+                Ok(TimeControl::Increment {
+                    base_ms: 5_000,
+                    inc_ms: 100,
+                }),
+            ),
+            (
+                "300",
+                Ok(TimeControl::Increment {
+                    base_ms: 300_000,
+                    inc_ms: 0,
+                }),
+            ),
+            ("movetime=50", Ok(TimeControl::TimePerMove(50))),
+            ("nodes=100000", Ok(TimeControl::Nodes(100_000))),
+            ("depth=12", Ok(TimeControl::Depth(12))),
+            ("mate=3", Ok(TimeControl::Mate(3))),
+            ("", Err(TimeControlError::Empty)),
+            ("tb", Err(TimeControlError::Tablebase)),
+            (
+                "hours=2",
+                Err(TimeControlError::Unknown("hours=2".to_string())),
+            ),
+            (
+                "depth=deep",
+                Err(TimeControlError::InvalidNumber("deep".to_string())),
+            ),
+            ("40/", Err(TimeControlError::InvalidNumber("".to_string()))),
+            (
+                "0/60",
+                Err(TimeControlError::InvalidNumber("0".to_string())),
+            ),
+            (
+                "5+-1",
+                Err(TimeControlError::InvalidNumber("-1".to_string())),
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(TimeControl::parse(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn moves_to_go_refills_the_clock() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let time_control = TimeControl::MovesToGo {
+            moves: 2,
+            base_ms: 10_000,
+            inc_ms: 0,
+        };
+        let result = Game::new(white.clone(), black, time_control).play();
+
+        assert_eq!(result.moves_list.len(), 4);
+        let gos = commands_received(&white)
+            .into_iter()
+            .filter(|command| command.starts_with("go"))
+            .collect::<Vec<_>>();
+        assert_eq!(gos[0], "go wtime 9990 btime 9990 winc 0 binc 0 movestogo 2");
+        assert!(gos[1].ends_with("movestogo 1"));
+        // both sides made their 2 moves and got another 10 s
+        assert!(result.clocks().unwrap().iter().all(|&clock| clock > 15_000));
+    }
+
+    #[test]
+    fn cancelled_game_is_aborted() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let cancel_token = Arc::new(AtomicBool::new(true));
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_cancel_token(cancel_token)
+            .play();
+
+        assert_eq!(result.reason, Termination::Aborted);
+        assert!(result.moves_list.is_empty());
+    }
+
+    #[test]
+    fn lost_castling_rights_make_a_new_position() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // the kings shuffle back to their start squares, but without the castling rights they
+        // had: the start placement comes up a third time after 8 plies, yet the first real
+        // threefold is the kings on f1/f8 after 2, 6 and 10
+        let white = mock_engine("White", &["e1f1", "f1e1", "e1f1", "f1e1", "e1f1", "f1e1"]);
+        let black = mock_engine("Black", &["e8f8", "f8e8", "e8f8", "f8e8", "e8f8", "f8e8"]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_start_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+            .unwrap()
+            .play();
+
+        assert_eq!(result.reason, Termination::Repetition);
+        assert_eq!(result.outcome, Outcome::Draw);
+        assert_eq!(result.moves_list.len(), 10);
+    }
+
+    #[test]
+    fn fivefold_repetition_is_automatic_under_strict_rules() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // the start position comes back every 4 plies
+        let white = mock_engine("White", &["g1f3", "f3g1"].repeat(4));
+        let black = mock_engine("Black", &["g8f6", "f6g8"].repeat(4));
+        let claimed = Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(10)).play();
+        assert_eq!(claimed.reason, Termination::Repetition);
+        assert_eq!(claimed.moves_list.len(), 8);
+
+        let strict = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_draw_rules(DrawRules::StrictFide)
+            .play();
+        assert_eq!(strict.reason, Termination::Repetition);
+        assert_eq!(strict.outcome, Outcome::Draw);
+        assert_eq!(strict.moves_list.len(), 16);
+    }
+
+    #[test]
+    fn quiet_moves_draw_at_fifty_or_seventy_five() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["a1a2", "a2a3"]);
+        let black = mock_engine("Black", &["e8d8", "d8e8"]);
+        let claimed = Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(10))
+            .with_start_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80")
+            .unwrap()
+            .play();
+        assert_eq!(claimed.reason, Termination::FiftyMoves);
+        assert_eq!(claimed.moves_list, ["a1a2"]);
+
+        let strict = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_draw_rules(DrawRules::StrictFide)
+            .with_start_fen("4k3/8/8/8/8/8/8/R3K3 w - - 147 80")
+            .unwrap()
+            .play();
+        assert_eq!(strict.reason, Termination::FiftyMoves);
+        assert_eq!(strict.moves_list, ["a1a2", "e8d8", "a2a3"]);
+    }
+
+    #[test]
+    fn seeded_jitter_spreads_and_repeats_movetimes() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let movetimes = |seed| {
+            let white = mock_engine("White", &["f2f3", "g2g4"]);
+            let black = mock_engine("Black", &["e7e5", "d8h4"]);
+            Game::new(white.clone(), black.clone(), TimeControl::TimePerMove(100))
+                .with_time_jitter(Duration::from_millis(20), Some(seed))
+                .play();
+            [white, black].map(|engine| {
+                commands_received(&engine)
+                    .iter()
+                    .filter_map(|command| command.strip_prefix("go movetime "))
+                    .map(|time| time.parse::<i32>().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        let first = movetimes(7);
+        assert!(first.iter().flatten().all(|time| (80..=120).contains(time)));
+        let all = first.concat();
+        assert!(all.iter().any(|&time| time != all[0]));
+        assert_eq!(movetimes(7), first);
+    }
+
+    #[test]
+    fn replay_plays_the_same_game_again() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // every process of a mock engine starts its moves over, as a deterministic engine would
+        let white = mock_engine("White", &["g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white.clone(), black, TimeControl::Depth(3))
+            .with_start_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .unwrap()
+            .with_opening(
+                4,
+                &Opening {
+                    fen: None,
+                    moves: vec!["f2f3".to_string()],
+                },
+            )
+            .with_slots(1, 0)
+            .play();
+        assert_eq!(result.moves_list, ["f2f3", "e7e5", "g2g4", "d8h4"]);
+
+        let replayed = result.replay().unwrap();
+        assert_eq!(replayed.moves_list, result.moves_list);
+        assert_eq!(replayed.outcome, Outcome::BlackWins);
+        assert_eq!(replayed.book_ply_count(), 1);
+        assert_eq!(replayed.opening_index(), Some(4));
+        assert_eq!(replayed.winner_slot(), Some(0));
+        assert_eq!(
+            commands_received(&white)
+                .iter()
+                .filter(|command| command.starts_with("go depth 3"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn outcomes_convert_to_and_from_results() {
+        for (result, outcome) in [
+            (1, Outcome::WhiteWins),
+            (-1, Outcome::BlackWins),
+            (0, Outcome::Draw),
+        ] {
+            assert_eq!(Outcome::from(result), outcome);
+            assert_eq!(i32::from(outcome), result);
+        }
+        assert_eq!(Outcome::from(2), Outcome::WhiteWins);
+        assert_eq!(Outcome::from(-7), Outcome::BlackWins);
+        assert_eq!(Outcome::loss_for(Turn::WHITE), Outcome::BlackWins);
+        assert_eq!(Outcome::loss_for(Turn::BLACK), Outcome::WhiteWins);
+        assert!(!Outcome::Draw.is_decisive());
+        assert_eq!(Outcome::BlackWins.pgn_token(), "0-1");
+    }
+
+    #[test]
+    fn stalemate_is_a_draw() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &[]);
+        let black = mock_engine("Black", &[]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_start_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1")
+            .unwrap()
+            .play();
+
+        assert_eq!(result.reason, Termination::Stalemate);
+        assert_eq!(result.outcome, Outcome::Draw);
+        assert_eq!(result.winner(), "");
+    }
+
+    #[test]
+    fn hung_engine_is_caught_by_the_idle_watchdog() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // searches for ages without a word, answering isready in between
+        let slow = MockEngine::new("Slow")
+            .with_moves(&["f2f3", "g2g4"])
+            .with_go(r#"mv=$1; shift; (sleep 0.3; echo "bestmove $mv") &"#)
+            .build()
+            .with_idle_timeout(Some(Duration::from_millis(50)));
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(slow, black, TimeControl::Infinite).play();
+        assert_eq!(result.reason, Termination::Checkmate);
+
+        // stuck in its search loop, so it hears neither isready nor stop
+        let hung = MockEngine::new("Hung")
+            .with_go("sleep 5")
+            .build()
+            .with_idle_timeout(Some(Duration::from_millis(100)));
+        let black = mock_engine("Black", &[]);
+        let start = Instant::now();
+        let result = Game::new(hung, black, TimeControl::Infinite).play();
+        assert_eq!(result.reason, Termination::EngineCrash);
+        assert_eq!(result.outcome, Outcome::BlackWins);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn infinite_search_is_stopped_at_the_search_timeout() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // `go infinite` never ends by itself, only `stop` gets a move out of the engine
+        let white = MockEngine::new("White")
+            .with_moves(&["f2f3", "g2g4"])
+            .with_go("pending=$1; shift")
+            .with_case(r#"stop) echo "bestmove $pending" ;;"#)
+            .build();
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white, black, TimeControl::Infinite)
+            .with_search_timeout(Duration::from_millis(50))
+            .play();
+        assert_eq!(result.reason, Termination::Checkmate);
+
+        // silent with no idle watchdog, and deaf to `stop` as well
+        let hung = MockEngine::new("Hung").with_go("sleep 5").build();
+        let black = mock_engine("Black", &[]);
+        let start = Instant::now();
+        let result = Game::new(hung, black, TimeControl::Infinite)
+            .with_search_timeout(Duration::from_millis(100))
+            .play();
+        assert_eq!(result.reason, Termination::EngineCrash);
+        assert_eq!(result.outcome, Outcome::BlackWins);
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn crashed_engine_leaves_its_stderr_in_the_result() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // complains on stderr and dies on its first search
+        let crasher = mock_engine_script(
+            "Crasher",
+            r#"echo "loading network" >&2
+while read -r line; do
+  case "$line" in
+    uci) echo "id name Crasher"; echo "uciok" ;;
+    isready) echo "readyok" ;;
+    go*) echo "assertion failed: square < 64" >&2; exit 1 ;;
+    quit) exit 0 ;;
+  esac
+done
+"#,
+        );
+        let black = mock_engine("Black", &[]);
+        let result = Game::new(crasher, black, TimeControl::Depth(1))
+            .with_search_timeout(Duration::from_millis(100))
+            .play();
+        assert_eq!(result.reason, Termination::EngineCrash);
+        assert_eq!(
+            result.engine_stderr(),
+            ["loading network", "assertion failed: square < 64"]
+        );
+
+        // nothing is kept for a game that ended normally
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10)).play();
+        assert_eq!(result.reason, Termination::Checkmate);
+        assert!(result.engine_stderr().is_empty());
+    }
+
+    #[test]
+    fn search_info_is_kept_for_every_move() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let talker = MockEngine::new("Talker")
+            .with_go(
+                r#"
+      echo "info depth 1 seldepth 2 score cp 5 nodes 20 nps 1000 pv e2e4"
+      echo "info depth 2 seldepth 4 score cp 12 nodes 80 nps 4000 pv e2e4 e7e5"
+      echo "bestmove e2e4""#,
+            )
+            .build();
+        let black = mock_engine("Black", &["e7e5"]);
+        let result = Game::new(talker, black, TimeControl::Depth(2))
+            .with_max_plies(Some(2))
+            .play();
+        assert_eq!(result.moves_list, ["e2e4", "e7e5"]);
+        assert_eq!(result.scores[0], Some(Score::Cp(12)));
+        let stats = result.move_stats()[0].unwrap();
+        assert_eq!(
+            (stats.depth, stats.seldepth, stats.nodes, stats.nps),
+            (Some(2), Some(4), Some(80), Some(4000))
+        );
+        assert_eq!(result.pvs()[0], ["e2e4", "e7e5"]);
+        // the plain mock sends no info at all
+        let stats = result.move_stats()[1].unwrap();
+        assert_eq!((stats.depth, stats.seldepth, stats.nps), (None, None, None));
+        assert!(result.pvs()[1].is_empty());
+    }
+
+    // Plays and scores its entries in turn, each a move and a score, e.g. `g1f3:cp:-600`.
+    fn scored_engine(name: &str, entries: &str) -> Engine {
+        MockEngine::new(name)
+            .with_moves(&entries.split_whitespace().collect::<Vec<_>>())
+            .with_go(
+                r#"
+      mv=${1%%:*}; score=$(echo "${1#*:}" | tr ':' ' '); shift
+      echo "info depth 1 score $score pv $mv"
+      echo "bestmove $mv""#,
+            )
+            .build()
+    }
+
+    #[test]
+    fn hopeless_scores_resign_the_game_for_either_side() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // white is down 600 on three moves in a row
+        let white = scored_engine(
+            "White",
+            "g1f3:cp:-600 f3g1:cp:-600 g1f3:cp:-600 f3g1:cp:-600",
+        );
+        let black = scored_engine("Black", "g8f6:cp:600 f6g8:cp:600 g8f6:cp:600");
+        let result = Game::new(white, black, TimeControl::Depth(1))
+            .with_resign_adjudication(500, 3)
+            .play();
+        assert_eq!(result.reason, Termination::Adjudicated);
+        assert_eq!(result.outcome, Outcome::BlackWins);
+        assert_eq!(result.moves_list.len(), 5);
+
+        // black sees itself mated, and a better score in between starts the count again
+        let white = scored_engine("White", "g1f3:cp:0 f3g1:cp:0 g1f3:cp:0 f3g1:cp:0");
+        let black = scored_engine(
+            "Black",
+            "g8f6:mate:-5 f6g8:cp:-100 g8f6:mate:-4 f6g8:mate:-3",
+        );
+        let result = Game::new(white, black, TimeControl::Depth(1))
+            .with_resign_adjudication(500, 2)
+            .play();
+        assert_eq!(result.reason, Termination::Adjudicated);
+        assert_eq!(result.outcome, Outcome::WhiteWins);
+        assert_eq!(result.moves_list.len(), 8);
+
+        // a mate for the mover is no reason to resign
+        let white = scored_engine("White", "g1f3:mate:3 f3g1:mate:2");
+        let black = scored_engine("Black", "g8f6:cp:0 f6g8:cp:0");
+        let result = Game::new(white, black, TimeControl::Depth(1))
+            .with_resign_adjudication(500, 1)
+            .with_max_plies(Some(4))
+            .play();
+        assert_eq!(result.reason, Termination::MaxLength);
+    }
+
+    #[test]
+    fn level_scores_adjudicate_a_draw() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let shuffle_white = "g1f3:cp:10 f3g1:cp:-5 g1f3:cp:10 f3g1:cp:-5";
+        let shuffle_black = "g8f6:cp:0 f6g8:cp:0 g8f6:cp:0 f6g8:cp:0";
+        let result = Game::new(
+            scored_engine("White", shuffle_white),
+            scored_engine("Black", shuffle_black),
+            TimeControl::Depth(1),
+        )
+        .with_draw_adjudication(20, 4, 0)
+        .play();
+        assert_eq!(result.reason, Termination::DrawAdjudicated);
+        assert_eq!(result.outcome, Outcome::Draw);
+        assert_eq!(result.moves_list.len(), 4);
+
+        // the count is in plies, not moves
+        let result = Game::new(
+            scored_engine("White", shuffle_white),
+            scored_engine("Black", shuffle_black),
+            TimeControl::Depth(1),
+        )
+        .with_draw_adjudication(20, 3, 0)
+        .play();
+        assert_eq!(result.reason, Termination::DrawAdjudicated);
+        assert_eq!(result.moves_list.len(), 3);
+
+        // only once move 3 is over, so not on black's third move but on white's fourth
+        let result = Game::new(
+            scored_engine("White", shuffle_white),
+            scored_engine("Black", shuffle_black),
+            TimeControl::Depth(1),
+        )
+        .with_draw_adjudication(20, 1, 3)
+        .play();
+        assert_eq!(result.reason, Termination::DrawAdjudicated);
+        assert_eq!(result.moves_list.len(), 7);
+
+        // white's 50 starts the count again
+        let result = Game::new(
+            scored_engine("White", "g1f3:cp:10 f3g1:cp:50 g1f3:cp:10 f3g1:cp:10"),
+            scored_engine("Black", shuffle_black),
+            TimeControl::Depth(1),
+        )
+        .with_draw_adjudication(20, 4, 0)
+        .play();
+        assert_eq!(result.reason, Termination::DrawAdjudicated);
+        assert_eq!(result.moves_list.len(), 7);
+    }
+
+    #[test]
+    #[cfg(feature = "syzygy")]
+    fn unreadable_tablebase_aborts_the_game() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let missing = Path::new(&white.path).with_file_name("no-such-tables");
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_tablebase(&missing)
+            .with_tb_adjudication(true)
+            .play();
+        assert_eq!(result.reason, Termination::Aborted);
+        assert!(result.moves_list.is_empty());
+
+        // without adjudication the path is only handed to the engines
+        let white = mock_engine("White", &["f2f3", "g2g4"]);
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white, black, TimeControl::TimePerMove(10))
+            .with_tablebase(&missing)
+            .play();
+        assert_eq!(result.reason, Termination::Checkmate);
+    }
+
+    #[test]
+    fn engine_dying_mid_search_is_noticed_at_once() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let dying = MockEngine::new("Dying").with_go("exit 1").build();
+        let black = mock_engine("Black", &[]);
+        let start = Instant::now();
+        let result = Game::new(dying, black, TimeControl::Infinite).play();
+        assert_eq!(result.reason, Termination::EngineCrash);
+        assert_eq!(result.outcome, Outcome::BlackWins);
+        // neither an unbounded wait nor the second it takes to give up on an unanswered isready
+        assert!(start.elapsed() < Duration::from_millis(800));
+    }
+
+    #[test]
+    fn position_command_grows_with_the_game() {
+        let mut position = PositionCommand::new(None);
+        assert_eq!(position.command(None), "position startpos\n");
+        assert_eq!(
+            position.command(Some("e2e4")),
+            "position startpos moves e2e4\n"
+        );
+        position.push("e2e4");
+        position.push("e7e5");
+        assert_eq!(
+            position.command(None),
+            "position startpos moves e2e4 e7e5\n"
+        );
+        assert_eq!(
+            position.command(Some("g1f3")),
+            "position startpos moves e2e4 e7e5 g1f3\n"
+        );
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let mut position = PositionCommand::new(Some(fen));
+        position.push("e1g1");
+        assert_eq!(position.plies(), 1);
+        assert_eq!(
+            position.command(None),
+            format!("position fen {} moves e1g1\n", fen)
+        );
+    }
+
+    #[test]
+    fn overhead_is_reported_and_grace_is_judged() {
+        init_bishop_magics();
+        init_rook_magics();
+        let increment = TimeControl::Increment {
+            base_ms: 100,
+            inc_ms: 100,
+        };
+        // takes 150ms a move on a 100ms clock
+        let slow = || {
+            MockEngine::new("Slow")
+                .with_moves(&["f2f3", "g2g4"])
+                .with_go(r#"sleep 0.15; echo "bestmove $1"; shift"#)
+                .build()
+        };
+
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(slow(), black, increment)
+            .with_timeout_grace(Duration::ZERO)
+            .play();
+        assert_eq!(result.reason, Termination::TimeForfeit);
+
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(slow(), black.clone(), increment)
+            .with_timeout_grace(Duration::from_millis(500))
+            .with_move_overhead(Duration::from_millis(30))
+            .play();
+        assert_eq!(result.reason, Termination::Checkmate);
+        // white's clock was at zero plus the increment after each late move
+        assert_eq!(
+            commands_received(&black)
+                .iter()
+                .find(|command| command.starts_with("go")),
+            Some(&"go wtime 70 btime 70 winc 100 binc 100".to_string())
+        );
+    }
+
+    #[test]
+    fn configured_options_are_sent_before_the_game() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let engine = |name: &str, moves: &[&str]| {
+            MockEngine::new(name)
+                .with_moves(moves)
+                .with_uci_line("option name Hash type spin default 16 min 1 max 1024")
+                .build()
+        };
+        let white = engine("White", &["f2f3", "g2g4"]).with_option("Hash", " 64");
+        // the dry run and the game agree on it
+        assert!(white.check_configured_options().is_empty());
+        let black = engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white.clone(), black, TimeControl::TimePerMove(10)).play();
+        assert_eq!(result.reason, Termination::Checkmate);
+        let commands = commands_received(&white);
+        let hash = commands
+            .iter()
+            .position(|c| c == "setoption name Hash value 64")
+            .unwrap();
+        let go = commands.iter().position(|c| c.starts_with("go")).unwrap();
+        assert!(commands[0] == "uci" && hash < go);
+
+        // out of range: nothing is sent and no game is played
+        let white = engine("White", &["f2f3", "g2g4"]).with_option("Hash", "4096");
+        let result = Game::new(
+            white.clone(),
+            engine("Black", &["e7e5", "d8h4"]),
+            TimeControl::TimePerMove(10),
+        )
+        .play();
+        assert_eq!(result.reason, Termination::Aborted);
+        assert!(result.moves_list.is_empty());
+        assert!(
+            !commands_received(&white)
+                .iter()
+                .any(|c| c.starts_with("setoption") || c.starts_with("go"))
+        );
+    }
+
+    #[test]
+    fn engines_are_synced_before_the_first_search() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let white = mock_engine("White", &["f2f3", "g2g4"]).with_option("Hash", "64");
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(white.clone(), black, TimeControl::TimePerMove(10)).play();
+        assert_eq!(result.reason, Termination::Checkmate);
+        // the game's own process comes after the ones Engine::new started
+        let commands = commands_received(&white);
+        let start = commands.iter().rposition(|c| c == "uci").unwrap();
+        assert_eq!(
+            commands[start..start + 5],
+            [
+                "uci",
+                "isready",
+                "setoption name Hash value 64",
+                "ucinewgame",
+                "isready"
+            ]
+        );
+        assert!(commands[start + 5].starts_with("position"));
+        assert!(commands[start + 6].starts_with("go"));
+    }
+
+    #[test]
+    fn overrunning_the_movetime_loses_on_time() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // ignores movetime and answers after 300ms
+        let slow = MockEngine::new("Slow")
+            .with_moves(&["f2f3", "g2g4"])
+            .with_go(r#"sleep 0.3; echo "bestmove $1"; shift"#)
+            .build();
+        let black = mock_engine("Black", &["e7e5", "d8h4"]);
+        let result = Game::new(slow.clone(), black.clone(), TimeControl::TimePerMove(50)).play();
+        assert_eq!(result.reason, Termination::TimeForfeit);
+        assert_eq!(result.outcome, Outcome::BlackWins);
+        assert!(result.moves_list.is_empty());
+
+        // the clocks' grace plays no part in a fixed movetime
+        let result = Game::new(slow.clone(), black.clone(), TimeControl::TimePerMove(50))
+            .with_timeout_grace(Duration::from_secs(1))
+            .play();
+        assert_eq!(result.reason, Termination::TimeForfeit);
+
+        let result = Game::new(slow, black, TimeControl::TimePerMove(50))
+            .with_movetime_margin(Duration::from_secs(1))
+            .play();
+        assert_eq!(result.reason, Termination::Checkmate);
+    }
+}
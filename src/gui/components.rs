@@ -1,7 +1,4 @@
-use crate::gui::constants::{
-    BLACK_BISHOP, BLACK_KING, BLACK_KNIGHT, BLACK_PAWN, BLACK_QUEEN, BLACK_ROOK, WHITE_BISHOP,
-    WHITE_KING, WHITE_KNIGHT, WHITE_PAWN, WHITE_QUEEN, WHITE_ROOK,
-};
+use crate::gui::theme::PieceTheme;
 use gpui::{Div, ElementId, FontWeight, SharedString, Stateful, div, img, prelude::*, px, rgb, deferred};
 use queenfish::board::pieces::PieceType;
 
@@ -75,6 +72,7 @@ pub fn board_square(
     i: usize,
     selected_square: Option<usize>,
     piece: Option<PieceType>,
+    piece_theme: &PieceTheme,
     is_board_flipped: bool,
     is_king_in_check: bool,
     current_turn_king_sq: usize,
@@ -91,30 +89,22 @@ pub fn board_square(
     } else {
         super::colors::BOARD_DARK
     };
+    // coordinates take the other square color, decided before any highlight repaints the square
+    let label_color = if color == super::colors::BOARD_DARK {
+        rgb(super::colors::BOARD_LIGHT)
+    } else {
+        rgb(super::colors::BOARD_DARK)
+    };
 
     if let Some(selected_square) = selected_square {
         if selected_square == i {
             color = super::colors::SQUARE_SELECTION;
         }
     }
-    let mut piece_image = "";
-    if let Some(piece) = piece {
-        piece_image = match piece as usize {
-            0 => WHITE_PAWN,
-            1 => WHITE_KNIGHT,
-            2 => WHITE_BISHOP,
-            3 => WHITE_ROOK,
-            4 => WHITE_QUEEN,
-            5 => WHITE_KING,
-            6 => BLACK_PAWN,
-            7 => BLACK_KNIGHT,
-            8 => BLACK_BISHOP,
-            9 => BLACK_ROOK,
-            10 => BLACK_QUEEN,
-            11 => BLACK_KING,
-            _ => "",
-        };
-    }
+    let piece_image = match piece {
+        Some(piece) => piece_theme.piece_path(piece),
+        None => Path::new(""),
+    };
 
     let mut element = div()
         .size_full()
@@ -131,10 +121,7 @@ pub fn board_square(
                 .absolute()
                 .right(px(3.))
                 .bottom_0()
-                .text_color(match color {
-                    super::colors::BOARD_DARK => rgb(super::colors::BOARD_LIGHT),
-                    _ => rgb(super::colors::BOARD_DARK),
-                })
+                .text_color(label_color)
                 .text_size(px(10.))
                 .child(((b'a' + (i as u8 % 8)) as char).to_string()),
         );
@@ -145,10 +132,7 @@ pub fn board_square(
                 .absolute()
                 .left(px(3.))
                 .top_0()
-                .text_color(match color {
-                    super::colors::BOARD_DARK => rgb(super::colors::BOARD_LIGHT),
-                    _ => rgb(super::colors::BOARD_DARK),
-                })
+                .text_color(label_color)
                 .text_size(px(10.))
                 .child(((i / 8) + 1).to_string()),
         );
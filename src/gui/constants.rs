@@ -1,13 +1,7 @@
-pub const WHITE_PAWN: &str = "pieces\\wP.svg";
-pub const WHITE_KNIGHT: &str = "pieces\\wN.svg";
-pub const WHITE_BISHOP: &str = "pieces\\wB.svg";
-pub const WHITE_ROOK: &str = "pieces\\wR.svg";
-pub const WHITE_QUEEN: &str = "pieces\\wQ.svg";
-pub const WHITE_KING: &str = "pieces\\wK.svg";
+pub const DEFAULT_PIECE_THEME: &str = "pieces";
 
-pub const BLACK_PAWN: &str = "pieces\\bP.svg";
-pub const BLACK_KNIGHT: &str = "pieces\\bN.svg";
-pub const BLACK_BISHOP: &str = "pieces\\bB.svg";
-pub const BLACK_ROOK: &str = "pieces\\bR.svg";
-pub const BLACK_QUEEN: &str = "pieces\\bQ.svg";
-pub const BLACK_KING: &str = "pieces\\bK.svg";
\ No newline at end of file
+// svg file of each piece inside a theme directory, indexed by `PieceType as usize`
+pub const PIECE_FILES: [&str; 12] = [
+    "wP.svg", "wN.svg", "wB.svg", "wR.svg", "wQ.svg", "wK.svg", "bP.svg", "bN.svg", "bB.svg",
+    "bR.svg", "bQ.svg", "bK.svg",
+];
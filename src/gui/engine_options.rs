@@ -63,6 +63,13 @@ impl Render for EngineOptionsWindow {
                     min.unwrap_or(0),
                     max.unwrap_or(0)
                 )),
+                EngineOption::COMBO {
+                    name,
+                    value,
+                    choices,
+                } => div().child(format!("{}: {} ({})", name, value, choices.join("/"))),
+                EngineOption::STRING { name, value } => div().child(format!("{}: {}", name, value)),
+                EngineOption::BUTTON { name } => div().child(name.clone()),
             });
         div()
             .id("engine_options_window")
@@ -2,6 +2,7 @@ use gpui::{Entity, Focusable, App, Render, Window, Context, IntoElement, rgb ,di
 use crate::gui::input::{InputController};
 use crate::gui::state::SharedState;
 use super::components::button;
+use crate::fen::validate_fen;
 
 
 
@@ -42,6 +43,10 @@ impl Render for FenWindow {
                     let input_controller = this.input_controller.clone().read(cx);
                     let input_field = input_controller.text_input.clone().read(cx);
                     let content = input_field.content.as_str().to_string();
+                    // an illegal position would only be rejected by the engines later
+                    if validate_fen(&content).is_err() {
+                        return;
+                    }
                     cx.global_mut::<SharedState>().fen_string =
                         Some(SharedString::from(content.clone()));
                     cx.notify();
@@ -4,5 +4,6 @@ pub mod colors;
 pub mod constants;
 pub mod fen_window;
 pub mod state;
+pub mod theme;
 pub mod components;
 pub mod engine_options;
\ No newline at end of file
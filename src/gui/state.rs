@@ -1,5 +1,6 @@
 use gpui::{Global, SharedString};
 use crate::Engine;
+use crate::gui::theme::PieceTheme;
 use queenfish::board::Board as QueenFishBoard;
 
 pub struct EnginesServices {
@@ -41,6 +42,7 @@ impl EnginesServices {
 pub struct SharedState {
     pub fen_string: Option<SharedString>,
     pub engines: EnginesServices,
+    pub piece_theme: PieceTheme,
 }
 impl Global for SharedState {}
 
@@ -49,6 +51,7 @@ impl SharedState {
         SharedState {
             fen_string: None,
             engines: EnginesServices::new(),
+            piece_theme: PieceTheme::default(),
         }
     }
 }
\ No newline at end of file
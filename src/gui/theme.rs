@@ -0,0 +1,47 @@
+use crate::gui::constants::{DEFAULT_PIECE_THEME, PIECE_FILES};
+use queenfish::board::pieces::PieceType;
+use std::path::{Path, PathBuf};
+
+/// A piece set: a directory holding one svg per piece, named as in `PIECE_FILES`.
+#[derive(Debug, Clone)]
+pub struct PieceTheme {
+    dir: PathBuf,
+    paths: Vec<PathBuf>, // resolved once, indexed like PIECE_FILES
+}
+
+impl Default for PieceTheme {
+    fn default() -> Self {
+        PieceTheme::new(DEFAULT_PIECE_THEME)
+    }
+}
+
+impl PieceTheme {
+    /// Pieces missing from `dir` fall back to the default theme's.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let paths = PIECE_FILES
+            .iter()
+            .map(|file| {
+                let path = dir.join(file);
+                if path.is_file() {
+                    return path;
+                }
+                log::warn!(
+                    "Piece theme {} has no {}, using the default one",
+                    dir.display(),
+                    file
+                );
+                Path::new(DEFAULT_PIECE_THEME).join(file)
+            })
+            .collect();
+        PieceTheme { dir, paths }
+    } //
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    } //
+
+    pub fn piece_path(&self, piece: PieceType) -> &Path {
+        &self.paths[piece as usize]
+    } //
+}
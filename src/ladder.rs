@@ -0,0 +1,157 @@
+use crate::elo::estimate_rating;
+use crate::engine::Engine;
+use crate::game::{Game, GameResult, Termination, TimeControl};
+
+const DEFAULT_MAX_GAMES: usize = 100;
+const DEFAULT_TARGET_MARGIN: f64 = 50.0;
+
+/// Rates a candidate engine against a pool of engines with known ratings. Games are played in
+/// pairs, one with each color, each pair against the pool engine rated closest to the current
+/// estimate, until the estimate's 95% margin is small enough.
+pub struct EloLadder {
+    candidate: Engine,
+    rated_pool: Vec<(Engine, f64)>,
+    time_control: TimeControl,
+    max_games: usize,
+    target_margin: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LadderResult {
+    pub rating: f64,
+    pub margin: f64,                          // 95% confidence, in Elo
+    pub games: Vec<(usize, f64, GameResult)>, // pool index, candidate's score, the game
+}
+
+impl EloLadder {
+    pub fn new(
+        candidate: Engine,
+        rated_pool: Vec<(Engine, f64)>,
+        time_control: TimeControl,
+    ) -> Self {
+        EloLadder {
+            candidate,
+            rated_pool,
+            time_control,
+            max_games: DEFAULT_MAX_GAMES,
+            target_margin: DEFAULT_TARGET_MARGIN,
+        }
+    } //
+
+    /// Stop after this many games even if the margin is still wide.
+    pub fn with_max_games(mut self, max_games: usize) -> Self {
+        self.max_games = max_games;
+        self
+    } //
+
+    /// Stop once the 95% margin of the rating is at most this many Elo.
+    pub fn with_target_margin(mut self, target_margin: f64) -> Self {
+        self.target_margin = target_margin;
+        self
+    } //
+
+    /// None if the pool is empty or no game could be finished.
+    pub fn run(&self) -> Option<LadderResult> {
+        let pool_size = self.rated_pool.len() as f64;
+        // before any game the candidate is assumed to be average for the pool
+        let mut rating = self
+            .rated_pool
+            .iter()
+            .map(|(_, rating)| rating)
+            .sum::<f64>()
+            / pool_size;
+        let mut margin = f64::INFINITY;
+        let mut games = Vec::new();
+        let mut played = 0;
+        while played < self.max_games && margin > self.target_margin {
+            let opponent = self.closest_opponent(rating)?;
+            for candidate_white in [true, false] {
+                if played == self.max_games {
+                    break;
+                }
+                played += 1;
+                let game_result = self.play(opponent, candidate_white);
+                if game_result.reason() == Termination::Aborted {
+                    continue;
+                }
+                let score = match game_result.winner_slot() {
+                    Some(0) => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5,
+                };
+                games.push((opponent, score, game_result));
+            }
+
+            let results = games
+                .iter()
+                .map(|(opponent, score, _)| (self.rated_pool[*opponent].1, *score))
+                .collect::<Vec<_>>();
+            if let Some(estimate) = estimate_rating(&results) {
+                (rating, margin) = estimate;
+            }
+        }
+        (!games.is_empty()).then_some(LadderResult {
+            rating,
+            margin,
+            games,
+        })
+    } //
+
+    fn closest_opponent(&self, rating: f64) -> Option<usize> {
+        (0..self.rated_pool.len()).min_by(|&a, &b| {
+            let distance = |index: usize| (self.rated_pool[index].1 - rating).abs();
+            distance(a).total_cmp(&distance(b))
+        })
+    } //
+
+    // the candidate is slot 0 and the opponent slot 1, as in a tournament
+    fn play(&self, opponent: usize, candidate_white: bool) -> GameResult {
+        let candidate = self.candidate.clone();
+        let opponent = self.rated_pool[opponent].0.clone();
+        if candidate_white {
+            Game::new(candidate, opponent, self.time_control).with_slots(0, 1)
+        } else {
+            Game::new(opponent, candidate, self.time_control).with_slots(1, 0)
+        }
+        .play()
+    } //
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::mock_engine::mock_engine;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    #[test]
+    fn a_losing_candidate_slides_down_the_ladder() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // never produces a move, so it loses every game
+        let candidate = mock_engine("Candidate", &[]);
+        let weak = mock_engine("Weak", &["e2e4"]);
+        let strong = mock_engine("Strong", &["e2e4"]);
+        let ladder = EloLadder::new(
+            candidate,
+            vec![(strong, 2000.0), (weak, 1000.0)],
+            TimeControl::TimePerMove(10),
+        )
+        .with_max_games(4);
+        let result = ladder.run().unwrap();
+
+        assert_eq!(result.games.len(), 4);
+        assert!(result.games.iter().all(|(_, score, _)| *score == 0.0));
+        // both are 500 from the starting guess of 1500, so the first listed is taken first;
+        // after that the estimate has dropped towards the weak engine
+        let opponents = result
+            .games
+            .iter()
+            .map(|(opponent, ..)| *opponent)
+            .collect::<Vec<_>>();
+        assert_eq!(opponents, [0, 0, 1, 1]);
+        assert!((result.rating - 200.0).abs() < 1e-6);
+    }
+}
@@ -1,10 +1,83 @@
+//! Plays UCI chess engines against each other.
+//!
+//! A downstream binary needs little more than [`Engine::new`] for each side, a [`TimeControl`]
+//! and either [`Game`] for a single game or [`Tournament`] for a match:
+//!
+//! ```no_run
+//! use arena::{Engine, TimeControl, Tournament};
+//! use queenfish::board::bishop_magic::init_bishop_magics;
+//! use queenfish::board::rook_magic::init_rook_magics;
+//!
+//! // move generation needs queenfish's magic tables, once per process
+//! init_bishop_magics();
+//! init_rook_magics();
+//!
+//! let first = Engine::new("/usr/bin/stockfish", "Stockfish");
+//! let second = Engine::new("./my-engine", "Mine");
+//! let mut tournament = Tournament::new(10, first, second, TimeControl::TimePerMove(100));
+//! let result = tournament.start();
+//! println!(
+//!     "{} {} - {} {} ({} draws)",
+//!     result.engine1(),
+//!     result.engine1_won(),
+//!     result.engine2_won(),
+//!     result.engine2(),
+//!     result.draws()
+//! );
+//! ```
+//!
+//! Everything else, from opening books to engine options and PGN output, is opt-in through the
+//! `with_*` builders on those types. A finished game's [`GameResult`] and a match's
+//! [`TournamentResult`] are read through accessors; engine processes stay behind
+//! [`EngineHandle`].
+
+pub mod csv_export;
+pub mod elo;
 pub mod engine;
+pub mod error;
+pub mod fen;
 pub mod game;
+pub mod ladder;
+pub mod move_cache;
+pub mod opening_book;
+pub mod oracle;
+pub mod perft;
+pub mod pgn;
+pub mod pgn_analyzer;
+pub mod pgn_filter;
+pub mod polyglot;
+pub mod round_robin;
+pub mod san;
+pub mod sprt;
 pub mod tournament;
 pub mod gui;
+#[cfg(feature = "syzygy")]
+pub mod tablebase;
+#[cfg(feature = "http")]
+pub mod standings_server;
+
+#[cfg(test)]
+#[cfg(unix)]
+mod mock_engine;
 
+pub use csv_export::*;
+pub use elo::*;
 pub use engine::*;
+pub use error::*;
+pub use fen::*;
 pub use game::*;
+pub use ladder::*;
+pub use move_cache::*;
+pub use opening_book::*;
+pub use oracle::*;
+pub use perft::*;
+pub use pgn::*;
+pub use pgn_analyzer::*;
+pub use pgn_filter::*;
+pub use polyglot::*;
+pub use round_robin::*;
+pub use san::*;
+pub use sprt::*;
 pub use tournament::*;
 
 #[cfg(test)]
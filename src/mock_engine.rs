@@ -0,0 +1,170 @@
+// Scripted UCI engines for tests. Each one is a small sh script, so these only run on unix.
+use crate::engine::Engine;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn script_path() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "arena-mock-{}-{}",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::create_dir_all(&dir).expect("Failed to create mock engine directory");
+    // Engine::new accepts `.exe` and extensionless files alike
+    dir.join("engine.exe")
+}
+
+/// Writes an arbitrary sh `script` as an engine binary, for engines `Engine::new` can't start.
+pub fn mock_script(script: &str) -> PathBuf {
+    let path = script_path();
+    fs::write(&path, format!("#!/bin/sh\n{}", script)).expect("Failed to write mock engine");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+        .expect("Failed to make mock engine executable");
+    path
+}
+
+/// Engine running an arbitrary sh `script`.
+pub fn mock_engine_script(name: &str, script: &str) -> Engine {
+    Engine::new(mock_script(script).to_str().unwrap(), name)
+}
+
+/// Engine that answers each `go` (or `ponderhit`) with the next entry of `moves`, ignoring the
+/// position. Entries may carry a ponder move, e.g. `"e2e4 ponder e7e5"`. `go ponder` waits
+/// silently and `stop` is answered with a null move. Every command received is logged, see
+/// `commands_received`.
+pub fn mock_engine(name: &str, moves: &[&str]) -> Engine {
+    MockEngine::new(name)
+        .with_moves(moves)
+        .with_case(r#""go ponder"*) ;;"#)
+        .with_case(r#"ponderhit) echo "bestmove $1"; shift ;;"#)
+        .with_case(r#"stop) echo "bestmove 0000" ;;"#)
+        .build()
+}
+
+/// Engine that plays `e2e4` as white and `e7e5` as black, whatever came before, taking `delay`
+/// over each move.
+pub fn king_pawn_engine(name: &str, delay: Duration) -> Engine {
+    MockEngine::new(name)
+        .with_case(r#"position*) position="$line" ;;"#)
+        .with_go(&format!(
+            r#"
+      sleep {}
+      case "$position" in
+        *moves*) echo "bestmove e7e5" ;;
+        *) echo "bestmove e2e4" ;;
+      esac"#,
+            delay.as_secs_f64()
+        ))
+        .build()
+}
+
+/// A scripted engine put together a piece at a time, for tests that need more than
+/// `mock_engine`. By default it answers `uci` with its name, `isready` with `readyok` and each
+/// `go` with the next of its moves, and logs every command it receives, see `commands_received`.
+pub struct MockEngine {
+    name: String,
+    moves: Vec<String>,
+    uci_lines: Vec<String>, // sent between `id name` and `uciok`
+    cases: Vec<String>,     // tried before `go`
+    go: String,
+    echo: bool, // send every command back before answering it
+}
+
+impl MockEngine {
+    pub fn new(name: &str) -> Self {
+        MockEngine {
+            name: name.to_string(),
+            moves: Vec::new(),
+            uci_lines: Vec::new(),
+            cases: Vec::new(),
+            go: r#"echo "bestmove $1"; shift"#.to_string(),
+            echo: false,
+        }
+    }
+
+    /// The moves `go` answers with in turn. The script keeps them as its arguments, so the next
+    /// one is `$1` to a `with_go` handler, which `shift`s past it.
+    pub fn with_moves(mut self, moves: &[&str]) -> Self {
+        self.moves = moves.iter().map(|mv| mv.to_string()).collect();
+        self
+    }
+
+    /// A line sent in answer to `uci` ahead of `uciok`, such as an `option`.
+    pub fn with_uci_line(mut self, line: &str) -> Self {
+        self.uci_lines.push(line.to_string());
+        self
+    }
+
+    /// A `pattern) commands ;;` line of the script's `case`, tried before `go`.
+    pub fn with_case(mut self, case: &str) -> Self {
+        self.cases.push(case.to_string());
+        self
+    }
+
+    /// The sh commands run on `go`, in place of answering with the next move.
+    pub fn with_go(mut self, go: &str) -> Self {
+        self.go = go.to_string();
+        self
+    }
+
+    /// Echo every command back before answering it, as some engines do.
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    pub fn build(self) -> Engine {
+        let uci = std::iter::once(format!("id name {}", self.name))
+            .chain(self.uci_lines)
+            .chain(std::iter::once("uciok".to_string()))
+            .map(|line| format!("echo \"{}\"", line))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let cases = self
+            .cases
+            .iter()
+            .map(|case| format!("    {}\n", case))
+            .collect::<String>();
+        mock_engine_script(
+            &self.name,
+            &format!(
+                r#"log="$(dirname "$0")/commands.log"
+set -- {}
+while read -r line; do
+  echo "$line" >> "$log"
+{}  case "$line" in
+    uci) {} ;;
+    isready) echo "readyok" ;;
+{}    go*) {} ;;
+    quit) exit 0 ;;
+  esac
+done
+"#,
+                self.moves
+                    .iter()
+                    .map(|mv| format!("\"{}\"", mv))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                if self.echo { "  echo \"$line\"\n" } else { "" },
+                uci,
+                cases,
+                self.go
+            ),
+        )
+    }
+}
+
+/// Every command the processes of an engine made by `mock_engine` received, in order.
+pub fn commands_received(engine: &Engine) -> Vec<String> {
+    let log = std::path::Path::new(&engine.path).with_file_name("commands.log");
+    fs::read_to_string(log)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
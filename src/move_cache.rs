@@ -0,0 +1,53 @@
+use queenfish::board::{Board, Move};
+
+/// Legal moves of the current position, generated on first use and kept until `invalidate` is
+/// called. Owners must invalidate after every change to the board (`make_move`, `unmake_move`,
+/// `load_from_fen`, ..), since the cache can't see them.
+#[derive(Debug, Clone, Default)]
+pub struct MoveCache {
+    moves: Option<Vec<Move>>,
+    generated: u64, // number of times the move generator actually ran
+}
+
+impl MoveCache {
+    pub fn moves(&mut self, board: &Board) -> &[Move] {
+        if self.moves.is_none() {
+            self.generated += 1;
+        }
+        self.moves
+            .get_or_insert_with(|| board.generate_moves().into_iter().collect())
+    } //
+
+    pub fn invalidate(&mut self) {
+        self.moves = None;
+    } //
+
+    pub fn generated(&self) -> u64 {
+        self.generated
+    } //
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    #[test]
+    fn moves_are_generated_once_per_position() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let mut board = Board::new();
+        let mut move_cache = MoveCache::default();
+        for _ in 0..64 {
+            assert_eq!(move_cache.moves(&board).len(), 20);
+        }
+        assert_eq!(move_cache.generated(), 1);
+
+        board.make_move(Move::from_uci("e2e4", &board));
+        move_cache.invalidate();
+        assert_eq!(move_cache.moves(&board).len(), 20);
+        assert_eq!(move_cache.generated(), 2);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::OpeningBookError;
+use crate::fen::validate_fen;
+use crate::game::Opening;
+use std::fs;
+use std::path::Path;
+
+/// Start positions read from an EPD or FEN file, one per line, for decorrelating a match: see
+/// `Tournament::with_opening_book`.
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    openings: Vec<Opening>,
+}
+
+impl OpeningBook {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OpeningBookError> {
+        let text =
+            fs::read_to_string(path).map_err(|error| OpeningBookError::Io(error.to_string()))?;
+        OpeningBook::parse(&text)
+    } //
+
+    /// Blank lines and `#` comments are skipped. EPD operations (`bm`, `id`, ...) after the four
+    /// position fields are ignored, and positions without move counters start at `0 1`. Every
+    /// position is checked with `validate_fen`.
+    pub fn parse(text: &str) -> Result<Self, OpeningBookError> {
+        let mut openings = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fen = position_fields(line);
+            validate_fen(&fen).map_err(|error| OpeningBookError::InvalidPosition {
+                line: index + 1,
+                error,
+            })?;
+            openings.push(Opening {
+                fen: Some(fen),
+                moves: Vec::new(),
+            });
+        }
+        Ok(OpeningBook { openings })
+    } //
+
+    pub fn openings(&self) -> &[Opening] {
+        &self.openings
+    } //
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    } //
+
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    } //
+}
+
+// The FEN of an EPD or FEN line: its four position fields and its move counters, `0 1` when the
+// line has none.
+fn position_fields(line: &str) -> String {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let position = fields[..fields.len().min(4)].join(" ");
+    match fields.get(4..6) {
+        Some(counters) if counters.iter().all(|field| field.parse::<u32>().is_ok()) => {
+            format!("{} {}", position, counters.join(" "))
+        }
+        _ => format!("{} 0 1", position),
+    }
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::FenError;
+
+    #[test]
+    fn epd_and_fen_lines_become_positions() {
+        let book = OpeningBook::parse(
+            "# two openings\n\
+             rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - bm e5; id \"king pawn\";\n\
+             \n\
+             4k3/8/8/8/8/8/8/R3K3 w Q - 3 40\n",
+        )
+        .unwrap();
+        let fens = book
+            .openings()
+            .iter()
+            .map(|opening| opening.fen.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fens,
+            [
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+                "4k3/8/8/8/8/8/8/R3K3 w Q - 3 40"
+            ]
+        );
+        assert!(
+            book.openings()
+                .iter()
+                .all(|opening| opening.moves.is_empty())
+        );
+
+        assert_eq!(
+            OpeningBook::parse("4k3/8/8/8/8/8/8/4K3 w - -\n8/8/8/8/8/8/8/4K3 w - -").unwrap_err(),
+            OpeningBookError::InvalidPosition {
+                line: 2,
+                error: FenError::MissingKing { white: false },
+            }
+        );
+    }
+}
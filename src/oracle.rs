@@ -0,0 +1,212 @@
+use crate::engine::{Engine, Score};
+use crate::game::TimeControl;
+use queenfish::board::{Board, Move, Turn};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Scores are capped here before losses are worked out, mates included, so one blunder into a
+// lost position doesn't swamp a game's average.
+const EVAL_CAP: i32 = 1000;
+
+/// How a searched move compares with what the oracle would have played in its place.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveAnnotation {
+    pub white: bool, // whether white made the move
+    pub played: String,
+    pub best_move: String,    // the oracle's choice
+    pub score: Option<Score>, // the oracle's score before the move, for the side to move
+    // centipawns given up against the oracle's best line; 0 for its own move, None where the
+    // oracle had no score on either side of the move
+    pub centipawn_loss: Option<u32>,
+}
+
+impl MoveAnnotation {
+    pub fn diverged(&self) -> bool {
+        self.played != self.best_move
+    } //
+}
+
+/// What the oracle of `Game::with_oracle` made of a finished game, move by move.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OracleReport {
+    pub oracle: String,
+    pub moves: Vec<Option<MoveAnnotation>>, // one per move of the game, None for book moves
+}
+
+impl OracleReport {
+    /// Mean centipawn loss of one side's annotated moves, None if it has none.
+    pub fn average_loss(&self, white: bool) -> Option<f64> {
+        let losses = self
+            .annotations(white)
+            .filter_map(|annotation| annotation.centipawn_loss)
+            .collect::<Vec<_>>();
+        if losses.is_empty() {
+            return None;
+        }
+        Some(losses.iter().map(|&loss| loss as f64).sum::<f64>() / losses.len() as f64)
+    } //
+
+    /// How many of one side's moves differ from the oracle's choice.
+    pub fn divergences(&self, white: bool) -> usize {
+        self.annotations(white)
+            .filter(|annotation| annotation.diverged())
+            .count()
+    } //
+
+    fn annotations(&self, white: bool) -> impl Iterator<Item = &MoveAnnotation> {
+        self.moves
+            .iter()
+            .flatten()
+            .filter(move |annotation| annotation.white == white)
+    } //
+}
+
+// Has `oracle` search every position from the first searched move on, the final one included, on
+// a single process, and sets each searched move's score before against the score after. A
+// position without legal moves needs no search: it is mate or stalemate.
+pub(crate) fn consult_oracle(
+    oracle: &Engine,
+    time_control: TimeControl,
+    start_fen: Option<&str>,
+    moves: &[String],
+    book_ply_count: usize,
+) -> OracleReport {
+    let mut board = Board::new();
+    if let Some(fen) = start_fen {
+        board.load_from_fen(fen);
+    }
+    // (fen, whether white is to move, score if already known) of every position moves reach
+    let mut positions = Vec::with_capacity(moves.len() + 1);
+    for ply in 0..=moves.len() {
+        let white = matches!(board.turn, Turn::WHITE);
+        let known = board.generate_moves().is_empty().then(|| {
+            if board.is_king_in_check(board.turn) {
+                Score::Mate(0)
+            } else {
+                Score::Cp(0)
+            }
+        });
+        positions.push((board.to_fen(), white, known));
+        if let Some(uci) = moves.get(ply) {
+            let mv = Move::from_uci(uci, &board);
+            board.make_move(mv);
+        }
+    }
+
+    let searched = (book_ply_count..positions.len())
+        .filter(|&ply| positions[ply].2.is_none())
+        .collect::<Vec<_>>();
+    let fens = searched
+        .iter()
+        .map(|&ply| positions[ply].0.clone())
+        .collect::<Vec<_>>();
+    let mut analysis = vec![None; positions.len()];
+    for (ply, result) in searched
+        .into_iter()
+        .zip(oracle.analyze_batch(&fens, time_control, 1))
+    {
+        match result {
+            Ok(result) => analysis[ply] = Some(result),
+            Err(error) => log::warn!("{} failed to analyze ply {}: {}", oracle.name, ply, error),
+        }
+    }
+
+    let score_at = |ply: usize| {
+        positions[ply]
+            .2
+            .or_else(|| analysis[ply].as_ref().and_then(|result| result.score))
+    };
+    let moves = moves
+        .iter()
+        .enumerate()
+        .map(|(ply, played)| {
+            let best_move = analysis.get(ply)?.as_ref()?.best_move.clone();
+            let score = score_at(ply);
+            // the score after the move is the opponent's, so the two add up to what was lost
+            let centipawn_loss = if *played == best_move {
+                Some(0)
+            } else {
+                score
+                    .zip(score_at(ply + 1))
+                    .map(|(before, after)| (capped(before) + capped(after)).max(0) as u32)
+            };
+            Some(MoveAnnotation {
+                white: positions[ply].1,
+                played: played.clone(),
+                best_move,
+                score,
+                centipawn_loss,
+            })
+        })
+        .collect();
+    OracleReport {
+        oracle: oracle.name.clone(),
+        moves,
+    }
+} //
+
+fn capped(score: Score) -> i32 {
+    match score {
+        Score::Cp(cp) => cp.clamp(-EVAL_CAP, EVAL_CAP),
+        Score::Mate(moves) if moves > 0 => EVAL_CAP,
+        Score::Mate(_) => -EVAL_CAP,
+    }
+} //
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::game::{Game, Termination};
+    use crate::mock_engine::{MockEngine, mock_engine};
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    #[test]
+    fn oracle_scores_every_searched_move() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        // searches the positions of the fool's mate in order; the mated one is never sent
+        let oracle = MockEngine::new("Oracle")
+            .with_go(
+                r#"
+      n=$((n+1))
+      case $n in
+        1) echo "info depth 8 score cp 30 pv e2e4"; echo "bestmove e2e4" ;;
+        2) echo "info depth 8 score cp 25 pv e7e5"; echo "bestmove e7e5" ;;
+        3) echo "info depth 8 score cp 40 pv b1c3"; echo "bestmove b1c3" ;;
+        *) echo "info depth 8 score mate 1 pv d8h4"; echo "bestmove d8h4" ;;
+      esac"#,
+            )
+            .build();
+        let result = Game::new(
+            mock_engine("White", &["f2f3", "g2g4"]),
+            mock_engine("Black", &["e7e5", "d8h4"]),
+            TimeControl::TimePerMove(10),
+        )
+        .with_oracle(oracle, TimeControl::Depth(8))
+        .play();
+        assert_eq!(result.reason(), Termination::Checkmate);
+
+        let report = result.oracle_report().unwrap();
+        assert_eq!(report.oracle, "Oracle");
+        let losses = report
+            .moves
+            .iter()
+            .map(|annotation| annotation.as_ref().unwrap().centipawn_loss)
+            .collect::<Vec<_>>();
+        // f3 loses 30 + 25; g4 allows mate, which counts as the cap
+        assert_eq!(losses, [Some(55), Some(0), Some(1040), Some(0)]);
+        assert_eq!(report.divergences(true), 2);
+        assert_eq!(report.divergences(false), 0);
+        assert_eq!(report.average_loss(true), Some(547.5));
+        assert_eq!(report.average_loss(false), Some(0.0));
+        assert_eq!(
+            report.moves[3].as_ref().unwrap().score,
+            Some(Score::Mate(1))
+        );
+    }
+}
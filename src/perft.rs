@@ -0,0 +1,75 @@
+use queenfish::board::Board;
+
+/// Number of leaf nodes of the legal move tree `depth` plies deep. Everything in the arena that
+/// decides a game (mate, stalemate, illegal moves) trusts `generate_moves`, and perft is how that
+/// trust is checked.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.generate_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .into_iter()
+        .map(|mv| {
+            let mut child = board.clone();
+            child.make_move(mv);
+            perft(&child, depth - 1)
+        })
+        .sum()
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    fn perft_fen(fen: &str, depth: u32) -> u64 {
+        init_bishop_magics();
+        init_rook_magics();
+        let mut board = Board::new();
+        board.load_from_fen(fen);
+        perft(&board, depth)
+    }
+
+    // reference counts from https://www.chessprogramming.org/Perft_Results
+    #[test]
+    fn startpos() {
+        init_bishop_magics();
+        init_rook_magics();
+        let board = Board::new();
+        assert_eq!(
+            (1..=4)
+                .map(|depth| perft(&board, depth))
+                .collect::<Vec<_>>(),
+            [20, 400, 8902, 197_281]
+        );
+    }
+
+    #[test]
+    fn kiwipete() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft_fen(fen, 1), 48);
+        assert_eq!(perft_fen(fen, 2), 2039);
+        assert_eq!(perft_fen(fen, 3), 97_862);
+    }
+
+    #[test]
+    fn en_passant_and_pins() {
+        assert_eq!(
+            perft_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4),
+            43_238
+        );
+    }
+
+    #[test]
+    fn promotions_and_castling() {
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_eq!(perft_fen(fen, 3), 9467);
+        let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+        assert_eq!(perft_fen(fen, 3), 62_379);
+    }
+}
@@ -0,0 +1,458 @@
+use crate::error::PgnError;
+use crate::fen::validate_fen;
+use crate::game::{GameResult, Outcome};
+use crate::san::{moves_to_san, san_to_move};
+use queenfish::board::Board;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PgnTags {
+    pub event: String,
+    pub site: String,
+    pub date: String, // YYYY.MM.DD, unknown parts as ??
+    pub round: String,
+    pub extra: Vec<(String, String)>, // emitted after the seven tag roster, in order
+}
+
+impl Default for PgnTags {
+    fn default() -> Self {
+        PgnTags {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            round: "?".to_string(),
+            extra: Vec::new(),
+        }
+    }
+}
+
+impl PgnTags {
+    pub fn with_event(mut self, event: &str) -> Self {
+        self.event = event.to_string();
+        self
+    } //
+    pub fn with_site(mut self, site: &str) -> Self {
+        self.site = site.to_string();
+        self
+    } //
+    pub fn with_date(mut self, date: &str) -> Self {
+        self.date = date.to_string();
+        self
+    } //
+    pub fn with_round(mut self, round: &str) -> Self {
+        self.round = round.to_string();
+        self
+    } //
+    pub fn with_tag(mut self, name: &str, value: &str) -> Self {
+        self.extra.push((name.to_string(), value.to_string()));
+        self
+    } //
+}
+
+pub fn result_token(result: i32) -> &'static str {
+    Outcome::from(result).pgn_token()
+} //
+
+// Inside a PGN string token only `\` and `"` need escaping, both with a backslash.
+pub fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+} //
+
+/// Today's date (UTC) in PGN `YYYY.MM.DD` form.
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+} //
+
+fn tag_line(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, escape_tag_value(value))
+} //
+
+// Numbered SAN tokens for the uci `moves` played from `start_fen`. `comments` holds an optional
+// comment per move and may be shorter than `moves`; a black move after a comment gets its number
+// repeated, as the export format asks.
+fn movetext_tokens(
+    start_fen: Option<&str>,
+    moves: &[String],
+    comments: &[Option<String>],
+) -> Vec<String> {
+    let mut board = Board::new();
+    // plies are counted from white's move of `first_move`, so a black start is one ply in
+    let (mut first_move, mut first_ply) = (1, 0);
+    if let Some(fen) = start_fen {
+        board.load_from_fen(fen);
+        let fields = fen.split_whitespace().collect::<Vec<_>>();
+        first_move = fields.get(5).and_then(|n| n.parse().ok()).unwrap_or(1);
+        first_ply = usize::from(fields.get(1) == Some(&"b"));
+    }
+
+    let mut tokens = Vec::new();
+    let mut after_comment = false;
+    for (index, mv) in moves_to_san(&board, moves).into_iter().enumerate() {
+        let ply = index + first_ply;
+        if ply % 2 == 0 {
+            tokens.push(format!("{}.", first_move + ply / 2));
+        } else if ply == first_ply || after_comment {
+            tokens.push(format!("{}...", first_move + ply / 2));
+        }
+        tokens.push(mv);
+        after_comment = false;
+        if let Some(Some(comment)) = comments.get(index) {
+            let comment = format!("{{ {} }}", comment);
+            tokens.extend(comment.split(' ').map(str::to_string));
+            after_comment = true;
+        }
+    }
+    tokens
+} //
+
+// A move's search time as an `[%emt]` command, h:mm:ss with milliseconds.
+fn elapsed_move_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "[%emt {}:{:02}:{:02}.{:03}]",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+} //
+
+// Joins movetext tokens into lines no longer than the PGN export limit.
+fn wrap(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(token);
+        line_length += token.len();
+    }
+    text
+} //
+
+impl GameResult {
+    /// Exports the game as PGN, with the moves in SAN. Every searched move carries its search
+    /// time as an `[%emt]` command, and after an opening or book line the first one is also
+    /// marked `out of book`.
+    pub fn to_pgn(&self, tags: &PgnTags) -> String {
+        let result = self.outcome().pgn_token();
+        let mut pgn = String::new();
+        // games from a custom position carry it, so readers can replay the moves
+        let setup = self
+            .start_fen()
+            .map(|fen| [("SetUp", "1"), ("FEN", fen)])
+            .into_iter()
+            .flatten();
+        for (name, value) in [
+            ("Event", tags.event.as_str()),
+            ("Site", tags.site.as_str()),
+            ("Date", tags.date.as_str()),
+            ("Round", tags.round.as_str()),
+            ("White", self.white()),
+            ("Black", self.black()),
+            ("Result", result),
+        ]
+        .into_iter()
+        .chain(setup)
+        .chain(
+            tags.extra
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        ) {
+            pgn.push_str(&tag_line(name, value));
+        }
+        pgn.push('\n');
+
+        // theory ends where the engines' own moves begin
+        let comments = self
+            .move_stats()
+            .iter()
+            .enumerate()
+            .map(|(ply, stats)| {
+                let out_of_book =
+                    (ply > 0 && ply == self.book_ply_count()).then_some("out of book");
+                let time = stats.map(|stats| elapsed_move_time(stats.time));
+                match (out_of_book, time) {
+                    (Some(note), Some(time)) => Some(format!("{} {}", note, time)),
+                    (note, time) => time.or(note.map(str::to_string)),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut tokens = movetext_tokens(self.start_fen(), self.moves_list(), &comments);
+        let comment = format!("{{Final position: {}}}", self.final_fen());
+        tokens.extend(comment.split(' ').map(str::to_string));
+        tokens.push(result.to_string());
+        pgn.push_str(&wrap(&tokens));
+        pgn.push('\n');
+        pgn
+    } //
+}
+
+/// A game read from a PGN file.
+#[derive(Debug, Clone, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>, // in file order
+    pub moves: Vec<String>,          // uci, mainline only
+    pub result: String,              // the game termination marker, `*` if there was none
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    } //
+    /// The position the moves start from, None for the standard start position.
+    pub fn start_fen(&self) -> Option<&str> {
+        self.tag("FEN")
+    } //
+    /// None for an unfinished game (`*`).
+    pub fn outcome(&self) -> Option<Outcome> {
+        match self.result.as_str() {
+            "1-0" => Some(Outcome::WhiteWins),
+            "0-1" => Some(Outcome::BlackWins),
+            "1/2-1/2" => Some(Outcome::Draw),
+            _ => None,
+        }
+    } //
+
+    /// Writes the game back out as PGN, with `comments[i]` after the i-th move.
+    pub fn to_pgn_with_comments(&self, comments: &[Option<String>]) -> String {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            pgn.push_str(&tag_line(name, value));
+        }
+        pgn.push('\n');
+        let mut tokens = movetext_tokens(self.start_fen(), &self.moves, comments);
+        tokens.push(self.result.clone());
+        pgn.push_str(&wrap(&tokens));
+        pgn.push('\n');
+        pgn
+    } //
+}
+
+/// Reads every game of a PGN file. Comments, NAGs and variations are skipped and the mainline's
+/// SAN is replayed into uci moves.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut sans = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '[' => {
+                // a tag section after movetext starts a new game; the last one had no result
+                if !sans.is_empty() {
+                    let (tags, sans) = (std::mem::take(&mut tags), std::mem::take(&mut sans));
+                    games.push(finish_game(tags, sans, "*")?);
+                }
+                chars.next();
+                tags.push(read_tag(&mut chars)?);
+            }
+            '{' => skip_past(&mut chars, '}'),
+            ';' => skip_past(&mut chars, '\n'),
+            '(' => {
+                let mut depth = 0;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        '{' => skip_past(&mut chars, '}'),
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"[]{};()".contains(*c))
+                {
+                    token.push(c);
+                }
+                if token.is_empty() {
+                    return Err(PgnError::Malformed(format!("unexpected '{}'", c)));
+                }
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+                    let (tags, sans) = (std::mem::take(&mut tags), std::mem::take(&mut sans));
+                    games.push(finish_game(tags, sans, &token)?);
+                } else if !token.starts_with('$') {
+                    // move numbers may be glued to the move, as in `12.e4` or `12...e5`; only
+                    // digits ending in a `.` are one, so `0-0` castling is left alone
+                    let unnumbered = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                    let san = match unnumbered.strip_prefix('.') {
+                        Some(san) => san.trim_start_matches('.'),
+                        None => token.as_str(),
+                    };
+                    if !san.is_empty() {
+                        sans.push(san.to_string());
+                    }
+                }
+            }
+        }
+    }
+    if !tags.is_empty() || !sans.is_empty() {
+        games.push(finish_game(tags, sans, "*")?);
+    }
+    Ok(games)
+} //
+
+fn skip_past(chars: &mut Peekable<Chars>, end: char) {
+    for c in chars.by_ref() {
+        if c == end {
+            break;
+        }
+    }
+} //
+
+// Reads `Name "Value"]`, the opening bracket already consumed.
+fn read_tag(chars: &mut Peekable<Chars>) -> Result<(String, String), PgnError> {
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"' && *c != ']') {
+        name.push(c);
+    }
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if name.is_empty() || chars.next() != Some('"') {
+        return Err(PgnError::Malformed(format!("bad tag '{}'", name)));
+    }
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => value.extend(chars.next()),
+            Some('"') => break,
+            Some(c) => value.push(c),
+            None => return Err(PgnError::Malformed(format!("unterminated tag '{}'", name))),
+        }
+    }
+    skip_past(chars, ']');
+    Ok((name, value))
+} //
+
+fn finish_game(
+    tags: Vec<(String, String)>,
+    sans: Vec<String>,
+    result: &str,
+) -> Result<PgnGame, PgnError> {
+    let mut game = PgnGame {
+        tags,
+        moves: Vec::new(),
+        result: result.to_string(),
+    };
+    let mut board = Board::new();
+    if let Some(fen) = game.start_fen() {
+        validate_fen(fen).map_err(PgnError::InvalidFen)?;
+        board.load_from_fen(fen);
+    }
+    for (ply, san) in sans.into_iter().enumerate() {
+        let Some(mv) = san_to_move(&board, &san) else {
+            return Err(PgnError::IllegalMove { ply: ply + 1, san });
+        };
+        game.moves.push(mv.to_uci());
+        board.make_move(mv);
+    }
+    Ok(game)
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    #[test]
+    fn tag_values_are_escaped() {
+        assert_eq!(
+            escape_tag_value(r#"My "Big" Match \ 2"#),
+            r#"My \"Big\" Match \\ 2"#
+        );
+    }
+
+    #[test]
+    fn move_times_are_hours_minutes_and_seconds() {
+        assert_eq!(
+            elapsed_move_time(Duration::from_millis(250)),
+            "[%emt 0:00:00.250]"
+        );
+        assert_eq!(
+            elapsed_move_time(Duration::from_millis(3_723_045)),
+            "[%emt 1:02:03.045]"
+        );
+    }
+
+    #[test]
+    fn movetext_is_wrapped() {
+        let tokens = (0..40).map(|_| "e2e4".to_string()).collect::<Vec<_>>();
+        let text = wrap(&tokens);
+        assert!(text.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(text.split_whitespace().count(), 40);
+    }
+
+    #[test]
+    fn games_are_read_back() {
+        init_bishop_magics();
+        init_rook_magics();
+        let text = r#"[Event "Test \"A\""]
+[White "x"]
+
+1. e4 {best by test} e5 2.Nf3 (2. f4 exf4 (2... d5)) 2... Nc6 $1 ; done
+3. Bb5 1-0
+
+[FEN "4k3/8/8/8/8/8/8/4K2R w K - 0 1"]
+1. O-O+ *
+"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some(r#"Test "A""#));
+        assert_eq!(games[0].moves, ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]);
+        assert_eq!(games[0].result, "1-0");
+        assert_eq!(games[1].moves, ["e1g1"]);
+
+        let games = parse_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 *").unwrap();
+        assert_eq!(
+            games[0].moves,
+            ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "f8c5", "e1g1"]
+        );
+
+        assert_eq!(
+            parse_pgn("1. e4 e4 *").unwrap_err(),
+            PgnError::IllegalMove {
+                ply: 2,
+                san: "e4".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::engine::{Engine, EngineHandle, Score};
+use crate::error::PgnError;
+use crate::game::{DEFAULT_SEARCH_TIMEOUT, TimeControl, wait_for_bestmove};
+use crate::pgn::{PgnGame, parse_pgn};
+use queenfish::board::{Board, Move, Turn};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Runs an engine over every position of a set of PGN games and annotates each move with the
+/// evaluation of the position it leads to, as `{ [%eval 0.35] }`.
+pub struct PgnAnalyzer {
+    engine: Engine,
+    time_control: TimeControl, // per position; Depth or TimePerMove make the most sense
+    search_timeout: Duration,
+}
+
+impl PgnAnalyzer {
+    pub fn new(engine: Engine, time_control: TimeControl) -> Self {
+        PgnAnalyzer {
+            engine,
+            time_control,
+            search_timeout: DEFAULT_SEARCH_TIMEOUT,
+        }
+    } //
+    /// How long a single search may run before the engine is told to stop. `Infinite` searches
+    /// always run this long.
+    pub fn with_search_timeout(mut self, search_timeout: Duration) -> Self {
+        self.search_timeout = search_timeout;
+        self
+    } //
+
+    pub fn analyze_file(&self, path: impl AsRef<Path>) -> Result<String, PgnError> {
+        let text = fs::read_to_string(path).map_err(|error| PgnError::Io(error.to_string()))?;
+        self.analyze(&text)
+    } //
+
+    /// The games of `pgn`, written back out with an eval comment after every move.
+    pub fn analyze(&self, pgn: &str) -> Result<String, PgnError> {
+        let games = parse_pgn(pgn)?;
+        Ok(games
+            .iter()
+            .map(|game| {
+                let comments = self
+                    .evaluate(game)?
+                    .into_iter()
+                    .map(|score| score.map(|score| format!("[%eval {}]", eval_text(score))))
+                    .collect::<Vec<_>>();
+                Ok(game.to_pgn_with_comments(&comments))
+            })
+            .collect::<Result<Vec<_>, PgnError>>()?
+            .join("\n"))
+    } //
+
+    /// The score, from white's point of view, of the position after each move of `game`. None
+    /// where the engine gave no score or the game is over. `PgnError::Engine` if the engine
+    /// can't be started, doesn't speak UCI or rejects its options.
+    pub fn evaluate(&self, game: &PgnGame) -> Result<Vec<Option<Score>>, PgnError> {
+        let mut process = self.engine.start_analysis().map_err(PgnError::Engine)?;
+        process.send_command("ucinewgame\n");
+
+        let position = match game.start_fen() {
+            Some(fen) => format!("position fen {}", fen),
+            None => "position startpos".to_string(),
+        };
+        let mut board = Board::new();
+        if let Some(fen) = game.start_fen() {
+            board.load_from_fen(fen);
+        }
+
+        let mut scores = Vec::new();
+        for (ply, uci) in game.moves.iter().enumerate() {
+            board.make_move(Move::from_uci(uci, &board));
+            // a finished game has nothing left to search
+            if board.generate_moves().is_empty() {
+                scores.push(None);
+                continue;
+            }
+            process.send_command(&format!(
+                "{} moves {}\n",
+                position,
+                game.moves[..=ply].join(" ")
+            ));
+            scores.push(self.search(&process, &board));
+        }
+
+        process.send_command("quit\n");
+        process.disconnect();
+        Ok(scores)
+    } //
+
+    fn search(&self, process: &EngineHandle, board: &Board) -> Option<Score> {
+        // every position is searched on a fresh clock
+        let clocks = match self.time_control.clock() {
+            Some((base_ms, _)) => [base_ms as i64; 2],
+            None => [0; 2],
+        };
+        process.send_command(&format!(
+            "go {}\n",
+            self.time_control.search_limits(clocks, 0)
+        ));
+        let score = wait_for_bestmove(process, Some(self.search_timeout))?.score?;
+        // engines score from the side to move
+        Some(match (board.turn, score) {
+            (Turn::WHITE, score) => score,
+            (Turn::BLACK, Score::Cp(cp)) => Score::Cp(-cp),
+            (Turn::BLACK, Score::Mate(moves)) => Score::Mate(-moves),
+        })
+    } //
+}
+
+// `[%eval]` takes pawns, or `#n` for a mate in n (negative when black mates).
+fn eval_text(score: Score) -> String {
+    match score {
+        Score::Cp(cp) => format!("{:.2}", cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{}", moves),
+    }
+} //
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::mock_engine::MockEngine;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    #[test]
+    fn every_move_gets_an_eval() {
+        init_bishop_magics();
+        init_rook_magics();
+        // always reports +0.35 for the side to move
+        let engine = MockEngine::new("Scorer")
+            .with_go(r#"echo "info depth 5 score cp 35 pv e2e4"; echo "bestmove e2e4""#)
+            .build();
+        let analyzer = PgnAnalyzer::new(engine, TimeControl::Depth(5));
+        let pgn = analyzer
+            .analyze("[White \"a\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n")
+            .unwrap();
+        let (tags, movetext) = pgn.split_once("\n\n").unwrap();
+        assert_eq!(tags, "[White \"a\"]");
+        assert_eq!(
+            movetext.split_whitespace().collect::<Vec<_>>().join(" "),
+            "1. f3 { [%eval -0.35] } 1... e5 { [%eval 0.35] } 2. g4 { [%eval -0.35] } 2... Qh4# 0-1"
+        );
+    }
+
+    #[test]
+    fn engine_that_fails_to_start_is_an_error() {
+        init_bishop_magics();
+        init_rook_magics();
+        let engine = MockEngine::new("Gone").build();
+        fs::remove_file(&engine.path).unwrap();
+        let analyzer = PgnAnalyzer::new(engine, TimeControl::Depth(5));
+        assert!(matches!(
+            analyzer.analyze("1. e4 e5 *"),
+            Err(PgnError::Engine(_))
+        ));
+    }
+
+    #[test]
+    fn scores_are_formatted_in_pawns_or_mates() {
+        assert_eq!(eval_text(Score::Cp(-7)), "-0.07");
+        assert_eq!(eval_text(Score::Cp(150)), "1.50");
+        assert_eq!(eval_text(Score::Mate(-3)), "#-3");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::error::PgnError;
+use crate::pgn::{PgnGame, parse_pgn};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Copies the games of a PGN file that pass a predicate, e.g. only the decisive ones or those
+/// lost on time, to another. The input is read one game at a time, so files of any size work,
+/// and kept games are written exactly as they were, comments and all.
+///
+/// A game runs from its tag section to the next one. One that doesn't parse (an illegal move,
+/// say) is logged and left out, so a single bad game doesn't cost the rest of the file.
+pub struct PgnFilter<F: FnMut(&PgnGame) -> bool> {
+    predicate: F,
+}
+
+impl<F: FnMut(&PgnGame) -> bool> PgnFilter<F> {
+    pub fn new(predicate: F) -> Self {
+        PgnFilter { predicate }
+    } //
+
+    pub fn filter_file(
+        &mut self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> Result<usize, PgnError> {
+        let input = File::open(input).map_err(|error| PgnError::Io(error.to_string()))?;
+        let output = File::create(output).map_err(|error| PgnError::Io(error.to_string()))?;
+        self.filter(BufReader::new(input), BufWriter::new(output))
+    } //
+
+    /// Returns the number of games kept.
+    pub fn filter(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+    ) -> Result<usize, PgnError> {
+        let mut kept = 0;
+        let mut read = 0; // games read so far, for the log
+        let mut game = String::new();
+        let mut has_movetext = false;
+        let mut in_comment = false;
+        for line in input.lines() {
+            let line = line.map_err(|error| PgnError::Io(error.to_string()))?;
+            // a comment may run over lines, and one of them may well start with `[%clk ..]`
+            let is_tag = !in_comment && line.trim_start().starts_with('[');
+            if is_tag && has_movetext {
+                read += 1;
+                kept += self.keep(read, &game, &mut output)?;
+                game.clear();
+                has_movetext = false;
+            }
+            if !is_tag && !line.trim().is_empty() {
+                has_movetext = true;
+                in_comment = comment_open_after(&line, in_comment);
+            }
+            game.push_str(&line);
+            game.push('\n');
+        }
+        if !game.trim().is_empty() {
+            kept += self.keep(read + 1, &game, &mut output)?;
+        }
+        output
+            .flush()
+            .map_err(|error| PgnError::Io(error.to_string()))?;
+        Ok(kept)
+    } //
+
+    // Writes the `number`th game of the input to `output` if it passes, returning 1 if it did.
+    fn keep(
+        &mut self,
+        number: usize,
+        text: &str,
+        output: &mut impl Write,
+    ) -> Result<usize, PgnError> {
+        let games = match parse_pgn(text) {
+            Ok(games) => games,
+            Err(error) => {
+                log::warn!("Skipping game {}: {}", number, error);
+                return Ok(0);
+            }
+        };
+        if !games.iter().any(&mut self.predicate) {
+            return Ok(0);
+        }
+        writeln!(output, "{}\n", text.trim_end())
+            .map_err(|error| PgnError::Io(error.to_string()))?;
+        Ok(1)
+    } //
+}
+
+// Whether a `{ }` comment is still open at the end of `line`, given whether one was at its start.
+fn comment_open_after(line: &str, mut open: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '{' if !open => open = true,
+            '}' if open => open = false,
+            // the rest of the line is a comment of its own
+            ';' if !open => break,
+            _ => {}
+        }
+    }
+    open
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Outcome;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    const GAMES: &str = r#"[Event "Test"]
+[Round "1"]
+[Result "0-1"]
+[Termination "normal"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+
+[Event "Test"]
+[Round "2"]
+[Result "1/2-1/2"]
+
+1. Nf3 { a comment over lines,
+[%clk 0:00:10] still the comment } Nf6 1/2-1/2
+[Event "Test"]
+[Round "3"]
+[Result "1-0"]
+[Termination "time forfeit"]
+
+1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+"#;
+
+    #[test]
+    fn decisive_games_are_kept_as_written() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let mut output = Vec::new();
+        let kept =
+            PgnFilter::new(|game: &PgnGame| game.outcome().is_some_and(Outcome::is_decisive))
+                .filter(GAMES.as_bytes(), &mut output)
+                .unwrap();
+        assert_eq!(kept, 2);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("[Event \"Test\"]\n[Round \"1\"]"));
+        assert!(output.contains("\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n[Event \"Test\"]\n[Round \"3\"]"));
+        let games = parse_pgn(&output).unwrap();
+        assert_eq!(
+            games
+                .iter()
+                .map(|game| game.tag("Round"))
+                .collect::<Vec<_>>(),
+            [Some("1"), Some("3")]
+        );
+
+        let mut output = Vec::new();
+        let kept = PgnFilter::new(|game: &PgnGame| game.tag("Termination") == Some("time forfeit"))
+            .filter(GAMES.as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(kept, 1);
+        assert_eq!(
+            parse_pgn(&String::from_utf8(output).unwrap()).unwrap()[0].result,
+            "1-0"
+        );
+    }
+
+    #[test]
+    fn unparseable_games_are_skipped() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let illegal = r#"[Event "Test"]
+[Round "0"]
+[Result "1-0"]
+
+1. e4 e5 2. Ke3 Ke6 3. Kxe5 1-0
+"#;
+        let input = format!("{}\n{}", illegal, GAMES);
+        let mut output = Vec::new();
+        let kept =
+            PgnFilter::new(|game: &PgnGame| game.outcome().is_some_and(Outcome::is_decisive))
+                .filter(input.as_bytes(), &mut output)
+                .unwrap();
+        assert_eq!(kept, 2);
+        let games = parse_pgn(&String::from_utf8(output).unwrap()).unwrap();
+        assert_eq!(
+            games
+                .iter()
+                .map(|game| game.tag("Round"))
+                .collect::<Vec<_>>(),
+            [Some("1"), Some("3")]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::engine::Engine;
+use crate::game::{DEFAULT_MAX_PLIES, Game, GameResult, Outcome, Termination, TimeControl};
+
+/// Plays every engine against every other, `rounds` games per pairing with the colors
+/// alternating, so an even number of rounds gives each engine every opponent equally often with
+/// each color. Cycles go through all pairings before any pairing plays again.
+pub struct RoundRobin {
+    engines: Vec<Engine>,
+    rounds: i32,
+    time_control: TimeControl,
+    max_plies: Option<usize>,
+}
+
+/// One engine's games against another, from the first engine's side.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PairingScore {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+}
+
+impl PairingScore {
+    pub fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    } //
+    /// A win counting 1 and a draw half.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    } //
+}
+
+#[derive(Debug, Clone)]
+pub struct RoundRobinResult {
+    pub engines: Vec<String>,
+    pub games: Vec<(usize, usize, GameResult)>, // white's and black's index in `engines`
+}
+
+impl RoundRobinResult {
+    /// `crosstable()[a][b]` is engine `a`'s score against engine `b`; an engine's own entry is
+    /// empty.
+    pub fn crosstable(&self) -> Vec<Vec<PairingScore>> {
+        let mut table = vec![vec![PairingScore::default(); self.engines.len()]; self.engines.len()];
+        for (white, black, game_result) in &self.games {
+            let (white, black) = (*white, *black);
+            match game_result.outcome() {
+                Outcome::WhiteWins => {
+                    table[white][black].wins += 1;
+                    table[black][white].losses += 1;
+                }
+                Outcome::BlackWins => {
+                    table[black][white].wins += 1;
+                    table[white][black].losses += 1;
+                }
+                Outcome::Draw => {
+                    table[white][black].draws += 1;
+                    table[black][white].draws += 1;
+                }
+            }
+        }
+        table
+    } //
+
+    /// Each engine's points over all its games, best first; ties keep the engines' order.
+    pub fn standings(&self) -> Vec<(String, f64)> {
+        let mut standings = self
+            .engines
+            .iter()
+            .zip(self.crosstable())
+            .map(|(engine, row)| (engine.clone(), row.iter().map(PairingScore::points).sum()))
+            .collect::<Vec<(String, f64)>>();
+        standings.sort_by(|a, b| b.1.total_cmp(&a.1));
+        standings
+    } //
+}
+
+impl RoundRobin {
+    pub fn new(engines: Vec<Engine>, rounds: i32, time_control: TimeControl) -> Self {
+        RoundRobin {
+            engines,
+            rounds,
+            time_control,
+            max_plies: Some(DEFAULT_MAX_PLIES),
+        }
+    } //
+
+    /// Passed on to every game, see `Game::with_max_plies`.
+    pub fn with_max_plies(mut self, max_plies: Option<usize>) -> Self {
+        self.max_plies = max_plies;
+        self
+    } //
+
+    /// Every pairing of engines, in the order their games are played.
+    pub fn schedule(&self) -> Vec<(usize, usize)> {
+        let engines = self.engines.len();
+        (0..self.rounds.max(0))
+            .flat_map(|round| {
+                (0..engines).flat_map(move |a| {
+                    (a + 1..engines).map(move |b| if round % 2 == 0 { (a, b) } else { (b, a) })
+                })
+            })
+            .collect()
+    } //
+
+    /// Plays the schedule in order. Aborted games are left out of the result.
+    pub fn start(&self) -> RoundRobinResult {
+        let mut games = Vec::new();
+        for (white, black) in self.schedule() {
+            let game_result = Game::new(
+                self.engines[white].clone(),
+                self.engines[black].clone(),
+                self.time_control,
+            )
+            .with_max_plies(self.max_plies)
+            .play();
+            if game_result.reason() == Termination::Aborted {
+                continue;
+            }
+            games.push((white, black, game_result));
+        }
+        RoundRobinResult {
+            engines: self
+                .engines
+                .iter()
+                .map(|engine| engine.name.clone())
+                .collect(),
+            games,
+        }
+    } //
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::mock_engine::{king_pawn_engine, mock_engine};
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+    use std::time::Duration;
+
+    #[test]
+    fn every_engine_meets_every_other_with_both_colors() {
+        init_bishop_magics();
+        init_rook_magics();
+
+        let open = |name: &str| king_pawn_engine(name, Duration::ZERO);
+        // never produces a move, so it loses every game
+        let silent = mock_engine("Silent", &[]);
+        let round_robin = RoundRobin::new(
+            vec![silent, open("One"), open("Two")],
+            2,
+            TimeControl::TimePerMove(10),
+        )
+        .with_max_plies(Some(2));
+        assert_eq!(
+            round_robin.schedule(),
+            [(0, 1), (0, 2), (1, 2), (1, 0), (2, 0), (2, 1)]
+        );
+
+        let result = round_robin.start();
+        assert_eq!(result.games.len(), 6);
+        let table = result.crosstable();
+        let lost_both = PairingScore {
+            wins: 0,
+            draws: 0,
+            losses: 2,
+        };
+        assert_eq!(table[0][1], lost_both);
+        assert_eq!(table[0][2], lost_both);
+        assert_eq!(table[2][0].wins, 2);
+        assert_eq!(table[1][2].draws, 2);
+        assert_eq!(table[2][1].draws, 2);
+        assert_eq!(table[1][1].games(), 0);
+        assert_eq!(
+            result.standings(),
+            [
+                ("One".to_string(), 3.0),
+                ("Two".to_string(), 3.0),
+                ("Silent".to_string(), 0.0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use queenfish::board::{Board, Move};
+
+const PIECE_LETTERS: [&str; 6] = ["", "N", "B", "R", "Q", "K"];
+
+fn square_name(square: usize) -> String {
+    format!(
+        "{}{}",
+        (b'a' + (square % 8) as u8) as char,
+        (b'1' + (square / 8) as u8) as char
+    )
+} //
+
+/// Standard algebraic notation of `mv`, which must be legal in `board`.
+pub fn uci_to_san(board: &Board, mv: Move) -> String {
+    let (from, to) = (mv.from(), mv.to());
+    let Some(piece) = board.piece_at[from] else {
+        return mv.to_uci();
+    };
+    // piece types run pawn, knight, bishop, rook, queen, king for white, then the same for black
+    let kind = piece as usize % 6;
+    let file_distance = (from % 8).abs_diff(to % 8);
+
+    let mut san = String::new();
+    if kind == 5 && file_distance == 2 {
+        san.push_str(if to % 8 > from % 8 { "O-O" } else { "O-O-O" });
+    } else {
+        let is_capture = board.piece_at[to].is_some() || (kind == 0 && file_distance != 0);
+        san.push_str(PIECE_LETTERS[kind]);
+        if kind == 0 {
+            if is_capture {
+                san.push((b'a' + (from % 8) as u8) as char);
+            }
+        } else if kind != 5 {
+            // other pieces of the same type that could also reach `to`
+            let rivals = board
+                .generate_moves()
+                .into_iter()
+                .filter(|other| {
+                    other.to() == to
+                        && other.from() != from
+                        && board.piece_at[other.from()] == Some(piece)
+                })
+                .map(|other| other.from())
+                .collect::<Vec<_>>();
+            let from_square = square_name(from);
+            if !rivals.is_empty() {
+                if rivals.iter().all(|rival| rival % 8 != from % 8) {
+                    san.push_str(&from_square[..1]);
+                } else if rivals.iter().all(|rival| rival / 8 != from / 8) {
+                    san.push_str(&from_square[1..]);
+                } else {
+                    san.push_str(&from_square);
+                }
+            }
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&square_name(to));
+        if let Some(promotion) = mv.to_uci().chars().nth(4) {
+            san.push('=');
+            san.push(promotion.to_ascii_uppercase());
+        }
+    }
+
+    let mut after = board.clone();
+    after.make_move(mv);
+    if after.is_king_in_check(after.turn) {
+        san.push(if after.generate_moves().is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    san
+} //
+
+/// SAN of a sequence of uci moves played from `board`.
+pub fn moves_to_san(board: &Board, moves: &[String]) -> Vec<String> {
+    let mut board = board.clone();
+    moves
+        .iter()
+        .map(|uci| {
+            let mv = Move::from_uci(uci, &board);
+            let san = uci_to_san(&board, mv);
+            board.make_move(mv);
+            san
+        })
+        .collect()
+} //
+
+/// The legal move of `board` written as `san`. Check marks and annotations (`+#!?`) are
+/// optional, and castling may be written with zeros.
+pub fn san_to_move(board: &Board, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+    board
+        .generate_moves()
+        .into_iter()
+        .find(|mv| uci_to_san(board, *mv).trim_end_matches(['+', '#']) == san)
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use queenfish::board::bishop_magic::init_bishop_magics;
+    use queenfish::board::rook_magic::init_rook_magics;
+
+    fn san(fen: &str, uci: &str) -> String {
+        init_bishop_magics();
+        init_rook_magics();
+        let mut board = Board::new();
+        board.load_from_fen(fen);
+        let mv = Move::from_uci(uci, &board);
+        uci_to_san(&board, mv)
+    }
+
+    #[test]
+    fn disambiguates_by_file_rank_or_both() {
+        assert_eq!(san("k7/8/8/8/8/8/K7/R6R w - - 0 1", "a1d1"), "Rad1");
+        assert_eq!(san("k7/8/8/8/8/8/K7/R6R w - - 0 1", "h1d1"), "Rhd1");
+        assert_eq!(san("7k/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("7k/8/8/R7/8/8/8/R3K3 w - - 0 1", "a5a3"), "R5a3");
+        assert_eq!(san("8/7k/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+        assert_eq!(san("8/7k/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a3b3"), "Qb3");
+    }
+
+    #[test]
+    fn special_moves() {
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castling, "e1g1"), "O-O");
+        assert_eq!(san(castling, "e1c1"), "O-O-O");
+        assert_eq!(san("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7a8q"), "bxa8=Q+");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(san("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1", "g1f3"), "Nf3");
+    }
+
+    #[test]
+    fn fools_mate() {
+        init_bishop_magics();
+        init_rook_magics();
+        let moves = ["f2f3", "e7e5", "g2g4", "d8h4"].map(str::to_string);
+        assert_eq!(
+            moves_to_san(&Board::new(), &moves),
+            vec!["f3", "e5", "g4", "Qh4#"]
+        );
+    }
+
+    #[test]
+    fn san_is_read_back() {
+        init_bishop_magics();
+        init_rook_magics();
+        let mut board = Board::new();
+        board.load_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        for (san, uci) in [("O-O", "e1g1"), ("0-0-0", "e1c1"), ("Rxa8+!?", "a1a8")] {
+            assert_eq!(
+                san_to_move(&board, san).map(|mv| mv.to_uci()),
+                Some(uci.to_string())
+            );
+        }
+        assert!(san_to_move(&board, "Qd1").is_none());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::elo::expected_score;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Games of each result added before the ratio is worked out.
+const PSEUDO_COUNT: f64 = 0.5;
+
+/// Sequential probability ratio test of H0: engine1 is `elo0` stronger than engine2, against
+/// H1: it is `elo1` stronger. `alpha` is the chance of accepting H1 when H0 holds, `beta` that of
+/// accepting H0 when H1 holds. See `Tournament::with_sprt`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// How a match run with an `Sprt` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SprtResult {
+    H0,           // the log-likelihood ratio fell to the lower bound
+    H1,           // it rose to the upper bound
+    Inconclusive, // every planned game was played, or the match ended for another reason
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        }
+    } //
+
+    /// The log-likelihood ratio at which H0 and H1 are accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    } //
+
+    /// Log-likelihood ratio of H1 against H0 after engine1's `wins`, `draws` and `losses`, in
+    /// the normal approximation of the score; 0 before any game. Half a game is added to each
+    /// count, so a run of one result alone (a clean sweep, say) still has a variance and decides
+    /// the test.
+    pub fn llr(&self, wins: u64, draws: u64, losses: u64) -> f64 {
+        if wins + draws + losses == 0 {
+            return 0.0;
+        }
+        let [wins, draws, losses] = [wins, draws, losses].map(|count| count as f64 + PSEUDO_COUNT);
+        let games = wins + draws + losses;
+        let score = (wins + draws / 2.0) / games;
+        let variance =
+            (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2))
+                / games;
+        let (score0, score1) = (expected_score(self.elo0), expected_score(self.elo1));
+        (score1 - score0) * (2.0 * score - score0 - score1) / (2.0 * variance / games)
+    } //
+
+    /// H0 or H1 once the ratio has crossed a bound, None while the test goes on.
+    pub fn decide(&self, wins: u64, draws: u64, losses: u64) -> Option<SprtResult> {
+        let llr = self.llr(wins, draws, losses);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Some(SprtResult::H0)
+        } else if llr >= upper {
+            Some(SprtResult::H1)
+        } else {
+            None
+        }
+    } //
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_ratio_decides_between_the_hypotheses() {
+        let sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3 && (upper - 2.944).abs() < 1e-3);
+
+        assert_eq!(sprt.llr(0, 0, 0), 0.0);
+        assert!((sprt.llr(600, 800, 400) - 9.219).abs() < 1e-3);
+        assert_eq!(sprt.decide(600, 800, 400), Some(SprtResult::H1));
+        assert_eq!(sprt.decide(400, 800, 600), Some(SprtResult::H0));
+        assert_eq!(sprt.decide(50, 100, 45), None);
+    }
+
+    #[test]
+    fn a_clean_sweep_decides_the_test() {
+        let sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        assert_eq!(sprt.decide(14, 0, 0), None);
+        assert_eq!(sprt.decide(15, 0, 0), Some(SprtResult::H1));
+        assert_eq!(sprt.decide(0, 0, 15), Some(SprtResult::H0));
+        assert_eq!(sprt.decide(0, 0, 5), None);
+    }
+}
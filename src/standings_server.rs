@@ -0,0 +1,272 @@
+use crate::tournament::TournamentEvent;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// A client that connects and never sends its request is dropped after this long.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the standings of a running tournament, and the games in progress, as JSON at
+/// `GET /` (or `/standings`), so a long run can be watched from a browser.
+///
+/// Events are read from the receiving end of `Tournament::with_events` on a thread of their own
+/// as they arrive, so they don't pile up while nobody is watching, and the games never wait on
+/// the server.
+pub struct StandingsServer {
+    address: SocketAddr,
+}
+
+impl StandingsServer {
+    pub fn start(address: &str, events: Receiver<TournamentEvent>) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let standings = Arc::new(Mutex::new(LiveStandings::default()));
+        let updated = standings.clone();
+        thread::spawn(move || {
+            for event in events {
+                updated.lock().unwrap().update(event);
+            }
+        });
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let json = standings.lock().unwrap().to_json();
+                if let Err(error) = respond(stream, json) {
+                    log::warn!("Failed to answer a standings request: {}", error);
+                }
+            }
+        });
+        Ok(StandingsServer { address })
+    } //
+
+    /// Where the server listens, with the port filled in if 0 was asked for.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    } //
+}
+
+#[derive(Debug, Default)]
+struct LiveStandings {
+    games_played: u64,
+    total_games: u64,
+    standings: Vec<(String, f64)>,
+    games: BTreeMap<i32, LiveGame>, // by round, until the game is counted in the standings
+}
+
+// A game in progress. Moves are the ones played since the start event, so an opening or book
+// line shows up in the FEN but not in the list.
+#[derive(Debug)]
+struct LiveGame {
+    white: String,
+    black: String,
+    moves: Vec<String>,
+    fen: Option<String>,
+}
+
+impl LiveStandings {
+    fn update(&mut self, event: TournamentEvent) {
+        match event {
+            TournamentEvent::GameStarted {
+                round,
+                white,
+                black,
+            } => {
+                let game = LiveGame {
+                    white,
+                    black,
+                    moves: Vec::new(),
+                    fen: None,
+                };
+                self.games.insert(round, game);
+            }
+            TournamentEvent::Move { round, uci, fen } => {
+                if let Some(game) = self.games.get_mut(&round) {
+                    game.moves.push(uci);
+                    game.fen = Some(fen);
+                }
+            }
+            TournamentEvent::GameFinished {
+                round,
+                progress,
+                standings,
+            } => {
+                self.games.remove(&round);
+                self.games_played = progress.games_played;
+                self.total_games = progress.total_games;
+                self.standings = standings;
+            }
+        }
+    } //
+
+    fn to_json(&self) -> String {
+        let standings = self
+            .standings
+            .iter()
+            .map(|(engine, points)| json!({ "engine": engine, "points": points }))
+            .collect::<Vec<_>>();
+        let games = self
+            .games
+            .iter()
+            .map(|(round, game)| {
+                json!({
+                    "round": round,
+                    "white": game.white,
+                    "black": game.black,
+                    "moves": game.moves,
+                    "fen": game.fen,
+                    "last_move": game.moves.last(),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "games_played": self.games_played,
+            "total_games": self.total_games,
+            "standings": standings,
+            "games": games,
+        })
+        .to_string()
+    } //
+}
+
+fn respond(stream: TcpStream, standings: String) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the headers are read past but not needed
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/" | "/standings")) => ("200 OK", standings),
+        _ => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+} //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tournament::TournamentProgress;
+    use std::io::Read;
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn body(response: &str) -> serde_json::Value {
+        serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn standings_and_the_live_games_are_served() {
+        let (tx, rx) = mpsc::channel();
+        let server = StandingsServer::start("127.0.0.1:0", rx).unwrap();
+
+        let response = get(server.local_addr(), "/");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(
+            body(&response),
+            json!({ "games_played": 0, "total_games": 0, "standings": [], "games": [] })
+        );
+
+        let started = |round: i32| TournamentEvent::GameStarted {
+            round,
+            white: "B".to_string(),
+            black: "Quote \"Me\"".to_string(),
+        };
+        // rounds 2 and 3 under way at once, with their moves interleaved
+        for event in [
+            started(1),
+            started(2),
+            started(3),
+            TournamentEvent::Move {
+                round: 3,
+                uci: "d2d4".to_string(),
+                fen: "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 1".to_string(),
+            },
+            TournamentEvent::Move {
+                round: 2,
+                uci: "e2e4".to_string(),
+                fen: "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string(),
+            },
+            TournamentEvent::GameFinished {
+                round: 1,
+                progress: TournamentProgress {
+                    games_played: 1,
+                    total_games: 4,
+                    elapsed: Duration::from_secs(3),
+                    eta: Some(Duration::from_secs(9)),
+                },
+                standings: vec![("Quote \"Me\"".to_string(), 1.0), ("B".to_string(), 0.0)],
+            },
+        ] {
+            tx.send(event).unwrap();
+        }
+
+        // the events are taken in on the server's own thread, the finished game last
+        let started = Instant::now();
+        let mut response = get(server.local_addr(), "/standings");
+        while body(&response)["games_played"] == 0 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+            response = get(server.local_addr(), "/standings");
+        }
+        let live_game = |round: i32, uci: &str, fen: &str| {
+            json!({
+                "round": round,
+                "white": "B",
+                "black": "Quote \"Me\"",
+                "moves": [uci],
+                "fen": fen,
+                "last_move": uci,
+            })
+        };
+        assert_eq!(
+            body(&response),
+            json!({
+                "games_played": 1,
+                "total_games": 4,
+                "standings": [
+                    { "engine": "Quote \"Me\"", "points": 1.0 },
+                    { "engine": "B", "points": 0.0 },
+                ],
+                "games": [
+                    live_game(
+                        2,
+                        "e2e4",
+                        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+                    ),
+                    live_game(
+                        3,
+                        "d2d4",
+                        "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 1"
+                    ),
+                ],
+            })
+        );
+
+        assert!(get(server.local_addr(), "/favicon.ico").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,39 @@
+use queenfish::board::{Board, Turn};
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess};
+use shakmaty_syzygy::{Tablebase as SyzygyTables, Wdl};
+use std::io;
+use std::path::Path;
+
+pub struct Tablebase {
+    tables: SyzygyTables<Chess>,
+}
+
+impl Tablebase {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut tables = SyzygyTables::new();
+        tables.add_directory(path)?;
+        Ok(Tablebase { tables })
+    } //
+
+    pub fn max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    } //
+
+    // Theoretical result from white's point of view (1, 0, -1), or None if the position is not
+    // covered by the loaded tables. Cursed wins and blessed losses count as draws, since the
+    // fifty-move rule applies.
+    pub fn probe(&self, board: &Board) -> Option<i32> {
+        let fen: Fen = format!("{} 0 1", board.to_fen()).parse().ok()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard).ok()?;
+        let side_to_move = match self.tables.probe_wdl_after_zeroing(&position).ok()? {
+            Wdl::Win => 1,
+            Wdl::Loss => -1,
+            _ => 0,
+        };
+        Some(match board.turn {
+            Turn::WHITE => side_to_move,
+            Turn::BLACK => -side_to_move,
+        })
+    } //
+}
@@ -1,7 +1,58 @@
-use crate::engine::Engine;
-use crate::game::{Game, GameResult, TimeControl};
+use crate::csv_export::CsvExporter;
+use crate::elo::{likelihood_of_superiority, match_elo};
+use crate::engine::{Engine, EngineLocation};
+#[cfg(feature = "serde")]
+use crate::error::TournamentStateError;
+use crate::error::{EngineError, OpeningError};
+use crate::game::{
+    BetweenGames, DEFAULT_MAX_PLIES, Game, GameResult, Opening, Outcome, Termination, TimeControl,
+};
+use crate::opening_book::OpeningBook;
+use crate::pgn::{PgnTags, today};
+use crate::polyglot::{PolyglotBook, SplitMix64};
+use crate::sprt::{Sprt, SprtResult};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Points per game result, used for the displayed standings only. Statistics such as Elo work
+/// from the win/draw/loss counts, whatever the scoring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScoringSystem {
+    pub win: f64,
+    pub draw: f64,
+    pub loss: f64,
+}
+
+impl Default for ScoringSystem {
+    fn default() -> Self {
+        ScoringSystem {
+            win: 1.0,
+            draw: 0.5,
+            loss: 0.0,
+        }
+    }
+}
+
+impl ScoringSystem {
+    pub fn points(&self, wins: u64, draws: u64, losses: u64) -> f64 {
+        wins as f64 * self.win + draws as f64 * self.draw + losses as f64 * self.loss
+    } //
+}
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TournamentResult {
     engine1: String,
     engine2: String,
@@ -10,6 +61,10 @@ pub struct TournamentResult {
     engine2_won: u64,
     draws: u64,
     total_games: u64,
+    pgn_tags: PgnTags,
+    elapsed: Duration,
+    scoring: ScoringSystem,
+    sprt_result: Option<SprtResult>,
 }
 impl TournamentResult {
     pub fn default() -> Self {
@@ -21,6 +76,10 @@ impl TournamentResult {
             engine2_won: 0,
             draws: 0,
             total_games: 0,
+            pgn_tags: PgnTags::default(),
+            elapsed: Duration::ZERO,
+            scoring: ScoringSystem::default(),
+            sprt_result: None,
         }
     }
     pub fn new(
@@ -40,52 +99,1594 @@ impl TournamentResult {
             engine2_won,
             draws,
             total_games,
+            pgn_tags: PgnTags::default(),
+            elapsed: Duration::ZERO,
+            scoring: ScoringSystem::default(),
+            sprt_result: None,
         }
     }
+
+    fn record(&mut self, game_result: &GameResult) {
+        self.games_list.push(game_result.clone());
+        self.total_games += 1;
+        match game_result.winner_slot() {
+            Some(0) => self.engine1_won += 1,
+            Some(_) => self.engine2_won += 1,
+            None => self.draws += 1,
+        }
+    } //
+
+    pub fn engine1(&self) -> &str {
+        &self.engine1
+    } //
+    pub fn engine2(&self) -> &str {
+        &self.engine2
+    } //
+    /// Every game played, in order; the indices `game_pgn` and `decisive_game` use.
+    pub fn games_list(&self) -> &[GameResult] {
+        &self.games_list
+    } //
+    pub fn engine1_won(&self) -> u64 {
+        self.engine1_won
+    } //
+    pub fn engine2_won(&self) -> u64 {
+        self.engine2_won
+    } //
+    pub fn draws(&self) -> u64 {
+        self.draws
+    } //
+    pub fn total_games(&self) -> u64 {
+        self.total_games
+    } //
+
+    /// engine1's Elo advantage over engine2 and its 95% margin, see `match_elo`. Counts wins
+    /// and draws, whatever the `ScoringSystem`.
+    pub fn elo_diff(&self) -> (f64, f64) {
+        match_elo(self.engine1_won, self.draws, self.engine2_won)
+    } //
+
+    /// Probability that engine1 is the stronger engine, see `likelihood_of_superiority`.
+    pub fn los(&self) -> f64 {
+        likelihood_of_superiority(self.engine1_won, self.engine2_won)
+    } //
+
+    /// How the SPRT of `Tournament::with_sprt` ended, None if the match had none.
+    pub fn sprt_result(&self) -> Option<SprtResult> {
+        self.sprt_result
+    } //
+
+    /// Wall time the whole tournament took.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The whole match as JSON: the counts, the tags and every game with its moves, scores and
+    /// clocks. `from_json` reads it back, though its games can't be replayed.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a tournament result always serializes")
+    } //
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    } //
+
+    /// Each engine's points under the tournament's `ScoringSystem`, best first.
+    pub fn standings(&self) -> Vec<(String, f64)> {
+        let mut standings = vec![
+            (
+                self.engine1.clone(),
+                self.scoring
+                    .points(self.engine1_won, self.draws, self.engine2_won),
+            ),
+            (
+                self.engine2.clone(),
+                self.scoring
+                    .points(self.engine2_won, self.draws, self.engine1_won),
+            ),
+        ];
+        standings.sort_by(|a, b| b.1.total_cmp(&a.1));
+        standings
+    }
+
+    /// The first game that wasn't drawn, as its index in `games_list` and whether white won.
+    pub fn decisive_game(&self) -> Option<(usize, bool)> {
+        self.games_list
+            .iter()
+            .position(|game_result| game_result.outcome().is_decisive())
+            .map(|index| {
+                (
+                    index,
+                    self.games_list[index].outcome() == Outcome::WhiteWins,
+                )
+            })
+    }
+
+    /// The engine with more match points, a win counting 1 and a draw half; None when level. For
+    /// a `PairingMode::FirstTo` match this is the match's winner, and None a drawn match.
+    pub fn match_winner(&self) -> Option<&str> {
+        match self.engine1_won.cmp(&self.engine2_won) {
+            std::cmp::Ordering::Greater => Some(&self.engine1),
+            std::cmp::Ordering::Less => Some(&self.engine2),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    /// PGN of the game at `index` in `games_list`, tagged with the tournament's event/site/date
+    /// and its round number.
+    pub fn game_pgn(&self, index: usize) -> Option<String> {
+        let game_result = self.games_list.get(index)?;
+        let tags = self.pgn_tags.clone().with_round(&(index + 1).to_string());
+        Some(game_result.to_pgn(&tags))
+    } //
+
+    /// Writes every game, in the order played, to one PGN database: each with its own Round tag
+    /// and the colors it was played with, separated by blank lines.
+    pub fn write_pgn<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for index in 0..self.games_list.len() {
+            if index > 0 {
+                writeln!(writer)?;
+            }
+            let pgn = self.game_pgn(index).expect("index is within games_list");
+            writer.write_all(pgn.as_bytes())?;
+        }
+        writer.flush()
+    } //
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TournamentProgress {
+    pub games_played: u64,
+    pub total_games: u64,
+    pub elapsed: Duration,
+    pub eta: Option<Duration>, // None until a game has finished
+}
+impl TournamentProgress {
+    // The estimate uses throughput (games per second so far), so it holds however many games
+    // run at once.
+    fn new(games_played: u64, total_games: u64, elapsed: Duration) -> Self {
+        let eta = (games_played > 0).then(|| {
+            let remaining = total_games.saturating_sub(games_played);
+            elapsed.mul_f64(remaining as f64 / games_played as f64)
+        });
+        TournamentProgress {
+            games_played,
+            total_games,
+            elapsed,
+            eta,
+        }
+    }
+}
+
+/// How many games a tournament plays.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PairingMode {
+    #[default]
+    Fixed, // every round given to `Tournament::new`
+    PlayUntilDecisive {
+        max_games: i32, // played in full if every game is drawn
+    },
+    /// A match won by the first engine to `points`, a win counting 1 and a draw half whatever
+    /// the `ScoringSystem`. Both reaching it on the same draw, as at 6-6 in a first to 6, is a
+    /// drawn match.
+    FirstTo { points: f64 },
+}
+
+/// How rounds draw their opening when there are several suites.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OpeningStrategy {
+    #[default]
+    Sequential, // every opening of the first suite, then the next suite, and so on
+    RoundRobin, // one opening from each suite in turn
+    Random,     // a random suite and opening, following `Tournament::with_seed`
 }
 
+/// What a running tournament reports on the channel given to `Tournament::with_events`. Every
+/// event names its round, since games played at the same time (see
+/// `Tournament::with_concurrency`) report their moves interleaved.
+#[derive(Debug, Clone)]
+pub enum TournamentEvent {
+    GameStarted {
+        round: i32, // from 1, as in logs and PGN
+        white: String,
+        black: String,
+    },
+    Move {
+        round: i32,
+        uci: String,
+        fen: String, // the position after the move
+    },
+    // only sent by `start`/`start_with_progress`, which keep the score, in round order
+    GameFinished {
+        round: i32,
+        progress: TournamentProgress,
+        standings: Vec<(String, f64)>,
+    },
+}
+
+#[derive(Clone)]
 pub struct Tournament {
     rounds: i32,
+    mode: PairingMode,
     engine1: Engine,
     engine2: Engine,
     time_control: TimeControl,
+    openings: Vec<Opening>,     // every suite, one after the other
+    opening_suites: Vec<usize>, // length of each suite in `openings`
+    opening_strategy: OpeningStrategy,
+    pair_openings: bool,
+    pgn_tags: PgnTags,
+    max_plies: Option<usize>,
+    log_dir: Option<PathBuf>,
+    deny_oversubscription: bool,
+    cancel_token: Arc<AtomicBool>,
+    polyglot_book: Option<(Arc<PolyglotBook>, usize)>, // book and the most plies taken from it
+    seed: u64,
+    scoring: ScoringSystem,
+    events: Option<Sender<TournamentEvent>>,
+    csv_out: Option<PathBuf>,
+    between_games: BetweenGames,
+    resumed: Vec<GameResult>, // games loaded by `resume`, counted before every `start`
+    completed: Vec<GameResult>, // games played by the latest `start`
+    #[cfg(feature = "serde")]
+    state_file: Option<PathBuf>,
+    concurrency: usize, // games played at the same time
+    sprt: Option<Sprt>,
 }
 
 impl Tournament {
-    pub fn new(rounds: i32, engine1: Engine, engine2: Engine, time_control: TimeControl) -> Self {
+    /// If both engines share a name the second one is renamed to `"<name> (2)"` so they can be
+    /// told apart in the output.
+    pub fn new(
+        rounds: i32,
+        engine1: Engine,
+        mut engine2: Engine,
+        time_control: TimeControl,
+    ) -> Self {
+        if engine2.name == engine1.name {
+            engine2.name = format!("{} (2)", engine2.name);
+        }
         Tournament {
             rounds,
+            mode: PairingMode::default(),
             engine1,
             engine2,
             time_control,
+            openings: Vec::new(),
+            opening_suites: Vec::new(),
+            opening_strategy: OpeningStrategy::default(),
+            pair_openings: false,
+            pgn_tags: PgnTags::default(),
+            max_plies: Some(DEFAULT_MAX_PLIES),
+            log_dir: None,
+            deny_oversubscription: false,
+            cancel_token: Arc::new(AtomicBool::new(false)),
+            polyglot_book: None,
+            seed: 0,
+            scoring: ScoringSystem::default(),
+            events: None,
+            csv_out: None,
+            between_games: BetweenGames::default(),
+            resumed: Vec::new(),
+            completed: Vec::new(),
+            #[cfg(feature = "serde")]
+            state_file: None,
+            concurrency: 1,
+            sprt: None,
+        }
+    } //
+
+    /// Report game starts, every move and the standings after each game on `events`. Sending
+    /// never blocks, so a slow reader can't hold up the games.
+    pub fn with_events(mut self, events: Sender<TournamentEvent>) -> Self {
+        self.events = Some(events);
+        self
+    } //
+
+    /// Passed on to every game, see `BetweenGames` for the tradeoffs. Defaults to
+    /// `BetweenGames::NewGame`.
+    pub fn with_between_games(mut self, between_games: BetweenGames) -> Self {
+        self.between_games = between_games;
+        self
+    } //
+
+    /// Write per-ply time, score, depth and nodes of every game to a CSV file at `path`, a game
+    /// at a time as they finish, see `CsvExporter`. Games are numbered from 1. A file that can't
+    /// be created is logged and the games are played without it.
+    pub fn with_csv_out(mut self, path: impl Into<PathBuf>) -> Self {
+        self.csv_out = Some(path.into());
+        self
+    } //
+
+    /// Stop the match as soon as `sprt` accepts either hypothesis, checked after every game in
+    /// round order. The rounds given to `new` are the most that are played;
+    /// `TournamentResult::sprt_result` tells how the test ended.
+    pub fn with_sprt(mut self, sprt: Sprt) -> Self {
+        self.sprt = Some(sprt);
+        self
+    } //
+
+    /// Play up to `concurrency` games at the same time, each with processes of its own. Games
+    /// are still handed out, counted and saved in round order, so the result is the one a
+    /// sequential run would give; a match that ends early (see `PairingMode`) lets the games
+    /// already started finish and drops them. Moves reported on `with_events` interleave
+    /// between the games in progress, each tagged with its round. Defaults to 1.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    } //
+
+    /// Save the tournament's state to `path` after every game, see `save_state`, so a run that
+    /// is interrupted can be picked up with `resume`.
+    #[cfg(feature = "serde")]
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    } //
+
+    /// Writes the games played so far and the settings that decide the games still to come to
+    /// `path` as JSON. Stop a run with `cancel_token`, save, and a tournament set up the same way
+    /// can `resume` from the file later, playing the same rounds with the same openings and
+    /// seeds it would have played. The win/draw/loss counts, and every statistic worked out from
+    /// them, follow from the saved games.
+    #[cfg(feature = "serde")]
+    pub fn save_state(&self, path: impl AsRef<Path>) -> Result<(), TournamentStateError> {
+        let state = TournamentState {
+            version: STATE_VERSION,
+            config: self.saved_config(),
+            games: self
+                .resumed
+                .iter()
+                .chain(&self.completed)
+                .cloned()
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&state)
+            .map_err(|error| TournamentStateError::Malformed(error.to_string()))?;
+        // written beside the old state and renamed over it, so a crash mid-write loses nothing
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        fs::write(&partial, json)
+            .and_then(|_| fs::rename(&partial, path))
+            .map_err(|error| TournamentStateError::Io(error.to_string()))
+    } //
+
+    /// Picks up a tournament saved with `save_state`: its games count as played, and `start`
+    /// goes on from the round after them. The tournament must be set up as the saved one was,
+    /// down to its engines' names, rounds, time control, openings and seed.
+    #[cfg(feature = "serde")]
+    pub fn resume(mut self, path: impl AsRef<Path>) -> Result<Self, TournamentStateError> {
+        let json = fs::read_to_string(path)
+            .map_err(|error| TournamentStateError::Io(error.to_string()))?;
+        let state: TournamentState = serde_json::from_str(&json)
+            .map_err(|error| TournamentStateError::Malformed(error.to_string()))?;
+        if state.version != STATE_VERSION {
+            return Err(TournamentStateError::Malformed(format!(
+                "unsupported version {}",
+                state.version
+            )));
+        }
+        if let Some(setting) = self.saved_config().mismatch(&state.config) {
+            return Err(TournamentStateError::Mismatch(setting.to_string()));
+        }
+        self.resumed = state
+            .games
+            .into_iter()
+            .enumerate()
+            .map(|(round, game_result)| self.round_game(round as i32).restored_result(game_result))
+            .collect();
+        Ok(self)
+    } //
+
+    #[cfg(feature = "serde")]
+    fn saved_config(&self) -> SavedConfig {
+        SavedConfig {
+            engines: [self.engine1.name.clone(), self.engine2.name.clone()],
+            rounds: self.rounds,
+            mode: self.mode,
+            time_control: self.time_control,
+            openings: self.openings.len(),
+            opening_strategy: self.opening_strategy,
+            pair_openings: self.pair_openings,
+            book_plies: self.polyglot_book.as_ref().map(|(_, max_plies)| *max_plies),
+            max_plies: self.max_plies,
+            seed: self.seed,
+            scoring: self.scoring,
+            between_games: self.between_games,
+            sprt: self.sprt,
+        }
+    } //
+
+    /// With `PlayUntilDecisive` the rounds given to `new` are ignored: games are played, colors
+    /// alternating as usual, until one is won or `max_games` were played.
+    pub fn with_mode(mut self, mode: PairingMode) -> Self {
+        self.mode = mode;
+        self
+    } //
+
+    /// Points awarded per game in the standings. Defaults to 1/0.5/0.
+    pub fn with_scoring(mut self, scoring: ScoringSystem) -> Self {
+        self.scoring = scoring;
+        self
+    } //
+
+    /// Passed on to every game, see `Game::with_max_plies`.
+    pub fn with_max_plies(mut self, max_plies: Option<usize>) -> Self {
+        self.max_plies = max_plies;
+        self
+    } //
+
+    /// Directory for per-process engine transcripts, named by round, color and engine, see
+    /// `Game::with_log_dir`.
+    pub fn with_log_dir(mut self, log_dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = Some(log_dir.into());
+        self
+    } //
+
+    /// Event/site (and any extra tags) for the exported games. Round and an unset date are
+    /// filled in per game.
+    pub fn with_pgn_tags(mut self, pgn_tags: PgnTags) -> Self {
+        self.pgn_tags = pgn_tags;
+        self
+    } //
+
+    /// Fails on the first opening a game couldn't start from, see `Game::try_with_opening`.
+    pub fn with_openings(self, openings: Vec<Opening>) -> Result<Self, OpeningError> {
+        self.with_opening_suites(vec![openings], OpeningStrategy::Sequential)
+    } //
+
+    /// Start rounds from the positions of `book` in order, each twice in a row so both engines
+    /// play it once with each color, and cycle back to the first after the last. Each game's
+    /// result, and so its PGN, carries the position it started from.
+    pub fn with_opening_book(self, book: &OpeningBook) -> Self {
+        self.with_openings(book.openings().to_vec())
+            .expect("the book checked its positions as it loaded")
+            .pair_openings(true)
+    } //
+
+    /// Draw openings from several suites, so a match much longer than one suite doesn't keep
+    /// replaying its first lines. `GameResult::opening_index` counts through the suites in order.
+    /// Every opening is checked up front, so a bad one fails here rather than in the middle of
+    /// the match.
+    pub fn with_opening_suites(
+        mut self,
+        suites: Vec<Vec<Opening>>,
+        strategy: OpeningStrategy,
+    ) -> Result<Self, OpeningError> {
+        let suites = suites
+            .into_iter()
+            .filter(|suite| !suite.is_empty())
+            .collect::<Vec<_>>();
+        self.opening_suites = suites.iter().map(Vec::len).collect();
+        self.openings = suites.into_iter().flatten().collect();
+        for (index, opening) in self.openings.iter().enumerate() {
+            Game::new(
+                self.engine1.clone(),
+                self.engine2.clone(),
+                self.time_control,
+            )
+            .try_with_opening(index, opening)?;
+        }
+        self.opening_strategy = strategy;
+        Ok(self)
+    } //
+
+    /// Play every opening twice in consecutive rounds, once with each engine as white.
+    pub fn pair_openings(mut self, pair_openings: bool) -> Self {
+        self.pair_openings = pair_openings;
+        self
+    } //
+
+    /// Setting the returned flag stops the tournament cleanly: the game in progress is aborted
+    /// before its next move, its engines quit, and `start` returns the games finished so far.
+    pub fn cancel_token(&self) -> Arc<AtomicBool> {
+        self.cancel_token.clone()
+    } //
+
+    /// Refuse to run when the engines' `Threads` add up to more than the available cpus, instead
+    /// of only warning.
+    pub fn deny_oversubscription(mut self, deny_oversubscription: bool) -> Self {
+        self.deny_oversubscription = deny_oversubscription;
+        self
+    } //
+
+    /// Sums the `Threads` of every engine running at the same time (both engines of each game in
+    /// progress, see `with_concurrency`) and compares it with `std::thread::available_parallelism`.
+    pub fn check_threads(&self) -> Result<(), EngineError> {
+        let threads = (self.engine1.threads() + self.engine2.threads()) * self.concurrency as u32;
+        let cpus = thread::available_parallelism().map_or(1, |cpus| cpus.get() as u32);
+        if threads > cpus {
+            return Err(EngineError::Oversubscribed { threads, cpus });
+        }
+        Ok(())
+    } //
+
+    // Oversubscription is only a warning unless `deny_oversubscription` is set.
+    fn oversubscription_error(&self) -> Option<EngineError> {
+        let error = self.check_threads().err()?;
+        if self.deny_oversubscription {
+            Some(error)
+        } else {
+            log::warn!("{}", error);
+            None
+        }
+    } //
+
+    /// Dry run: starts each engine, goes through the uci handshake and checks its configured
+    /// options, then quits it. Returns every problem found rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), Vec<EngineError>> {
+        let mut errors = Vec::new();
+        for engine in [&self.engine1, &self.engine2] {
+            let started = match &engine.location {
+                EngineLocation::Local(_) => Engine::try_new(&engine.path, &engine.name),
+                EngineLocation::Remote(address) => Engine::try_remote(address, &engine.name),
+            };
+            match started {
+                Ok(mut started) => {
+                    started.configured_options = engine.configured_options.clone();
+                    errors.extend(started.check_configured_options());
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        errors.extend(self.oversubscription_error());
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    } //
+
+    /// Start every game with up to `max_plies` weighted random moves from a Polyglot `.bin` book
+    /// (after the fixed opening, if any). Lines follow `with_seed`, and paired rounds share one.
+    pub fn with_polyglot_book(
+        mut self,
+        path: impl AsRef<Path>,
+        max_plies: usize,
+    ) -> io::Result<Self> {
+        self.polyglot_book = Some((Arc::new(PolyglotBook::open(path)?), max_plies));
+        Ok(self)
+    } //
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    } //
+
+    // Seed for the random opening and book line of a round; both games of an opening pair get
+    // the same one.
+    fn round_seed(&self, round: i32) -> u64 {
+        let line = if self.pair_openings { round / 2 } else { round } as u64;
+        self.seed ^ line.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    } //
+
+    // Most games the tournament can play; `PlayUntilDecisive` may stop short of it.
+    fn planned_games(&self) -> i32 {
+        match self.mode {
+            PairingMode::Fixed => self.rounds,
+            PairingMode::PlayUntilDecisive { max_games } => max_games,
+            // every game hands out a point, so after this many someone has the target
+            PairingMode::FirstTo { points } => (2.0 * points).ceil() as i32,
         }
     } //
 
-    // pub fn start(&mut self) -> TournamentResult {
-    //     let mut tournament_result = TournamentResult::default();
-    //     tournament_result.engine1 = self.engine1.name.clone();
-    //     tournament_result.engine2 = self.engine2.name.clone();
-    //     for i in 0..self.rounds {
-    //         let engine1 = Engine::new(&self.engine1.path, &self.engine1.name);
-    //         let engine2 = Engine::new(&self.engine2.path, &self.engine2.name);
-    //         let mut game;
-    //         if i % 2 == 0 {
-    //             game = Game::new(engine1, engine2, self.time_control);
-    //         } else {
-    //             game = Game::new(engine2, engine1, self.time_control);
-    //         }
-    //         let game_result = game.play();
-    //         tournament_result.games_list.push(game_result.clone());
-    //         tournament_result.total_games += 1;
-
-    //         if game_result.winner() == self.engine1.name {
-    //             tournament_result.engine1_won += 1;
-    //         } else if game_result.winner() == self.engine2.name {
-    //             tournament_result.engine2_won += 1;
-    //         } else {
-    //             tournament_result.draws += 1;
-    //         }
-    //     }
-    //     tournament_result
-    // } //
+    fn opening_for_round(&self, round: i32) -> Option<usize> {
+        if self.openings.is_empty() {
+            return None;
+        }
+        let line = if self.pair_openings { round / 2 } else { round } as usize;
+        let suites = self.opening_suites.len();
+        let (suite, index) = match self.opening_strategy {
+            OpeningStrategy::Sequential => return Some(line % self.openings.len()),
+            OpeningStrategy::RoundRobin => {
+                let suite = line % suites;
+                (suite, (line / suites) % self.opening_suites[suite])
+            }
+            OpeningStrategy::Random => {
+                let mut rng = SplitMix64(self.round_seed(round));
+                let suite = (rng.next_u64() % suites as u64) as usize;
+                let index = rng.next_u64() % self.opening_suites[suite] as u64;
+                (suite, index as usize)
+            }
+        };
+        Some(self.opening_suites[..suite].iter().sum::<usize>() + index)
+    } //
+
+    fn round_game(&self, round: i32) -> Game {
+        let engine1 = self.engine1.clone();
+        let engine2 = self.engine2.clone();
+        let mut game;
+        if round % 2 == 0 {
+            game = Game::new(engine1, engine2, self.time_control).with_slots(0, 1);
+        } else {
+            game = Game::new(engine2, engine1, self.time_control).with_slots(1, 0);
+        }
+        // rounds are numbered from 1 in logs and PGN
+        game = game
+            .with_max_plies(self.max_plies)
+            .with_round(round + 1)
+            .with_between_games(self.between_games)
+            .with_cancel_token(self.cancel_token.clone());
+        if let Some(log_dir) = &self.log_dir {
+            game = game.with_log_dir(log_dir);
+        }
+        if let Some(index) = self.opening_for_round(round) {
+            game = game.with_opening(index, &self.openings[index]);
+        }
+        if let Some((book, max_plies)) = &self.polyglot_book {
+            game = game.with_book(book, *max_plies, self.round_seed(round));
+        }
+        game
+    } //
+
+    fn play_round(&self, round: i32) -> GameResult {
+        let mut game = self.round_game(round);
+        let Some(events) = &self.events else {
+            return game.play();
+        };
+        let (white, black) = if round % 2 == 0 {
+            (&self.engine1, &self.engine2)
+        } else {
+            (&self.engine2, &self.engine1)
+        };
+        let _ = events.send(TournamentEvent::GameStarted {
+            round: round + 1,
+            white: white.name.clone(),
+            black: black.name.clone(),
+        });
+        game.play_with_observer(|board, mv| {
+            let _ = events.send(TournamentEvent::Move {
+                round: round + 1,
+                uci: mv.to_uci(),
+                fen: board.to_fen(),
+            });
+        })
+    } //
+
+    /// Plays the tournament on a background thread, yielding each game as soon as it and every
+    /// round before it have finished. Dropping the iterator stops the tournament after the games
+    /// in progress; see `cancel_token` to stop sooner. A game that fails (an engine that can't be
+    /// started, say) ends the tournament before its round. Panics if the engines oversubscribe
+    /// the cpus and `deny_oversubscription` is set; `try_games` returns the error instead.
+    pub fn games(&self) -> impl Iterator<Item = GameResult> + use<> {
+        self.try_games().unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    /// Like `games`, but returns `EngineError::Oversubscribed` rather than panicking.
+    pub fn try_games(&self) -> Result<impl Iterator<Item = GameResult> + use<>, EngineError> {
+        match self.oversubscription_error() {
+            Some(error) => Err(error),
+            None => Ok(self.play_games()),
+        }
+    } //
+
+    // `games`, once the engines are known to fit the cpus.
+    fn play_games(&self) -> impl Iterator<Item = GameResult> + use<> {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let mut tournament = self.clone();
+        thread::spawn(move || {
+            // engine1's wins, the draws and engine2's wins
+            let mut score = [0; 3];
+            // games from before a resume count, and may already have ended the match
+            let resumed = std::mem::take(&mut tournament.resumed);
+            for (round, game_result) in resumed.iter().enumerate() {
+                if tournament.ends_match(round as i32, game_result, &mut score) {
+                    return;
+                }
+            }
+            let (done_tx, done_rx) = mpsc::channel();
+            thread::scope(|scope| {
+                let mut next_round = resumed.len() as i32;
+                let mut next_handed_out = next_round;
+                let mut in_flight = 0;
+                let mut finished = BTreeMap::new();
+                let mut stopped = false;
+                loop {
+                    while !stopped
+                        && in_flight < tournament.concurrency
+                        && next_round < tournament.planned_games()
+                        && !tournament.cancel_token.load(Ordering::Relaxed)
+                    {
+                        let (worker, done_tx) = (tournament.clone(), done_tx.clone());
+                        let round = next_round;
+                        scope.spawn(move || {
+                            // a game that panics (an engine that can't be started, say) still
+                            // reports back, or the loop below would wait for it forever
+                            let game_result =
+                                panic::catch_unwind(AssertUnwindSafe(|| worker.play_round(round)))
+                                    .ok();
+                            let _ = done_tx.send((round, game_result));
+                        });
+                        next_round += 1;
+                        in_flight += 1;
+                    }
+                    if in_flight == 0 {
+                        break;
+                    }
+                    let (round, game_result) = done_rx.recv().expect("a game is in progress");
+                    in_flight -= 1;
+                    if stopped {
+                        continue;
+                    }
+                    // games finish in any order but are handed out in round order
+                    finished.insert(round, game_result);
+                    while let Some(game_result) = finished.remove(&next_handed_out) {
+                        let Some(game_result) = game_result else {
+                            log::error!("Round {} failed, stopping", next_handed_out + 1);
+                            stopped = true;
+                            break;
+                        };
+                        // an aborted game isn't a result, so it is never handed out
+                        if game_result.reason() == Termination::Aborted {
+                            stopped = true;
+                            break;
+                        }
+                        let over = tournament.ends_match(next_handed_out, &game_result, &mut score);
+                        if tx.send(game_result).is_err() || over {
+                            stopped = true;
+                            break;
+                        }
+                        next_handed_out += 1;
+                    }
+                }
+            });
+        });
+        rx.into_iter()
+    } //
+
+    // Adds the game to `score` (engine1's wins, the draws, engine2's wins) and tells whether the
+    // match is over after it, short of the planned games: a decisive game in `PlayUntilDecisive`,
+    // the target reached in `FirstTo`, or a hypothesis accepted by the SPRT.
+    fn ends_match(&self, round: i32, game_result: &GameResult, score: &mut [u64; 3]) -> bool {
+        match game_result.winner_slot() {
+            Some(0) => score[0] += 1,
+            Some(_) => score[2] += 1,
+            None => score[1] += 1,
+        }
+        if let Some(result) = self
+            .sprt
+            .and_then(|sprt| sprt.decide(score[0], score[1], score[2]))
+        {
+            log::info!("SPRT accepted {:?} after round {}", result, round + 1);
+            return true;
+        }
+        // engine1's and engine2's match points, a draw counting half
+        let points = [
+            score[0] as f64 + score[1] as f64 / 2.0,
+            score[2] as f64 + score[1] as f64 / 2.0,
+        ];
+        if game_result.outcome().is_decisive()
+            && matches!(self.mode, PairingMode::PlayUntilDecisive { .. })
+        {
+            log::info!("Round {} was decisive, stopping", round + 1);
+            return true;
+        }
+        // nothing is clinched before the target: the leader hasn't reached it, so the
+        // trailing engine winning every game left (enough to reach it) would still win
+        if let PairingMode::FirstTo { points: target } = self.mode
+            && points.iter().any(|&points| points >= target)
+        {
+            let (engine1, engine2) = (&self.engine1.name, &self.engine2.name);
+            match points[0].total_cmp(&points[1]) {
+                std::cmp::Ordering::Greater => {
+                    log::info!("{} wins the match {}-{}", engine1, points[0], points[1])
+                }
+                std::cmp::Ordering::Less => {
+                    log::info!("{} wins the match {}-{}", engine2, points[1], points[0])
+                }
+                std::cmp::Ordering::Equal => {
+                    log::info!("The match is drawn {}-{}", points[0], points[1])
+                }
+            }
+            return true;
+        }
+        false
+    } //
+
+    /// Plays the tournament. Starting it again plays it again, from the first round or from
+    /// the round after the games loaded by `resume`. Panics where `games` does; see `try_start`.
+    pub fn start(&mut self) -> TournamentResult {
+        self.start_with_progress(|_| {})
+    } //
+
+    /// Like `start`, but returns `EngineError::Oversubscribed`, before any game is played,
+    /// rather than panicking.
+    pub fn try_start(&mut self) -> Result<TournamentResult, EngineError> {
+        self.try_start_with_progress(|_| {})
+    } //
+
+    /// Like `start`, but calls `progress` after every finished game with the elapsed time and an
+    /// estimate of the time left.
+    pub fn start_with_progress<F: FnMut(&TournamentProgress)>(
+        &mut self,
+        progress: F,
+    ) -> TournamentResult {
+        self.try_start_with_progress(progress)
+            .unwrap_or_else(|error| panic!("{}", error))
+    } //
+
+    /// `start_with_progress` as `try_start` is to `start`.
+    pub fn try_start_with_progress<F: FnMut(&TournamentProgress)>(
+        &mut self,
+        mut progress: F,
+    ) -> Result<TournamentResult, EngineError> {
+        if let Some(error) = self.oversubscription_error() {
+            return Err(error);
+        }
+        let start = Instant::now();
+        let mut tournament_result = TournamentResult::default();
+        tournament_result.engine1 = self.engine1.name.clone();
+        tournament_result.engine2 = self.engine2.name.clone();
+        tournament_result.pgn_tags = self.pgn_tags.clone();
+        tournament_result.scoring = self.scoring;
+        if tournament_result.pgn_tags.date == PgnTags::default().date {
+            tournament_result.pgn_tags.date = today();
+        }
+        // the games are worth playing even when their CSV can't be written
+        let mut csv = self
+            .csv_out
+            .as_ref()
+            .and_then(|path| match CsvExporter::create(path) {
+                Ok(csv) => Some(csv),
+                Err(error) => {
+                    log::warn!("Failed to create CSV output {}: {}", path.display(), error);
+                    None
+                }
+            });
+        let write_csv = |csv: &mut Option<CsvExporter<_>>, index: u64, game_result: &GameResult| {
+            if let Some(csv) = csv.as_mut()
+                && let Err(error) = csv.write_game(index as usize, game_result)
+            {
+                log::warn!("Failed to write game to CSV: {}", error);
+            }
+        };
+        // a resumed tournament starts from the games it had played, and every run plays the
+        // rest anew rather than carrying on from the last one
+        self.completed.clear();
+        for game_result in &self.resumed {
+            tournament_result.record(game_result);
+            write_csv(&mut csv, tournament_result.total_games, game_result);
+        }
+        for game_result in self.play_games() {
+            tournament_result.record(&game_result);
+            write_csv(&mut csv, tournament_result.total_games, &game_result);
+            self.completed.push(game_result);
+            #[cfg(feature = "serde")]
+            if let Some(path) = &self.state_file
+                && let Err(error) = self.save_state(path)
+            {
+                log::warn!("{}", error);
+            }
+
+            let game_progress = TournamentProgress::new(
+                tournament_result.total_games,
+                self.planned_games().max(0) as u64,
+                start.elapsed(),
+            );
+            if let Some(events) = &self.events {
+                let _ = events.send(TournamentEvent::GameFinished {
+                    round: tournament_result.total_games as i32,
+                    progress: game_progress,
+                    standings: tournament_result.standings(),
+                });
+            }
+            progress(&game_progress);
+        }
+        tournament_result.sprt_result = self.sprt.map(|sprt| {
+            sprt.decide(
+                tournament_result.engine1_won,
+                tournament_result.draws,
+                tournament_result.engine2_won,
+            )
+            .unwrap_or(SprtResult::Inconclusive)
+        });
+        tournament_result.elapsed = start.elapsed();
+        Ok(tournament_result)
+    } //
+}
+
+#[cfg(feature = "serde")]
+const STATE_VERSION: u32 = 1;
+
+// What `Tournament::save_state` writes.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct TournamentState {
+    version: u32,
+    config: SavedConfig,
+    games: Vec<GameResult>, // in round order; the next round is the one after the last
+}
+
+// The settings a resumed tournament must share with the saved one for its remaining rounds to
+// be the ones the saved one would have played. Engines go by name and openings by count, since
+// the binaries and files themselves can't be compared.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedConfig {
+    engines: [String; 2],
+    rounds: i32,
+    mode: PairingMode,
+    time_control: TimeControl,
+    openings: usize,
+    opening_strategy: OpeningStrategy,
+    pair_openings: bool,
+    book_plies: Option<usize>,
+    max_plies: Option<usize>,
+    seed: u64,
+    scoring: ScoringSystem,
+    between_games: BetweenGames,
+    #[serde(default)]
+    sprt: Option<Sprt>,
+}
+
+#[cfg(feature = "serde")]
+impl SavedConfig {
+    // The first setting that differs from `saved`, by name.
+    fn mismatch(&self, saved: &SavedConfig) -> Option<&'static str> {
+        [
+            ("engines", self.engines == saved.engines),
+            ("number of rounds", self.rounds == saved.rounds),
+            ("pairing mode", self.mode == saved.mode),
+            ("time control", self.time_control == saved.time_control),
+            ("number of openings", self.openings == saved.openings),
+            (
+                "opening strategy",
+                self.opening_strategy == saved.opening_strategy,
+            ),
+            ("opening pairing", self.pair_openings == saved.pair_openings),
+            ("book", self.book_plies == saved.book_plies),
+            ("ply limit", self.max_plies == saved.max_plies),
+            ("seed", self.seed == saved.seed),
+            ("scoring", self.scoring == saved.scoring),
+            (
+                "between games policy",
+                self.between_games == saved.between_games,
+            ),
+            ("SPRT", self.sprt == saved.sprt),
+        ]
+        .into_iter()
+        .find(|(_, same)| !same)
+        .map(|(setting, _)| setting)
+    } //
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+    use super::*;
+    use crate::error::FenError;
+    use crate::mock_engine::{MockEngine, commands_received, king_pawn_engine, mock_engine};
+    use std::fs;
+
+    #[test]
+    fn validate_reports_every_bad_option() {
+        let options = MockEngine::new("Options")
+            .with_uci_line("option name Hash type spin default 16 min 1 max 1024")
+            .with_uci_line("option name Skill Level type spin default 20 min 0 max 20")
+            .with_uci_line("option name Ponder type check default false")
+            .build();
+        let engine1 = options
+            .clone()
+            .with_option("Hash", "4096")
+            .with_option("skill level", "5")
+            .with_option("Ponder", "yes")
+            .with_option("Contempt", "10");
+        let engine2 = mock_engine("Plain", &[]);
+
+        let tournament = Tournament::new(2, engine1, engine2.clone(), TimeControl::Infinite);
+        let errors = tournament.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                EngineError::InvalidOptionValue {
+                    engine: "Options".to_string(),
+                    name: "Hash".to_string(),
+                    value: "4096".to_string(),
+                },
+                EngineError::InvalidOptionValue {
+                    engine: "Options".to_string(),
+                    name: "Ponder".to_string(),
+                    value: "yes".to_string(),
+                },
+                EngineError::UnknownOption {
+                    engine: "Options".to_string(),
+                    name: "Contempt".to_string(),
+                },
+            ]
+        );
+
+        let tournament =
+            Tournament::new(2, engine2.clone(), engine2.clone(), TimeControl::Infinite);
+        assert_eq!(tournament.validate(), Ok(()));
+
+        // options that pass are the ones the games are played with
+        let engine1 = options
+            .with_option("Hash", "64")
+            .with_option("skill level", "5");
+        let mut tournament =
+            Tournament::new(1, engine1.clone(), engine2, TimeControl::TimePerMove(10));
+        assert_eq!(tournament.validate(), Ok(()));
+        tournament.start();
+        let commands = commands_received(&engine1);
+        assert!(commands.contains(&"setoption name Hash value 64".to_string()));
+        assert!(commands.contains(&"setoption name skill level value 5".to_string()));
+    }
+
+    #[test]
+    fn oversubscription_is_an_error_only_when_denied() {
+        let engine1 = MockEngine::new("Greedy")
+            .with_uci_line("option name Threads type spin default 1 min 1 max 100000")
+            .build();
+        assert_eq!(engine1.threads(), 1);
+        let engine1 = engine1.with_option("Threads", "100000");
+        let engine2 = mock_engine("Plain", &[]);
+        assert_eq!(engine1.threads(), 100000);
+        assert_eq!(engine2.threads(), 1);
+
+        let tournament = Tournament::new(2, engine1, engine2, TimeControl::Infinite);
+        assert!(matches!(
+            tournament.check_threads(),
+            Err(EngineError::Oversubscribed {
+                threads: 100001,
+                ..
+            })
+        ));
+        assert_eq!(tournament.validate(), Ok(()));
+        let mut denied = tournament.deny_oversubscription(true);
+        let errors = denied.validate().unwrap_err();
+        assert!(matches!(errors[..], [EngineError::Oversubscribed { .. }]));
+        assert!(matches!(
+            denied.try_start(),
+            Err(EngineError::Oversubscribed { .. })
+        ));
+        assert!(denied.try_games().is_err());
+        assert!(denied.completed.is_empty());
+    }
+
+    #[test]
+    fn cancelled_tournament_returns_early() {
+        let engine1 = mock_engine("One", &["f2f3", "g2g4"]);
+        let engine2 = mock_engine("Two", &["e7e5", "d8h4"]);
+        let mut tournament = Tournament::new(100, engine1, engine2, TimeControl::TimePerMove(10));
+        tournament.cancel_token().store(true, Ordering::Relaxed);
+
+        let tournament_result = tournament.start();
+        assert_eq!(tournament_result.total_games, 0);
+        assert!(tournament_result.games_list.is_empty());
+    }
+
+    #[test]
+    fn unwritable_csv_out_still_plays_the_games() {
+        let engine1 = mock_engine("One", &["f2f3", "g2g4"]);
+        let engine2 = mock_engine("Two", &["e7e5", "d8h4"]);
+        let csv_out = std::path::Path::new(&engine1.path).join("games.csv");
+        let mut tournament = Tournament::new(1, engine1, engine2, TimeControl::TimePerMove(10))
+            .with_csv_out(&csv_out);
+
+        let tournament_result = tournament.try_start().unwrap();
+        assert_eq!(tournament_result.total_games, 1);
+        assert!(!csv_out.exists());
+    }
+
+    #[test]
+    fn progress_reports_an_eta() {
+        let progress = TournamentProgress::new(0, 10, Duration::from_secs(5));
+        assert_eq!(progress.eta, None);
+        let progress = TournamentProgress::new(4, 10, Duration::from_secs(8));
+        assert_eq!(progress.eta, Some(Duration::from_secs(12)));
+
+        let engine1 = mock_engine("One", &["f2f3", "g2g4", "f2f3", "g2g4"]);
+        let engine2 = mock_engine("Two", &["e7e5", "d8h4", "e7e5", "d8h4"]);
+        let mut tournament = Tournament::new(2, engine1, engine2, TimeControl::TimePerMove(10));
+        let mut reports = Vec::new();
+        let tournament_result = tournament.start_with_progress(|progress| reports.push(*progress));
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].games_played, 2);
+        assert_eq!(reports[1].eta, Some(Duration::ZERO));
+        assert!(tournament_result.elapsed() >= reports[1].elapsed);
+    }
+
+    #[test]
+    fn events_follow_the_games() {
+        let engine1 = mock_engine("One", &["f2f3", "g2g4"]);
+        let engine2 = mock_engine("Two", &["e7e5", "d8h4"]);
+        let (tx, rx) = mpsc::channel();
+        let mut tournament =
+            Tournament::new(1, engine1, engine2, TimeControl::TimePerMove(10)).with_events(tx);
+        tournament.start();
+
+        let events = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 6);
+        assert!(matches!(
+            &events[0],
+            TournamentEvent::GameStarted { round: 1, white, black } if white == "One" && black == "Two"
+        ));
+        let moves = events[1..5]
+            .iter()
+            .map(|event| match event {
+                TournamentEvent::Move { round: 1, uci, .. } => uci.as_str(),
+                other => panic!("expected a move, got {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(moves, ["f2f3", "e7e5", "g2g4", "d8h4"]);
+        match &events[5] {
+            TournamentEvent::GameFinished {
+                round,
+                progress,
+                standings,
+            } => {
+                assert_eq!(*round, 1);
+                assert_eq!(progress.games_played, 1);
+                assert_eq!(
+                    standings,
+                    &vec![("Two".to_string(), 1.0), ("One".to_string(), 0.0)]
+                );
+            }
+            other => panic!("expected the standings, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn openings_are_drawn_from_every_suite() {
+        let engine = mock_engine("Plain", &[]);
+        // one opening per file, each pushing that file's pawn
+        let suite = |files: &str| {
+            files
+                .chars()
+                .map(|file| Opening {
+                    fen: None,
+                    moves: vec![format!("{}2{}3", file, file)],
+                })
+                .collect::<Vec<_>>()
+        };
+        let openings = |tournament: &Tournament| {
+            (0..8)
+                .map(|round| {
+                    let index = tournament.opening_for_round(round).unwrap();
+                    tournament.openings[index].moves[0][..1].to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let tournament = |strategy| {
+            Tournament::new(8, engine.clone(), engine.clone(), TimeControl::Infinite)
+                .with_opening_suites(vec![suite("abc"), Vec::new(), suite("gh")], strategy)
+                .unwrap()
+        };
+
+        let sequential = tournament(OpeningStrategy::Sequential);
+        assert_eq!(
+            openings(&sequential),
+            ["a", "b", "c", "g", "h", "a", "b", "c"]
+        );
+        let round_robin = tournament(OpeningStrategy::RoundRobin).pair_openings(true);
+        assert_eq!(
+            openings(&round_robin),
+            ["a", "a", "g", "g", "b", "b", "h", "h"]
+        );
+
+        // the same seed gives the same draw, and paired rounds share their opening
+        let random = tournament(OpeningStrategy::Random)
+            .pair_openings(true)
+            .with_seed(7);
+        let drawn = openings(&random);
+        assert_eq!(drawn, openings(&random.clone()));
+        assert!(drawn.chunks(2).all(|pair| pair[0] == pair[1]));
+        assert_ne!(drawn, openings(&random.with_seed(8)));
+    }
+
+    // Engine that shuffles its king's knight out and back, whatever its color, so two of them
+    // draw by repetition. `extra` adds more `position -> move` cases ahead of the shuffle.
+    fn shuffler(name: &str, extra: &str) -> Engine {
+        MockEngine::new(name)
+            .with_case(r#"position*) position="$line" ;;"#)
+            .with_go(&format!(
+                r#"
+      case "$position" in
+{}
+        "position startpos"|*f6g8) echo "bestmove g1f3" ;;
+        *g8f6) echo "bestmove f3g1" ;;
+        *g1f3) echo "bestmove g8f6" ;;
+        *f3g1) echo "bestmove f6g8" ;;
+      esac"#,
+                extra
+            ))
+            .build()
+    }
+
+    // Opens 1. e4 as white and mates `weak` with 2. d4 3. Qh5#, and shuffles as black.
+    fn sharp() -> Engine {
+        shuffler(
+            "Sharp",
+            r#"        "position startpos") echo "bestmove e2e4" ;;
+        *f7f6) echo "bestmove d2d4" ;;
+        *g7g5) echo "bestmove d1h5" ;;"#,
+        )
+    }
+
+    // Answers 1. e4 with 1... f6 2... g5 and is mated by `sharp`, and shuffles as white.
+    fn weak() -> Engine {
+        shuffler(
+            "Weak",
+            r#"        *e2e4) echo "bestmove f7f6" ;;
+        *d2d4) echo "bestmove g7g5" ;;"#,
+        )
+    }
+
+    #[test]
+    fn invalid_openings_are_refused_before_the_match() {
+        let engine = mock_engine("Plain", &[]);
+        let openings = vec![
+            Opening::default(),
+            Opening {
+                fen: Some("8/8/8/8/8/8/8/4K3 w - - 0 1".to_string()),
+                moves: Vec::new(),
+            },
+        ];
+        let error = Tournament::new(2, engine.clone(), engine, TimeControl::Infinite)
+            .with_openings(openings)
+            .err();
+        assert_eq!(
+            error,
+            Some(OpeningError::InvalidFen {
+                index: 1,
+                error: FenError::MissingKing { white: false },
+            })
+        );
+    }
+
+    #[test]
+    fn opening_book_positions_are_played_with_both_colors() {
+        let book = OpeningBook::parse(
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1\n4k3/8/8/8/8/8/8/R3K3 w Q - id \"long\";",
+        )
+        .unwrap();
+        let one = mock_engine("One", &["e1e2"]);
+        let mut tournament = Tournament::new(
+            5,
+            one.clone(),
+            mock_engine("Two", &["e1e2"]),
+            TimeControl::TimePerMove(10),
+        )
+        .with_opening_book(&book)
+        .with_max_plies(Some(1));
+        let result = tournament.start();
+
+        let short = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let long = "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1";
+        let played = result
+            .games_list
+            .iter()
+            .map(|game| (game.start_fen().unwrap(), game.white()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            played,
+            [
+                (short, "One"),
+                (short, "Two"),
+                (long, "One"),
+                (long, "Two"),
+                (short, "One")
+            ]
+        );
+        assert!(
+            result
+                .games_list
+                .iter()
+                .all(|game| game.moves_list() == ["e1e2"])
+        );
+        assert!(commands_received(&one).contains(&format!("position fen {}", long)));
+        assert!(
+            result
+                .game_pgn(2)
+                .unwrap()
+                .contains(&format!("[FEN \"{}\"]", long))
+        );
+    }
+
+    #[test]
+    fn concurrent_games_are_handed_out_in_round_order() {
+        let slow = |name: &str| king_pawn_engine(name, Duration::from_millis(300));
+        let mut tournament = Tournament::new(4, slow("One"), slow("Two"), TimeControl::Depth(1))
+            .with_max_plies(Some(2))
+            .with_concurrency(4);
+        let start = Instant::now();
+        let result = tournament.start();
+        // one after the other the games would take at least 2.4s
+        assert!(start.elapsed() < Duration::from_millis(1800));
+        let whites = result
+            .games_list
+            .iter()
+            .map(|game| game.white())
+            .collect::<Vec<_>>();
+        assert_eq!(whites, ["One", "Two", "One", "Two"]);
+        assert!(result.games_list.iter().all(|game| {
+            game.reason() == Termination::MaxLength && game.moves_list() == ["e2e4", "e7e5"]
+        }));
+        assert_eq!(result.draws, 4);
+    }
+
+    #[test]
+    fn engine_that_cannot_start_ends_the_tournament() {
+        for concurrency in [1, 3] {
+            let engine1 = mock_engine("One", &[]);
+            let engine2 = mock_engine("Two", &[]);
+            // started once by `Engine::new`, then gone
+            fs::remove_file(&engine2.path).unwrap();
+            let mut tournament = Tournament::new(4, engine1, engine2, TimeControl::TimePerMove(10))
+                .with_concurrency(concurrency);
+            let result = tournament.start();
+            assert_eq!(result.total_games, 0, "concurrency {}", concurrency);
+        }
+    }
+
+    #[test]
+    fn sprt_stops_the_match_once_h1_is_accepted() {
+        // Sharp wins as white and draws as black
+        let mut tournament = Tournament::new(100, sharp(), weak(), TimeControl::TimePerMove(10))
+            .with_sprt(Sprt::new(0.0, 200.0, 0.05, 0.05));
+        let result = tournament.start();
+        // 5 wins and 4 draws take the ratio past 2.94
+        assert_eq!(result.total_games, 9);
+        assert_eq!(result.sprt_result(), Some(SprtResult::H1));
+
+        let mut tournament = Tournament::new(
+            2,
+            shuffler("One", ""),
+            shuffler("Two", ""),
+            TimeControl::TimePerMove(10),
+        )
+        .with_sprt(Sprt::new(0.0, 200.0, 0.05, 0.05));
+        let result = tournament.start();
+        assert_eq!(result.total_games, 2);
+        assert_eq!(result.sprt_result(), Some(SprtResult::Inconclusive));
+    }
+
+    #[test]
+    fn play_until_decisive_stops_at_the_first_win() {
+        let mut tournament = Tournament::new(
+            100,
+            shuffler("One", ""),
+            shuffler("Two", ""),
+            TimeControl::TimePerMove(10),
+        )
+        .with_mode(PairingMode::PlayUntilDecisive { max_games: 3 });
+        let result = tournament.start();
+        assert_eq!(result.total_games, 3);
+        assert_eq!(result.decisive_game(), None);
+
+        // Sharp opens 1. e4 as white and mates Weak, who answers with 1... f6 2... g5
+        let mut tournament = Tournament::new(100, weak(), sharp(), TimeControl::TimePerMove(10))
+            .with_mode(PairingMode::PlayUntilDecisive { max_games: 10 });
+        let result = tournament.start();
+        // Weak is white first and the shuffle is drawn; with colors swapped Sharp wins as white
+        assert_eq!(result.total_games, 2);
+        assert_eq!(result.games_list[0].white(), "Weak");
+        assert_eq!(result.games_list[0].reason(), Termination::Repetition);
+        assert_eq!(result.decisive_game(), Some((1, true)));
+        assert_eq!(result.games_list[1].winner(), "Sharp");
+    }
+
+    #[test]
+    fn first_to_stops_once_the_target_is_reached() {
+        // Weak draws as white and loses as black
+        let mut tournament = Tournament::new(100, weak(), sharp(), TimeControl::TimePerMove(10))
+            .with_mode(PairingMode::FirstTo { points: 2.5 });
+        let result = tournament.start();
+        // 0.5-0.5, 0.5-1.5, 1-2, 1-3
+        assert_eq!(result.total_games, 4);
+        assert_eq!(result.match_winner(), Some("Sharp"));
+        assert_eq!(
+            result.standings(),
+            [("Sharp".to_string(), 3.0), ("Weak".to_string(), 1.0)]
+        );
+
+        // two shufflers draw every game, so both reach 2 together
+        let mut tournament = Tournament::new(
+            100,
+            shuffler("One", ""),
+            shuffler("Two", ""),
+            TimeControl::TimePerMove(10),
+        )
+        .with_mode(PairingMode::FirstTo { points: 2.0 });
+        let result = tournament.start();
+        assert_eq!(result.total_games, 4);
+        assert_eq!(result.match_winner(), None);
+    }
+
+    #[test]
+    fn pgn_database_holds_every_round() {
+        // Weak draws as white and loses as black
+        let mut tournament = Tournament::new(2, sharp(), weak(), TimeControl::TimePerMove(10))
+            .with_pgn_tags(PgnTags::default().with_event("Test match"));
+        let result = tournament.start();
+
+        let mut database = Vec::new();
+        result.write_pgn(&mut database).unwrap();
+        let games = crate::pgn::parse_pgn(&String::from_utf8(database).unwrap()).unwrap();
+        assert_eq!(games.len(), 2);
+        for (game, (round, white, black, outcome)) in games.iter().zip([
+            ("1", "Sharp", "Weak", "1-0"),
+            ("2", "Weak", "Sharp", "1/2-1/2"),
+        ]) {
+            assert_eq!(game.tag("Event"), Some("Test match"));
+            assert_eq!(game.tag("Round"), Some(round));
+            assert_eq!(game.tag("White"), Some(white));
+            assert_eq!(game.tag("Black"), Some(black));
+            assert_eq!(game.tag("Result"), Some(outcome));
+            assert_eq!(game.result, outcome);
+        }
+    }
+
+    #[test]
+    fn starting_again_replays_the_tournament() {
+        let mut tournament = Tournament::new(
+            2,
+            mock_engine("One", &["f2f3", "g2g4"]),
+            mock_engine("Two", &["e7e5", "d8h4"]),
+            TimeControl::TimePerMove(10),
+        );
+        let first = tournament.start();
+        let second = tournament.start();
+        assert_eq!(second.total_games, 2);
+        assert_eq!(
+            (second.engine1_won, second.engine2_won, second.draws),
+            (first.engine1_won, first.engine2_won, first.draws)
+        );
+        assert_eq!(tournament.completed.len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_round_trips_the_match() {
+        // Weak draws as white and loses as black
+        let mut tournament = Tournament::new(2, sharp(), weak(), TimeControl::TimePerMove(10))
+            .with_pgn_tags(PgnTags::default().with_event("Test match"));
+        let result = tournament.start();
+
+        let loaded = TournamentResult::from_json(&result.to_json()).unwrap();
+        assert_eq!(loaded.engine1(), "Sharp");
+        assert_eq!(
+            (loaded.engine1_won(), loaded.draws(), loaded.engine2_won()),
+            (1, 1, 0)
+        );
+        assert_eq!(loaded.pgn_tags.event, "Test match");
+        assert_eq!(loaded.elapsed(), result.elapsed());
+        assert_eq!(loaded.games_list().len(), 2);
+        for (loaded, played) in loaded.games_list().iter().zip(result.games_list()) {
+            assert_eq!(loaded.moves_list(), played.moves_list());
+            assert_eq!(loaded.outcome(), played.outcome());
+            assert_eq!(loaded.reason(), played.reason());
+            assert!(loaded.replay().is_none());
+        }
+        assert!(TournamentResult::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn football_scoring_changes_the_standings() {
+        // Sharp won 3, Solid won 2, 7 draws
+        let mut tournament_result = TournamentResult::new(
+            "Sharp".to_string(),
+            "Solid".to_string(),
+            Vec::new(),
+            3,
+            2,
+            7,
+            12,
+        );
+        assert_eq!(
+            tournament_result.standings(),
+            [("Sharp".to_string(), 6.5), ("Solid".to_string(), 5.5)]
+        );
+
+        tournament_result.scoring = ScoringSystem {
+            win: 3.0,
+            draw: 1.0,
+            loss: 0.0,
+        };
+        assert_eq!(
+            tournament_result.standings(),
+            [("Sharp".to_string(), 16.0), ("Solid".to_string(), 13.0)]
+        );
+        // the counts the statistics work from are untouched
+        assert_eq!(tournament_result.engine1_won, 3);
+        assert_eq!(tournament_result.draws, 7);
+    }
+
+    #[test]
+    fn between_games_policy_decides_the_reset() {
+        for (policy, reset) in [
+            (BetweenGames::NewGame, Some("ucinewgame")),
+            (
+                BetweenGames::ClearHashOnly,
+                Some("setoption name Clear Hash"),
+            ),
+            (BetweenGames::Nothing, None),
+        ] {
+            let engine1 = mock_engine("One", &["f2f3", "g2g4"]);
+            let engine2 = mock_engine("Two", &["e7e5", "d8h4"]);
+            Tournament::new(1, engine1.clone(), engine2, TimeControl::TimePerMove(10))
+                .with_between_games(policy)
+                .start();
+
+            let commands = commands_received(&engine1);
+            let resets = commands
+                .iter()
+                .filter(|command| *command == "ucinewgame" || command.contains("Clear Hash"))
+                .collect::<Vec<_>>();
+            match reset {
+                Some(reset) => assert_eq!(resets, [reset], "{:?}", policy),
+                None => assert!(resets.is_empty(), "{:?}", policy),
+            }
+            // sent before the engine is given a position
+            let first_position = commands
+                .iter()
+                .position(|command| command.starts_with("position"))
+                .unwrap();
+            assert!(
+                reset.is_none_or(|reset| commands[..first_position].contains(&reset.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn resumed_tournament_ends_as_an_uninterrupted_one() {
+        let engines = || {
+            (
+                mock_engine("One", &["f2f3", "g2g4"]),
+                mock_engine("Two", &["e7e5", "d8h4"]),
+            )
+        };
+        let tournament = |(one, two): (Engine, Engine)| {
+            Tournament::new(10, one, two, TimeControl::TimePerMove(10)).with_seed(7)
+        };
+        let summary = |result: &TournamentResult| {
+            (
+                result.engine1_won,
+                result.engine2_won,
+                result.draws,
+                result.total_games,
+                result
+                    .games_list
+                    .iter()
+                    .map(|game| (game.white().to_string(), game.moves_list().to_vec()))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let uninterrupted = tournament(engines()).start();
+
+        let (one, two) = engines();
+        let dir = Path::new(&one.path).parent().unwrap().to_path_buf();
+        let (state, after_three) = (dir.join("state.json"), dir.join("after-3.json"));
+        tournament((one, two))
+            .with_state_file(&state)
+            .start_with_progress(|progress| {
+                if progress.games_played == 3 {
+                    fs::copy(&state, &after_three).unwrap();
+                }
+            });
+
+        let mut resumed = tournament(engines()).resume(&after_three).unwrap();
+        assert_eq!(resumed.resumed.len(), 3);
+        assert_eq!(summary(&resumed.start()), summary(&uninterrupted));
+        assert_eq!(summary(&resumed.start()), summary(&uninterrupted));
+
+        let (one, two) = engines();
+        let longer = Tournament::new(12, one, two, TimeControl::TimePerMove(10)).with_seed(7);
+        assert_eq!(
+            longer.resume(&after_three).err(),
+            Some(TournamentStateError::Mismatch(
+                "number of rounds".to_string()
+            ))
+        );
+        let tested = tournament(engines()).with_sprt(Sprt::new(0.0, 10.0, 0.05, 0.05));
+        assert_eq!(
+            tested.resume(&after_three).err(),
+            Some(TournamentStateError::Mismatch("SPRT".to_string()))
+        );
+    }
 }